@@ -0,0 +1,54 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use zredis::{BulkString, RespArray, RespDecode, RespEncode, RespFrame, RespMap};
+
+fn frames() -> Vec<(&'static str, RespFrame)> {
+    let small = BulkString::new("hello world").into();
+    let array = RespArray::new((0..100).map(RespFrame::from).collect::<Vec<_>>()).into();
+    let mut map = RespMap::new();
+    for i in 0..50 {
+        map.insert(
+            format!("field{}", i),
+            BulkString::new(format!("value{}", i)).into(),
+        );
+    }
+    vec![
+        ("bulk_string", small),
+        ("int_array_100", array),
+        ("map_50", map.into()),
+    ]
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, frame) in frames() {
+        let encoded = frame.encode();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &frame, |b, frame| {
+            b.iter(|| black_box(frame).encode())
+        });
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, frame) in frames() {
+        let encoded = frame.encode();
+        // a benchmark over a broken round trip measures nothing useful
+        let decoded = RespFrame::decode(&mut BytesMut::from(&encoded[..])).unwrap();
+        assert_eq!(decoded, frame, "round trip mismatch for {}", name);
+
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &encoded, |b, encoded| {
+            b.iter(|| {
+                let mut buf = BytesMut::from(&encoded[..]);
+                RespFrame::decode(black_box(&mut buf)).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,360 @@
+use super::{
+    fits_listpack, hash_fields, ordered_fields, string_reply, string_value, Backend, KeyType,
+    SetEncoding, StoredValue, ZSet,
+};
+use crate::{cmd::CommandError, BulkString, Nf64, RespArray, RespDecode, RespEncode, RespFrame};
+use bytes::BytesMut;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// DUMP payload: version, type, flags, [ttl], value
+//  - version 2 has hashes as field, value pairs, version 1 as a map. both restore
+//  - flags bit 0 says a remaining ttl in ms follows as a little endian i64
+//  - the value is the RESP encoding of the string, an array of field, value
+//    pairs for hashes and an array of members for sets and lists
+const DUMP_VERSION: u8 = 2;
+// hashes were a map, which couldn't hold binary field names
+const DUMP_VERSION_MAP_HASHES: u8 = 1;
+const FLAG_TTL: u8 = 1;
+const HEADER_LEN: usize = 3;
+const TTL_LEN: usize = 8;
+
+impl KeyType {
+    fn tag(&self) -> u8 {
+        match self {
+            KeyType::String => 0,
+            KeyType::Hash => 1,
+            KeyType::Set => 2,
+            KeyType::List => 3,
+            KeyType::ZSet => 4,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(KeyType::String),
+            1 => Some(KeyType::Hash),
+            2 => Some(KeyType::Set),
+            3 => Some(KeyType::List),
+            4 => Some(KeyType::ZSet),
+            _ => None,
+        }
+    }
+}
+
+impl Backend {
+    // serialized value of the key together with its remaining ttl, None if missing
+    pub fn dump(&self, key: &str) -> Option<Vec<u8>> {
+        let key_type = self.key_type(key)?;
+        let value: RespFrame = match key_type {
+            // integers go out as the text they were set with
+            KeyType::String => string_reply(self.map.get(key)?.value()).as_ref().clone(),
+            KeyType::Hash => {
+                let fields = self.hmap.get(key)?;
+                let mut items = Vec::new();
+                for (field, value) in ordered_fields(&fields) {
+                    items.push(BulkString::new(field).into());
+                    items.push(value);
+                }
+                RespArray::new(items).into()
+            }
+            KeyType::Set => RespArray::new(self.smembers(key)).into(),
+            KeyType::List => {
+                RespArray::new(self.list.get(key)?.iter().cloned().collect::<Vec<_>>()).into()
+            }
+            // member, score, member, score, ...
+            KeyType::ZSet => {
+                let mut items = Vec::new();
+                for (member, score) in self.zset.get(key)?.iter() {
+                    items.push(BulkString::new(member).into());
+                    items.push(Nf64::new(score).into());
+                }
+                RespArray::new(items).into()
+            }
+        };
+
+        let ttl = self.expire_deadline(key).map(|deadline| {
+            deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis() as i64
+        });
+        let mut payload = vec![DUMP_VERSION, key_type.tag(), 0];
+        if let Some(ttl) = ttl {
+            payload[2] |= FLAG_TTL;
+            payload.extend_from_slice(&ttl.to_le_bytes());
+        }
+        payload.extend_from_slice(&value.encode());
+        Some(payload)
+    }
+
+    // `deadline` overrides the ttl stored in the payload
+    pub fn restore(
+        &self,
+        key: String,
+        payload: &[u8],
+        deadline: Option<Instant>,
+        replace: bool,
+    ) -> Result<(), CommandError> {
+        let invalid = || {
+            CommandError::InvalidArgument("DUMP payload version or checksum are wrong".to_string())
+        };
+        let [version, tag, flags, ..] = *payload else {
+            return Err(invalid());
+        };
+        let key_type = KeyType::from_tag(tag)
+            .filter(|_| version == DUMP_VERSION || version == DUMP_VERSION_MAP_HASHES);
+        let key_type = key_type.ok_or_else(invalid)?;
+
+        let mut rest = &payload[HEADER_LEN..];
+        let mut stored_deadline = None;
+        if flags & FLAG_TTL != 0 {
+            let ttl: [u8; TTL_LEN] = rest
+                .get(..TTL_LEN)
+                .and_then(|ttl| ttl.try_into().ok())
+                .ok_or_else(invalid)?;
+            let ttl = i64::from_le_bytes(ttl).max(0) as u64;
+            stored_deadline = Instant::now().checked_add(Duration::from_millis(ttl));
+            rest = &rest[TTL_LEN..];
+        }
+        let mut buf = BytesMut::from(rest);
+        let value = RespFrame::decode(&mut buf).map_err(|_| invalid())?;
+        if !buf.is_empty() {
+            return Err(invalid());
+        }
+
+        let value = match (key_type, value) {
+            (KeyType::String, value) => StoredValue::String(Arc::new(string_value(value))),
+            (KeyType::Hash, RespFrame::Array(items)) => {
+                let mut fields = Vec::with_capacity(items.len() / 2);
+                for pair in items.chunks(2) {
+                    match pair {
+                        [RespFrame::BulkString(field), value] => {
+                            fields.push((field.to_vec(), value.clone()))
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                StoredValue::Hash(hash_fields(fields))
+            }
+            (KeyType::Hash, RespFrame::Map(map)) => StoredValue::Hash(hash_fields(
+                map.entries().map(|(k, v)| (k.to_vec(), v.clone())),
+            )),
+            (KeyType::Set, RespFrame::Array(members)) => {
+                StoredValue::Set(members.0.into_iter().collect())
+            }
+            (KeyType::List, RespFrame::Array(items)) => {
+                StoredValue::List(items.0.into_iter().collect())
+            }
+            (KeyType::ZSet, RespFrame::Array(items)) => {
+                let mut zset = ZSet::default();
+                for pair in items.chunks(2) {
+                    match pair {
+                        [RespFrame::BulkString(member), RespFrame::Double(score)] => {
+                            let member =
+                                String::from_utf8(member.to_vec()).map_err(|_| invalid())?;
+                            zset.insert(member, **score);
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                StoredValue::ZSet(zset)
+            }
+            _ => return Err(invalid()),
+        };
+
+        if self.exists(&key) {
+            if !replace {
+                return Err(CommandError::BusyKey);
+            }
+            self.del(&key);
+        }
+        self.insert_value(key.clone(), value);
+        if let Some(deadline) = deadline.or(stored_deadline) {
+            self.expire_at(&key, deadline);
+        }
+        Ok(())
+    }
+
+    // moves the key and its ttl to `dest`, false if the key doesn't exist here
+    pub fn migrate_key(
+        &self,
+        key: &str,
+        dest: &Backend,
+        replace: bool,
+    ) -> Result<bool, CommandError> {
+        if !self.exists(key) {
+            return Ok(false);
+        }
+        if dest.exists(key) {
+            if !replace {
+                return Err(CommandError::BusyKey);
+            }
+            dest.del(key);
+        }
+        let deadline = self.expire_deadline(key);
+        for value in self.take(key) {
+            dest.insert_value(key.to_string(), value);
+        }
+        if let Some(deadline) = deadline {
+            dest.expire_at(key, deadline);
+        }
+        Ok(true)
+    }
+
+    // MOVE to another database of this server, false if the key is missing here
+    // or already taken there
+    pub fn move_key(&self, key: &str, db: usize) -> Result<bool, CommandError> {
+        let Some(dest) = self.database(db) else {
+            return Err(CommandError::InvalidArgument(
+                "DB index is out of range".to_string(),
+            ));
+        };
+        if Arc::ptr_eq(&self.db, &dest.db) {
+            return Err(CommandError::InvalidArgument(
+                "source and destination objects are the same".to_string(),
+            ));
+        }
+        match self.migrate_key(key, &dest, false) {
+            Err(CommandError::BusyKey) => Ok(false),
+            moved => moved,
+        }
+    }
+
+    // RENAME, the ttl goes along with the value and whatever `dest` held is dropped.
+    // false if the key doesn't exist
+    pub fn rename_key(&self, key: &str, dest: &str) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        if key == dest {
+            return true;
+        }
+        let deadline = self.expire_deadline(key);
+        let values = self.take(key);
+        self.del(dest);
+        // the deadline is in place before the value, so `dest` is never seen without it
+        if let Some(deadline) = deadline {
+            self.expires.insert(dest.to_string(), deadline);
+        }
+        for value in values {
+            self.insert_value(dest.to_string(), value);
+        }
+        true
+    }
+
+    // COPY within this database, the copy starts out without a ttl. false if the key
+    // doesn't exist, or `dest` does and isn't to be replaced
+    pub fn copy_key(&self, key: &str, dest: &str, replace: bool) -> bool {
+        let Some(value) = self.cloned_value(key) else {
+            return false;
+        };
+        if self.exists(dest) {
+            if !replace {
+                return false;
+            }
+            self.del(dest);
+        }
+        self.insert_value(dest.to_string(), value);
+        true
+    }
+
+    fn cloned_value(&self, key: &str) -> Option<StoredValue> {
+        Some(match self.key_type(key)? {
+            KeyType::String => StoredValue::String(self.map.get(key)?.value().clone()),
+            KeyType::Hash => StoredValue::Hash(self.hmap.get(key)?.value().clone()),
+            KeyType::Set => StoredValue::Set(self.dset.get(key)?.value().clone()),
+            KeyType::List => StoredValue::List(self.list.get(key)?.value().clone()),
+            KeyType::ZSet => StoredValue::ZSet(self.zset.get(key)?.value().clone()),
+        })
+    }
+
+    fn insert_value(&self, key: String, value: StoredValue) {
+        self.touch(&key);
+        self.changed();
+        match value {
+            StoredValue::String(value) => {
+                self.map.insert(key, value);
+            }
+            StoredValue::Hash(hash) => {
+                let large = hash.len() > self.encoding.hash_max_listpack_entries()
+                    || hash.iter().any(|e| !fits_listpack(e.key(), &e.value().1));
+                if large {
+                    self.hashtable_encoded.insert(key.clone());
+                }
+                self.hmap.insert(key, hash);
+            }
+            StoredValue::Set(set) => {
+                let members: Vec<RespFrame> = set.iter().map(|m| m.key().clone()).collect();
+                let encoding = SetEncoding::of(&members, self.encoding.set_max_intset_entries());
+                self.set_encoding.insert(key.clone(), encoding);
+                self.dset.insert(key, set);
+            }
+            StoredValue::List(list) => {
+                self.list.insert(key.clone(), list);
+                self.notify_list(&key);
+            }
+            StoredValue::ZSet(zset) => {
+                self.zset.insert(key, zset);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+    use anyhow::Result;
+
+    #[test]
+    fn test_migrate_hash() -> Result<()> {
+        let src = Backend::new();
+        let dest = Backend::new();
+        src.hset(
+            "h".to_string(),
+            "f1".to_string(),
+            BulkString::new("v1").into(),
+        );
+        src.hset(
+            "h".to_string(),
+            "f2".to_string(),
+            BulkString::new("v2").into(),
+        );
+        let deadline = Instant::now() + Duration::from_secs(100);
+        src.expire_at("h", deadline);
+
+        assert!(src.migrate_key("h", &dest, false)?);
+        assert!(!src.exists("h"));
+        assert_eq!(dest.key_type("h"), Some(KeyType::Hash));
+        assert_eq!(dest.hget("h", "f1"), Some(BulkString::new("v1").into()));
+        assert_eq!(dest.hget("h", "f2"), Some(BulkString::new("v2").into()));
+        assert_eq!(dest.expire_deadline("h"), Some(deadline));
+
+        assert!(!src.migrate_key("h", &dest, false)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_existing_destination() -> Result<()> {
+        let src = Backend::new();
+        let dest = Backend::new();
+        src.set("k".to_string(), BulkString::new("new").into());
+        dest.set("k".to_string(), BulkString::new("old").into());
+
+        assert!(matches!(
+            src.migrate_key("k", &dest, false),
+            Err(CommandError::BusyKey)
+        ));
+        assert!(src.exists("k"));
+
+        assert!(src.migrate_key("k", &dest, true)?);
+        assert!(!src.exists("k"));
+        assert_eq!(
+            dest.get("k")?.as_deref(),
+            Some(&BulkString::new("new").into())
+        );
+
+        Ok(())
+    }
+}
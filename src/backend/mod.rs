@@ -1,29 +1,298 @@
-use crate::{RespFrame, SimpleString};
+mod dump;
+mod glob;
+mod pubsub;
+mod zset;
+
+pub use glob::*;
+pub use pubsub::*;
+pub use zset::{Aggregate, ZRangeBy, ZSet};
+
+use crate::{cmd::CommandError, BulkString, RespFrame, SimpleString};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use dashmap::DashSet;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+// random picks RANDOMKEY makes before it goes through the remaining keys in order
+const RANDOMKEY_MAX_TRIES: usize = 100;
+
+// the largest HRANDFIELD count either way, same as redis
+pub const HRANDFIELD_MAX_COUNT: i64 = i64::MAX / 2;
+
+// how many repeated fields hrandfield makes room for before it starts picking
+const MAX_HRANDFIELD_RESERVE: usize = 1024;
+
+// redis' default `databases 16`
+pub const DEFAULT_DATABASES: usize = 16;
 
+// redis' default `proto-max-bulk-len 512mb`
+pub const DEFAULT_PROTO_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+// a hash's fields, binary safe like keys' values, each with the sequence number
+// of its first insert so replies can list them in insertion order
+pub(crate) type HashFields = DashMap<Vec<u8>, (u64, RespFrame)>;
+
+static FIELD_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// gets the database index and request of every command that changed data, see
+// Backend::set_write_hook
+pub type WriteHook = Arc<dyn Fn(usize, &RespFrame) + Send + Sync>;
+
+// the hook can't be printed, only whether there is one
+#[derive(Default)]
+pub(crate) struct WriteHookSlot(RwLock<Option<WriteHook>>);
+
+// a handle on one database of the server, cloning it is cheap
 #[derive(Debug, Clone)]
-pub struct Backend(Arc<BackendInner>);
+pub struct Backend {
+    db: Arc<BackendInner>,
+    // every database of the server, shared by all handles
+    databases: Arc<RwLock<Vec<Arc<BackendInner>>>>,
+    // changes made through this handle and its clones, so a request can tell
+    // whether it wrote anything. database() hands out a handle with its own count
+    changes: Arc<AtomicU64>,
+}
 
 #[derive(Debug)]
 pub struct BackendInner {
-    pub(crate) map: DashMap<String, RespFrame>,
-    pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
+    // strings are shared, a read hands out another reference instead of a copy
+    pub(crate) map: DashMap<String, Arc<RespFrame>>,
+    pub(crate) hmap: DashMap<String, HashFields>,
     pub(crate) dset: DashMap<String, DashSet<RespFrame>>,
+    // encoding OBJECT ENCODING reports for each set, it only ever gets bigger
+    pub(crate) set_encoding: DashMap<String, SetEncoding>,
+    // hashes that went past the listpack limits, they stay a hashtable
+    pub(crate) hashtable_encoded: DashSet<String>,
+    pub(crate) list: DashMap<String, VecDeque<RespFrame>>,
+    pub(crate) zset: DashMap<String, ZSet>,
+    // wake up blocked list pops when a key gets new elements. there is only an entry
+    // while someone waits on the key, see ListWaiter
+    pub(crate) list_notify: DashMap<String, Arc<Notify>>,
+    // key -> deadline, keys without an entry never expire
+    pub(crate) expires: DashMap<String, Instant>,
+    // key -> last read or write, what OBJECT IDLETIME reports
+    pub(crate) last_access: DashMap<String, Instant>,
+    // channels, the read only flag and the password are server wide, every database shares them
+    pub(crate) pubsub: Arc<PubSubRegistry>,
+    // like a read only replica, write commands are refused
+    pub(crate) read_only: Arc<AtomicBool>,
+    // the default user's password, connections must AUTH first when there is one
+    pub(crate) password: Arc<RwLock<Option<String>>>,
+    // whether expire_cycle reclaims expired keys, otherwise only access does
+    pub(crate) active_expire: Arc<AtomicBool>,
+    // HGETALL, HKEYS and HVALS list fields in insertion order instead of sorted by
+    // name, off by default
+    pub(crate) hash_insertion_order: Arc<AtomicBool>,
+    // the longest a string may get by APPEND, SETRANGE or SETBIT
+    pub(crate) proto_max_bulk_len: Arc<AtomicUsize>,
+    // counters INFO reports, for the whole server
+    pub(crate) stats: Arc<ServerStats>,
+    // what OBJECT ENCODING goes by, for the whole server
+    pub(crate) encoding: Arc<EncodingThresholds>,
+    // told about every successful write, in any database
+    pub(crate) on_write: Arc<WriteHookSlot>,
+    // when the server was created, the same for every database
+    pub(crate) start_time: Instant,
+}
+
+// a blocked pop's interest in a key, dropping it (the pop returned, or the client went
+// away) removes the key's notifier unless someone else still waits on it
+#[derive(Debug)]
+pub struct ListWaiter {
+    backend: Backend,
+    key: String,
+    notify: Arc<Notify>,
+}
+
+impl ListWaiter {
+    pub fn notified(&self) -> Notified<'_> {
+        self.notify.notified()
+    }
+}
+
+impl Drop for ListWaiter {
+    fn drop(&mut self) {
+        // the map holds one reference and this waiter the other. a new waiter
+        // clones it under the same lock, so it can't slip in between
+        self.backend
+            .list_notify
+            .remove_if(&self.key, |_, notify| Arc::strong_count(notify) == 2);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    // commands refused because the key holds another type
+    pub(crate) wrongtype_errors: AtomicU64,
+    // keys removed because their ttl ran out, lazily or by the expire cycle
+    pub(crate) expired_keys: AtomicU64,
+}
+
+// a value taken out of, or about to go into, one of the typed maps
+#[allow(dead_code)]
+#[derive(Debug)]
+pub(crate) enum StoredValue {
+    String(Arc<RespFrame>),
+    Hash(HashFields),
+    Set(DashSet<RespFrame>),
+    List(VecDeque<RespFrame>),
+    ZSet(ZSet),
+}
+
+// what TYPE reports for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    Hash,
+    Set,
+    List,
+    ZSet,
+}
+
+impl KeyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::String => "string",
+            KeyType::Hash => "hash",
+            KeyType::Set => "set",
+            KeyType::List => "list",
+            KeyType::ZSet => "zset",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "string" => Some(KeyType::String),
+            "hash" => Some(KeyType::Hash),
+            "set" => Some(KeyType::Set),
+            "list" => Some(KeyType::List),
+            "zset" => Some(KeyType::ZSet),
+            _ => None,
+        }
+    }
+}
+
+// same thresholds as redis' set-max-listpack-entries and set-max-listpack-value,
+// the configurable ones are in EncodingThresholds
+const SET_MAX_LISTPACK_ENTRIES: usize = 128;
+const SET_MAX_LISTPACK_VALUE: usize = 64;
+
+// the sizes up to which OBJECT ENCODING reports the compact encodings, named and
+// defaulting like redis' settings. list-max-listpack-size only takes an entry
+// count, not redis' negative byte size classes
+#[derive(Debug)]
+pub struct EncodingThresholds {
+    hash_max_listpack_entries: AtomicUsize,
+    set_max_intset_entries: AtomicUsize,
+    list_max_listpack_size: AtomicUsize,
+}
+
+impl Default for EncodingThresholds {
+    fn default() -> Self {
+        Self {
+            hash_max_listpack_entries: AtomicUsize::new(128),
+            set_max_intset_entries: AtomicUsize::new(512),
+            list_max_listpack_size: AtomicUsize::new(128),
+        }
+    }
+}
+
+impl EncodingThresholds {
+    fn setting(&self, name: &str) -> Option<&AtomicUsize> {
+        match name.to_ascii_lowercase().as_str() {
+            "hash-max-listpack-entries" => Some(&self.hash_max_listpack_entries),
+            "set-max-intset-entries" => Some(&self.set_max_intset_entries),
+            "list-max-listpack-size" => Some(&self.list_max_listpack_size),
+            _ => None,
+        }
+    }
+
+    fn hash_max_listpack_entries(&self) -> usize {
+        self.hash_max_listpack_entries.load(Ordering::Relaxed)
+    }
+
+    fn set_max_intset_entries(&self) -> usize {
+        self.set_max_intset_entries.load(Ordering::Relaxed)
+    }
+
+    fn list_max_listpack_size(&self) -> usize {
+        self.list_max_listpack_size.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SetEncoding {
+    IntSet,
+    ListPack,
+    HashTable,
+}
+
+impl SetEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SetEncoding::IntSet => "intset",
+            SetEncoding::ListPack => "listpack",
+            SetEncoding::HashTable => "hashtable",
+        }
+    }
+
+    // the encoding a set needs after `member` was added and it holds `len` members
+    fn grow(self, member: &RespFrame, len: usize, max_intset: usize) -> Self {
+        let needed = if is_set_int(member) && len <= max_intset {
+            SetEncoding::IntSet
+        } else if len <= SET_MAX_LISTPACK_ENTRIES
+            && frame_bytes(member).len() <= SET_MAX_LISTPACK_VALUE
+        {
+            SetEncoding::ListPack
+        } else {
+            SetEncoding::HashTable
+        };
+        self.max(needed)
+    }
+
+    fn of<'a>(members: impl IntoIterator<Item = &'a RespFrame>, max_intset: usize) -> Self {
+        members
+            .into_iter()
+            .enumerate()
+            .fold(SetEncoding::IntSet, |enc, (i, member)| {
+                enc.grow(member, i + 1, max_intset)
+            })
+    }
 }
 
 impl Deref for Backend {
     type Target = BackendInner;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.db
     }
 }
 
 impl Default for Backend {
     fn default() -> Self {
-        Self(Arc::new(BackendInner::default()))
+        Self::with_databases(DEFAULT_DATABASES)
+    }
+}
+
+impl fmt::Debug for WriteHookSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let installed = self
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some();
+        f.debug_struct("WriteHookSlot")
+            .field("installed", &installed)
+            .finish()
     }
 }
 
@@ -33,6 +302,23 @@ impl Default for BackendInner {
             map: DashMap::new(),
             hmap: DashMap::new(),
             dset: DashMap::new(),
+            set_encoding: DashMap::new(),
+            hashtable_encoded: DashSet::new(),
+            list: DashMap::new(),
+            zset: DashMap::new(),
+            list_notify: DashMap::new(),
+            expires: DashMap::new(),
+            last_access: DashMap::new(),
+            pubsub: Arc::new(PubSubRegistry::default()),
+            read_only: Arc::new(AtomicBool::new(false)),
+            password: Arc::new(RwLock::new(None)),
+            active_expire: Arc::new(AtomicBool::new(true)),
+            hash_insertion_order: Arc::new(AtomicBool::new(false)),
+            proto_max_bulk_len: Arc::new(AtomicUsize::new(DEFAULT_PROTO_MAX_BULK_LEN)),
+            stats: Arc::new(ServerStats::default()),
+            encoding: Arc::new(EncodingThresholds::default()),
+            on_write: Arc::new(WriteHookSlot::default()),
+            start_time: Instant::now(),
         }
     }
 }
@@ -42,56 +328,876 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        //self.map.get(key).map(|v| v.value().clone())
-        self.map.get(key).map(|v| v.value().clone())
+    // a server with `n` databases (at least one), the handle is on database 0
+    pub fn with_databases(n: usize) -> Self {
+        let first = Arc::new(BackendInner::default());
+        let mut databases = vec![first.clone()];
+        databases.extend((1..n).map(|_| {
+            Arc::new(BackendInner {
+                pubsub: first.pubsub.clone(),
+                read_only: first.read_only.clone(),
+                password: first.password.clone(),
+                active_expire: first.active_expire.clone(),
+                hash_insertion_order: first.hash_insertion_order.clone(),
+                proto_max_bulk_len: first.proto_max_bulk_len.clone(),
+                stats: first.stats.clone(),
+                encoding: first.encoding.clone(),
+                on_write: first.on_write.clone(),
+                start_time: first.start_time,
+                ..Default::default()
+            })
+        }));
+        Self {
+            db: first,
+            databases: Arc::new(RwLock::new(databases)),
+            changes: Arc::default(),
+        }
+    }
+
+    pub fn num_databases(&self) -> usize {
+        self.databases
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    // every handle sees the other database's contents from its next lookup on,
+    // false if either index is out of range
+    pub fn swap_databases(&self, a: usize, b: usize) -> bool {
+        let mut databases = self
+            .databases
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if a >= databases.len() || b >= databases.len() {
+            return false;
+        }
+        databases.swap(a, b);
+        self.changed();
+        true
+    }
+
+    // a handle on another database of the same server, None if out of range
+    pub fn database(&self, index: usize) -> Option<Backend> {
+        let databases = self
+            .databases
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(Self {
+            db: databases.get(index)?.clone(),
+            databases: self.databases.clone(),
+            changes: Arc::default(),
+        })
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    // for replication or change data capture experiments: after a command changed
+    // data, `hook` is called with the index of the database it ran against and its
+    // request. there is one for the whole server, setting it replaces the last one
+    pub fn set_write_hook(&self, hook: impl Fn(usize, &RespFrame) + Send + Sync + 'static) {
+        *self
+            .on_write
+            .0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(hook));
+    }
+
+    pub fn has_write_hook(&self) -> bool {
+        self.write_hook().is_some()
+    }
+
+    // tells the hook about a request that ran on this handle, if it changed anything
+    // since `changes` was read. `request` is None when there was no hook to keep it for
+    pub(crate) fn notify_write(&self, db: usize, changes: u64, request: Option<RespFrame>) {
+        if self.changes() == changes {
+            return;
+        }
+        // called without the lock held, so the hook may use the backend
+        if let (Some(hook), Some(request)) = (self.write_hook(), request) {
+            hook(db, &request);
+        }
+    }
+
+    // the index this handle's database has now, SWAPDB can move it
+    pub(crate) fn index(&self) -> usize {
+        self.databases
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .position(|db| Arc::ptr_eq(db, &self.db))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn changes(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+
+    fn changed(&self) {
+        self.changes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn write_hook(&self) -> Option<WriteHook> {
+        self.on_write
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_hash_insertion_order(&self, enabled: bool) {
+        self.hash_insertion_order.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn hash_insertion_order(&self) -> bool {
+        self.hash_insertion_order.load(Ordering::Relaxed)
+    }
+
+    pub fn set_proto_max_bulk_len(&self, len: usize) {
+        self.proto_max_bulk_len.store(len, Ordering::Relaxed);
+    }
+
+    pub fn proto_max_bulk_len(&self) -> usize {
+        self.proto_max_bulk_len.load(Ordering::Relaxed)
+    }
+
+    // what CONFIG SET of hash-max-listpack-entries, set-max-intset-entries or
+    // list-max-listpack-size does in redis, false for any other name. there is no
+    // CONFIG command, so only code embedding the server can change them. hashes
+    // and sets already past a threshold keep their encoding, like in redis
+    pub fn set_encoding_threshold(&self, name: &str, value: usize) -> bool {
+        match self.encoding.setting(name) {
+            Some(setting) => {
+                setting.store(value, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn encoding_threshold(&self, name: &str) -> Option<usize> {
+        Some(self.encoding.setting(name)?.load(Ordering::Relaxed))
+    }
+
+    // like redis' requirepass, None lets every connection in
+    pub fn set_password(&self, password: Option<String>) {
+        *self
+            .password
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = password;
+    }
+
+    pub fn requires_auth(&self) -> bool {
+        self.password
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    // there are no ACL users, only the default one, and without a password it takes any
+    pub fn check_password(&self, username: &str, password: &str) -> bool {
+        username == "default"
+            && self
+                .password
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_deref()
+                .is_none_or(|expected| expected == password)
+    }
+
+    // a key holding a hash, set or list is an error, not a missing string
+    pub fn get(&self, key: &str) -> Result<Option<Arc<RespFrame>>, CommandError> {
+        self.get_typed(key, KeyType::String, |b| {
+            b.map.get(key).map(|v| string_reply(v.value()))
+        })
     }
 
     pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+        self.expires.remove(&key);
+        self.touch(&key);
+        self.changed();
+        self.map.insert(key, Arc::new(string_value(value)));
+    }
+
+    // SET with its NX, XX and GET options: whether the value was written, and the
+    // string it replaced when `get` asks for it. NX and XX count keys of any type,
+    // GET refuses other types without writing anything
+    pub fn set_with(
+        &self,
+        key: String,
+        value: RespFrame,
+        nx: bool,
+        xx: bool,
+        get: bool,
+    ) -> Result<(bool, Option<Arc<RespFrame>>), CommandError> {
+        if get {
+            self.check_type(&key, KeyType::String)?;
+        }
+        let other_type = matches!(self.key_type(&key), Some(ty) if ty != KeyType::String);
+        match self.map.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let old = get.then(|| string_reply(entry.get()));
+                if nx {
+                    return Ok((false, old));
+                }
+                self.expires.remove(entry.key());
+                self.touch(entry.key());
+                self.changed();
+                entry.insert(Arc::new(string_value(value)));
+                Ok((true, old))
+            }
+            Entry::Vacant(entry) => {
+                if (nx && other_type) || (xx && !other_type) {
+                    return Ok((false, None));
+                }
+                self.expires.remove(entry.key());
+                self.touch(entry.key());
+                self.changed();
+                entry.insert(Arc::new(string_value(value)));
+                Ok((true, None))
+            }
+        }
+    }
+
+    // INCR, DECR: the new value. the ttl is kept, and a value that isn't an
+    // integer, or would overflow, is left alone
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, CommandError> {
+        self.check_type(key, KeyType::String)?;
+        self.prepare_write(key);
+        let mut entry = self
+            .map
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(RespFrame::Integer(0)));
+        let current = match entry.value().as_ref() {
+            RespFrame::Integer(n) => *n,
+            // like redis, "0100" or "+5" aren't integers even though they parse as one
+            v => canonical_int(&frame_bytes(v)).ok_or_else(|| {
+                CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+            })?,
+        };
+        let next = current.checked_add(delta).ok_or_else(|| {
+            CommandError::InvalidArgument("increment or decrement would overflow".to_string())
+        })?;
+        *entry = Arc::new(RespFrame::Integer(next));
+        self.changed();
+        Ok(next)
+    }
+
+    // APPEND, the new length
+    pub fn append(&self, key: &str, value: &[u8]) -> Result<usize, CommandError> {
+        self.update_string(key, |bytes| {
+            self.check_string_len(bytes.len() + value.len())?;
+            bytes.extend_from_slice(value);
+            Ok(bytes.len())
+        })
+    }
+
+    // SETRANGE, the new length. the gap up to `offset` is filled with zero bytes
+    pub fn setrange(&self, key: &str, offset: usize, value: &[u8]) -> Result<usize, CommandError> {
+        // nothing to write, a missing key isn't created
+        if value.is_empty() {
+            return Ok(self.get(key)?.map_or(0, |v| frame_bytes(&v).len()));
+        }
+        self.update_string(key, |bytes| {
+            let end = offset.saturating_add(value.len());
+            self.check_string_len(end)?;
+            if bytes.len() < end {
+                bytes.resize(end, 0);
+            }
+            bytes[offset..end].copy_from_slice(value);
+            Ok(bytes.len())
+        })
+    }
+
+    // SETBIT, the bit's previous value. bit 0 is the most significant of the first byte
+    pub fn setbit(&self, key: &str, offset: u64, bit: bool) -> Result<bool, CommandError> {
+        let byte = usize::try_from(offset / 8).unwrap_or(usize::MAX);
+        self.update_string(key, |bytes| {
+            self.check_string_len(byte.saturating_add(1))?;
+            if bytes.len() <= byte {
+                bytes.resize(byte + 1, 0);
+            }
+            let mask = 0x80u8 >> (offset % 8);
+            let old = bytes[byte] & mask != 0;
+            if bit {
+                bytes[byte] |= mask;
+            } else {
+                bytes[byte] &= !mask;
+            }
+            Ok(old)
+        })
+    }
+
+    fn check_string_len(&self, len: usize) -> Result<(), CommandError> {
+        if len > self.proto_max_bulk_len() {
+            return Err(CommandError::StringTooLong);
+        }
+        Ok(())
+    }
+
+    // changes a string in place, a missing key starts out empty. unlike SET the
+    // ttl is kept. `update` must fail before it changes anything, the value and
+    // the key's access time then stay as they were
+    fn update_string<T>(
+        &self,
+        key: &str,
+        update: impl FnOnce(&mut Vec<u8>) -> Result<T, CommandError>,
+    ) -> Result<T, CommandError> {
+        self.check_type(key, KeyType::String)?;
+        self.is_expired(key);
+        let ret = match self.map.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => match Arc::make_mut(entry.get_mut()) {
+                // the bytes are changed where they are, only copied if a reply still
+                // holds them, so a string built with APPEND isn't copied every time
+                RespFrame::BulkString(bytes) => update(&mut bytes.0)?,
+                // an integer becomes the text it stands for
+                value => {
+                    let mut bytes = frame_bytes(value);
+                    let ret = update(&mut bytes)?;
+                    *value = BulkString::new(bytes).into();
+                    ret
+                }
+            },
+            Entry::Vacant(entry) => {
+                let mut bytes = Vec::new();
+                let ret = update(&mut bytes)?;
+                entry.insert(Arc::new(BulkString::new(bytes).into()));
+                ret
+            }
+        };
+        self.touch(key);
+        self.changed();
+        Ok(ret)
+    }
+
+    pub fn exists(&self, key: &str) -> bool {
+        if self.is_expired(key) {
+            return false;
+        }
+        self.map.contains_key(key)
+            || self.hmap.contains_key(key)
+            || self.dset.contains_key(key)
+            || self.list.contains_key(key)
+            || self.zset.contains_key(key)
+    }
+
+    pub fn key_type(&self, key: &str) -> Option<KeyType> {
+        if self.is_expired(key) {
+            return None;
+        }
+        if self.map.contains_key(key) {
+            Some(KeyType::String)
+        } else if self.hmap.contains_key(key) {
+            Some(KeyType::Hash)
+        } else if self.dset.contains_key(key) {
+            Some(KeyType::Set)
+        } else if self.list.contains_key(key) {
+            Some(KeyType::List)
+        } else if self.zset.contains_key(key) {
+            Some(KeyType::ZSet)
+        } else {
+            None
+        }
+    }
+
+    // WRONGTYPE unless the key is missing or holds `expected`
+    pub fn check_type(&self, key: &str, expected: KeyType) -> Result<(), CommandError> {
+        match self.key_type(key) {
+            Some(ty) if ty != expected => {
+                self.stats.wrongtype_errors.fetch_add(1, Ordering::Relaxed);
+                Err(CommandError::WrongType)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn wrongtype_errors(&self) -> u64 {
+        self.stats.wrongtype_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.stats.expired_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.uptime().as_secs()
+    }
+
+    // shared by the typed reads: check the kind of value first, then run `get`
+    pub fn get_typed<T>(
+        &self,
+        key: &str,
+        expected: KeyType,
+        get: impl FnOnce(&Self) -> Option<T>,
+    ) -> Result<Option<T>, CommandError> {
+        self.check_type(key, expected)?;
+        let value = get(self);
+        if value.is_some() {
+            self.touch(key);
+        }
+        Ok(value)
+    }
+
+    // empties this database, the other ones and the server wide state stay as they
+    // are. clients blocked on a list keep waiting for a push
+    pub fn clear(&self) {
+        self.changed();
+        self.map.clear();
+        self.hmap.clear();
+        self.dset.clear();
+        self.set_encoding.clear();
+        self.hashtable_encoded.clear();
+        self.list.clear();
+        self.zset.clear();
+        self.expires.clear();
+        self.last_access.clear();
+        // notifiers nobody waits on go too, blocked clients keep theirs
+        self.list_notify
+            .retain(|_, notify| Arc::strong_count(notify) > 1);
     }
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
+    // keys in this database, counting expired ones nothing has reclaimed yet
+    pub fn approximate_len(&self) -> usize {
+        self.map.len() + self.hmap.len() + self.dset.len() + self.list.len() + self.zset.len()
+    }
+
+    // every key of every type, expired ones included
+    fn stored_keys(&self) -> Vec<String> {
+        self.map
+            .iter()
+            .map(|e| e.key().clone())
+            .chain(self.hmap.iter().map(|e| e.key().clone()))
+            .chain(self.dset.iter().map(|e| e.key().clone()))
+            .chain(self.list.iter().map(|e| e.key().clone()))
+            .chain(self.zset.iter().map(|e| e.key().clone()))
+            .collect()
+    }
+
+    // a random live key. an expired pick is dropped on the spot and another one
+    // tried; after RANDOMKEY_MAX_TRIES of those the rest are checked in turn, so
+    // None really means there are no live keys
+    pub fn random_key(&self) -> Option<String> {
+        let mut candidates = self.stored_keys();
+        let mut rng = rand::thread_rng();
+        for _ in 0..RANDOMKEY_MAX_TRIES {
+            if candidates.is_empty() {
+                return None;
+            }
+            let key = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+            if self.exists(&key) {
+                return Some(key);
+            }
+        }
+        candidates.into_iter().find(|key| self.exists(key))
+    }
+
+    // walks the key space in the order of the keys' hashes, `count` keys at a
+    // time. the cursor is the hash of the next key and 0 once everything was
+    // visited, so unlike an offset it doesn't move when keys before it come or
+    // go: a key that exists for the whole scan is returned at least once, keys
+    // added or removed meanwhile may or may not be. like redis, the filters run
+    // after a page is taken, so a page can come back empty
+    pub fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: usize,
+        key_type: Option<KeyType>,
+    ) -> (u64, Vec<String>) {
+        let mut keys: Vec<(u64, String)> = self
+            .stored_keys()
+            .into_iter()
+            .map(|key| (scan_hash(&key), key))
+            .filter(|(hash, _)| *hash >= cursor)
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut end = count.max(1).min(keys.len());
+        // keys with the same hash go in the same page, the cursor can't point between them
+        while end < keys.len() && keys[end].0 == keys[end - 1].0 {
+            end += 1;
+        }
+        let next = keys.get(end).map_or(0, |(hash, _)| *hash);
+        let page = keys[..end]
+            .iter()
+            .map(|(_, key)| key)
+            .filter(|key| pattern.is_none_or(|p| glob_match(p.as_bytes(), key.as_bytes())))
+            .filter(|key| match key_type {
+                Some(ty) => self.key_type(key) == Some(ty),
+                None => self.exists(key),
+            })
+            .cloned()
+            .collect();
+        (next, page)
+    }
+
+    // what OBJECT ENCODING reports, None for a missing key
+    pub fn object_encoding(&self, key: &str) -> Option<&'static str> {
+        let encoding = match self.key_type(key)? {
+            KeyType::String => match self.map.get(key)?.value().as_ref() {
+                RespFrame::Integer(_) => "int",
+                v if is_set_int(v) => "int",
+                v if frame_bytes(v).len() <= 44 => "embstr",
+                _ => "raw",
+            },
+            KeyType::Hash => {
+                if self.hashtable_encoded.contains(key) {
+                    "hashtable"
+                } else {
+                    "listpack"
+                }
+            }
+            KeyType::Set => match self.set_encoding.get(key) {
+                Some(encoding) => encoding.as_str(),
+                None => {
+                    let members = self.smembers(key);
+                    SetEncoding::of(&members, self.encoding.set_max_intset_entries()).as_str()
+                }
+            },
+            KeyType::List => {
+                if self.list.get(key)?.len() <= self.encoding.list_max_listpack_size() {
+                    "listpack"
+                } else {
+                    "quicklist"
+                }
+            }
+            KeyType::ZSet => {
+                let zset = self.zset.get(key)?;
+                let small = zset.len() <= SET_MAX_LISTPACK_ENTRIES
+                    && zset.iter().all(|(m, _)| m.len() <= SET_MAX_LISTPACK_VALUE);
+                if small {
+                    "listpack"
+                } else {
+                    "skiplist"
+                }
+            }
+        };
+        Some(encoding)
+    }
+
+    // seconds since the key was last read or written, reading it here doesn't count
+    pub fn object_idletime(&self, key: &str) -> Option<u64> {
+        self.key_type(key)?;
+        let idle = self
+            .last_access
+            .get(key)
+            .map_or(0, |at| at.elapsed().as_secs());
+        Some(idle)
+    }
+
+    pub fn del(&self, key: &str) -> bool {
+        !self.take(key).is_empty()
+    }
+
+    // same as del, but the removed values are dropped on a blocking thread
+    pub fn unlink(&self, key: &str) -> bool {
+        let removed = self.take(key);
+        if removed.is_empty() {
+            return false;
+        }
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn_blocking(move || drop(removed));
+        }
+        true
+    }
+
+    fn take(&self, key: &str) -> Vec<StoredValue> {
+        let removed = self.remove_values(key);
+        if !removed.is_empty() {
+            self.changed();
+        }
+        removed
+    }
+
+    // take without counting it as a change, for keys that expired
+    fn remove_values(&self, key: &str) -> Vec<StoredValue> {
+        self.expires.remove(key);
+        self.last_access.remove(key);
+        let mut removed = Vec::new();
+        if let Some((_, v)) = self.map.remove(key) {
+            removed.push(StoredValue::String(v));
+        }
+        if let Some((_, v)) = self.hmap.remove(key) {
+            self.hashtable_encoded.remove(key);
+            removed.push(StoredValue::Hash(v));
+        }
+        if let Some((_, v)) = self.dset.remove(key) {
+            self.set_encoding.remove(key);
+            removed.push(StoredValue::Set(v));
+        }
+        if let Some((_, v)) = self.list.remove(key) {
+            removed.push(StoredValue::List(v));
+        }
+        if let Some((_, v)) = self.zset.remove(key) {
+            removed.push(StoredValue::ZSet(v));
+        }
+        removed
+    }
+
+    // set the deadline of an existing key, returns false if the key is missing
+    pub fn expire_at(&self, key: &str, deadline: Instant) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+        if deadline <= Instant::now() {
+            self.del(key);
+        } else {
+            self.touch(key);
+            self.changed();
+            self.expires.insert(key.to_string(), deadline);
+        }
+        true
+    }
+
+    pub fn expire_deadline(&self, key: &str) -> Option<Instant> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.expires.get(key).map(|v| *v.value())
+    }
+
+    pub fn set_active_expire(&self, enabled: bool) {
+        self.active_expire.store(enabled, Ordering::Relaxed);
+    }
+
+    // drops the expired keys of every database, unless DEBUG SET-ACTIVE-EXPIRE 0 turned it
+    // off. the server runs it periodically, returns how many keys were removed
+    pub fn expire_cycle(&self) -> usize {
+        if !self.active_expire.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let now = Instant::now();
+        let mut removed = 0;
+        for db in (0..self.num_databases()).filter_map(|index| self.database(index)) {
+            let expired: Vec<String> = db
+                .expires
+                .iter()
+                .filter(|entry| *entry.value() <= now)
+                .map(|entry| entry.key().clone())
+                .collect();
+            removed += expired.iter().filter(|key| db.is_expired(key)).count();
+        }
+        removed
+    }
+
+    // checked at the top of every read, expired keys are removed lazily
+    pub fn is_expired(&self, key: &str) -> bool {
+        let expired = self
+            .expires
+            .get(key)
+            .is_some_and(|deadline| *deadline <= Instant::now());
+        // only whoever actually removed it counts it
+        if expired && !self.remove_values(key).is_empty() {
+            self.stats.expired_keys.fetch_add(1, Ordering::Relaxed);
+        }
+        expired
+    }
+
+    // every write goes through here before touching the value: an expired key
+    // is dropped first, and the key counts as just accessed
+    fn prepare_write(&self, key: &str) {
+        self.is_expired(key);
+        self.touch(key);
+    }
+
+    fn touch(&self, key: &str) {
+        self.last_access.insert(key.to_string(), Instant::now());
+    }
+
+    pub fn hget(&self, key: &str, field: impl AsRef<[u8]>) -> Option<RespFrame> {
+        if self.is_expired(key) {
+            return None;
+        }
         self.hmap
             .get(key)
-            .and_then(|v| v.get(field).map(|v| v.value().clone()))
+            .and_then(|v| v.get(field.as_ref()).map(|v| v.value().1.clone()))
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    pub fn hset(&self, key: String, field: impl Into<Vec<u8>>, value: RespFrame) {
+        self.prepare_write(&key);
+        let field = field.into();
+        let large = !fits_listpack(&field, &value);
+        let hmap = self.hmap.entry(key.clone()).or_default();
+        // overwriting a field keeps its place
+        match hmap.entry(field) {
+            Entry::Occupied(mut entry) => entry.get_mut().1 = value,
+            Entry::Vacant(entry) => {
+                entry.insert((next_field_seq(), value));
+            }
+        }
+        if large || hmap.len() > self.encoding.hash_max_listpack_entries() {
+            self.hashtable_encoded.insert(key);
+        }
+        self.changed();
     }
 
-    pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+    // the fields in the order they were first set
+    pub fn hgetall(&self, key: &str) -> Option<Vec<(Vec<u8>, RespFrame)>> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.hmap.get(key).map(|hash| ordered_fields(&hash))
     }
 
-    pub fn hmget(&self, key: &str, fields: Vec<String>) -> Option<Vec<RespFrame>> {
+    pub fn hmget(&self, key: &str, fields: Vec<Vec<u8>>) -> Option<Vec<RespFrame>> {
         //self.hmap.get(key).filter(|x| fields.contains(x));
+        if self.is_expired(key) {
+            return None;
+        }
         self.hmap.get(key).map(|smap| {
             fields
                 .iter()
-                .filter_map(|field| smap.get(field).map(|v| v.value().clone()))
+                .filter_map(|field| smap.get(field).map(|v| v.value().1.clone()))
                 .collect()
         })
     }
 
+    // `count` random fields, distinct when positive, possibly repeated when negative.
+    // HRANDFIELD refuses counts past HRANDFIELD_MAX_COUNT either way
+    pub fn hrandfield(&self, key: &str, count: i64) -> Vec<(Vec<u8>, RespFrame)> {
+        let Some(entries) = self.hgetall(key) else {
+            return Vec::new();
+        };
+        let mut rng = rand::thread_rng();
+        if count >= 0 {
+            entries
+                .choose_multiple(&mut rng, count as usize)
+                .cloned()
+                .collect()
+        } else if entries.is_empty() {
+            Vec::new()
+        } else {
+            // the count comes from the client, so it doesn't size the reply up front
+            let count = count.unsigned_abs() as usize;
+            let mut picked = Vec::with_capacity(count.min(MAX_HRANDFIELD_RESERVE));
+            picked.extend((0..count).filter_map(|_| entries.choose(&mut rng).cloned()));
+            picked
+        }
+    }
+
     pub fn echo(&self, key: &str) -> Option<RespFrame> {
         Some(RespFrame::SimpleString(SimpleString::new(key.to_string())))
     }
 
     pub fn sadd(&self, key: String, memb: RespFrame) -> Option<u8> {
         //self.dset.get(key).and(optb)
-        let set: DashSet<RespFrame> = DashSet::new();
-        set.insert(memb);
-        if self.dset.insert(key, set).is_some() {
-            Some(1)
-        } else {
-            Some(0)
+        self.prepare_write(&key);
+        let set = self.dset.entry(key.clone()).or_default();
+        if !set.insert(memb.clone()) {
+            return None;
+        }
+        let mut encoding = self.set_encoding.entry(key).or_insert(SetEncoding::IntSet);
+        *encoding = encoding.grow(&memb, set.len(), self.encoding.set_max_intset_entries());
+        self.changed();
+        Some(1)
+    }
+
+    pub fn smembers(&self, key: &str) -> Vec<RespFrame> {
+        if self.is_expired(key) {
+            return Vec::new();
         }
+        self.dset
+            .get(key)
+            .map(|set| set.iter().map(|v| v.key().clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn sinter(&self, keys: &[String]) -> Vec<RespFrame> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Vec::new();
+        };
+        let others: Vec<HashSet<_>> = rest
+            .iter()
+            .map(|key| self.smembers(key).into_iter().collect())
+            .collect();
+        self.smembers(first)
+            .into_iter()
+            .filter(|member| others.iter().all(|set| set.contains(member)))
+            .collect()
+    }
+
+    // size of the intersection, stops counting once `limit` is reached
+    pub fn sintercard(&self, keys: &[String], limit: Option<usize>) -> usize {
+        let Some((first, rest)) = keys.split_first() else {
+            return 0;
+        };
+        let others: Vec<HashSet<_>> = rest
+            .iter()
+            .map(|key| self.smembers(key).into_iter().collect())
+            .collect();
+        let matches = self
+            .smembers(first)
+            .into_iter()
+            .filter(|member| others.iter().all(|set| set.contains(member)));
+        match limit {
+            Some(limit) => matches.take(limit).count(),
+            None => matches.count(),
+        }
+    }
+
+    pub fn sunion(&self, keys: &[String]) -> Vec<RespFrame> {
+        let union: HashSet<_> = keys.iter().flat_map(|key| self.smembers(key)).collect();
+        union.into_iter().collect()
+    }
+
+    // the difference one member at a time, for callers that only count it, cap it
+    // or collect it somewhere. the members of the other sets are copied up front,
+    // the first set is walked in place. `f` runs with that one set locked for
+    // reading and must not write to the keyspace
+    pub fn sdiff_iter<T>(
+        &self,
+        keys: &[String],
+        f: impl FnOnce(&mut dyn Iterator<Item = RespFrame>) -> T,
+    ) -> T {
+        let Some((first, rest)) = keys.split_first() else {
+            return f(&mut std::iter::empty());
+        };
+        // copied before the first set is held, so no key is ever locked twice, even
+        // when it's named twice
+        let others: HashSet<RespFrame> = rest.iter().flat_map(|key| self.smembers(key)).collect();
+        // expiring a key takes a write lock, so it's done before the set is held
+        self.is_expired(first);
+        let Some(first) = self.dset.get(first) else {
+            return f(&mut std::iter::empty());
+        };
+        let mut diff = first
+            .iter()
+            .map(|member| member.key().clone())
+            .filter(|member| !others.contains(member));
+        f(&mut diff)
+    }
+
+    // replace dest with the given members, an empty result deletes dest
+    pub fn sstore(&self, dest: String, members: impl IntoIterator<Item = RespFrame>) -> usize {
+        let set: DashSet<RespFrame> = members.into_iter().collect();
+        self.del(&dest);
+        if set.is_empty() {
+            return 0;
+        }
+        self.touch(&dest);
+        let len = set.len();
+        let members: Vec<RespFrame> = set.iter().map(|m| m.key().clone()).collect();
+        let encoding = SetEncoding::of(&members, self.encoding.set_max_intset_entries());
+        self.set_encoding.insert(dest.clone(), encoding);
+        self.dset.insert(dest, set);
+        self.changed();
+        len
     }
 
     pub fn sismember(&self, key: String, item: RespFrame) -> Option<u8> {
+        if self.is_expired(&key) {
+            return None;
+        }
         match self.dset.get(&key) {
             Some(vset) => {
                 if vset.contains(&item) {
@@ -103,4 +1209,336 @@ impl Backend {
             None => None,
         }
     }
+
+    pub fn lpush(&self, key: String, value: RespFrame) -> i64 {
+        self.lpush_many(key, vec![value])
+    }
+
+    pub fn rpush(&self, key: String, value: RespFrame) -> i64 {
+        self.rpush_many(key, vec![value])
+    }
+
+    // each value goes to the head in turn, so the last one ends up first
+    pub fn lpush_many(&self, key: String, values: Vec<RespFrame>) -> i64 {
+        self.prepare_write(&key);
+        let len = {
+            let mut list = self.list.entry(key.clone()).or_default();
+            for value in values {
+                list.push_front(value);
+            }
+            list.len()
+        };
+        self.changed();
+        self.notify_list(&key);
+        len as i64
+    }
+
+    pub fn rpush_many(&self, key: String, values: Vec<RespFrame>) -> i64 {
+        self.prepare_write(&key);
+        let len = {
+            let mut list = self.list.entry(key.clone()).or_default();
+            list.extend(values);
+            list.len()
+        };
+        self.changed();
+        self.notify_list(&key);
+        len as i64
+    }
+
+    pub fn lpop(&self, key: &str) -> Option<RespFrame> {
+        if self.is_expired(key) {
+            return None;
+        }
+        let value = self.list.get_mut(key)?.pop_front();
+        self.touch(key);
+        if value.is_some() {
+            self.changed();
+        }
+        if self
+            .list
+            .remove_if(key, |_, list| list.is_empty())
+            .is_some()
+        {
+            self.last_access.remove(key);
+        }
+        value
+    }
+
+    pub fn rpop(&self, key: &str) -> Option<RespFrame> {
+        if self.is_expired(key) {
+            return None;
+        }
+        let value = self.list.get_mut(key)?.pop_back();
+        self.touch(key);
+        if value.is_some() {
+            self.changed();
+        }
+        if self
+            .list
+            .remove_if(key, |_, list| list.is_empty())
+            .is_some()
+        {
+            self.last_access.remove(key);
+        }
+        value
+    }
+
+    // elements of the list, set or sorted set at key, compared as numbers unless
+    // `alpha`, None if some element is not a number. other types are WRONGTYPE
+    pub fn sort(
+        &self,
+        key: &str,
+        alpha: bool,
+        desc: bool,
+    ) -> Result<Option<Vec<RespFrame>>, CommandError> {
+        let mut elements: Vec<RespFrame> = match self.key_type(key) {
+            None => return Ok(Some(Vec::new())),
+            Some(KeyType::List) => self
+                .list
+                .get(key)
+                .map(|list| list.iter().cloned().collect())
+                .unwrap_or_default(),
+            Some(KeyType::Set) => self.smembers(key),
+            Some(KeyType::ZSet) => self
+                .zset
+                .get(key)
+                .map(|zset| {
+                    zset.iter()
+                        .map(|(member, _)| BulkString::new(member).into())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Some(_) => {
+                self.stats.wrongtype_errors.fetch_add(1, Ordering::Relaxed);
+                return Err(CommandError::WrongType);
+            }
+        };
+        if alpha {
+            elements.sort_by_cached_key(frame_bytes);
+        } else {
+            let Some(mut scored) = elements
+                .into_iter()
+                .map(|v| sort_score(&v).map(|score| (score, v)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                return Ok(None);
+            };
+            scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+            elements = scored.into_iter().map(|(_, v)| v).collect();
+        }
+        if desc {
+            elements.reverse();
+        }
+        Ok(Some(elements))
+    }
+
+    // the new length, 0 for a missing key and -1 if the pivot isn't in the list
+    pub fn linsert(&self, key: &str, before: bool, pivot: &RespFrame, element: RespFrame) -> i64 {
+        if self.is_expired(key) {
+            return 0;
+        }
+        let Some(mut list) = self.list.get_mut(key) else {
+            return 0;
+        };
+        let Some(i) = list.iter().position(|v| v == pivot) else {
+            return -1;
+        };
+        list.insert(if before { i } else { i + 1 }, element);
+        let len = list.len();
+        drop(list);
+        self.touch(key);
+        self.changed();
+        len as i64
+    }
+
+    // indexes of `element` in the list, skipping the first `rank - 1` matches.
+    // a negative rank searches from the tail, a count of 0 returns every match
+    pub fn lpos(&self, key: &str, element: &RespFrame, rank: i64, count: usize) -> Vec<usize> {
+        if self.is_expired(key) {
+            return Vec::new();
+        }
+        let Some(list) = self.list.get(key) else {
+            return Vec::new();
+        };
+        let matches = list.iter().enumerate().filter(|(_, v)| *v == element);
+        let skip = (rank.unsigned_abs() - 1) as usize;
+        let limit = if count == 0 { usize::MAX } else { count };
+        if rank > 0 {
+            matches.skip(skip).take(limit).map(|(i, _)| i).collect()
+        } else {
+            matches
+                .rev()
+                .skip(skip)
+                .take(limit)
+                .map(|(i, _)| i)
+                .collect()
+        }
+    }
+
+    // what a blocked pop waits on, the key's notifier goes away with its last waiter
+    pub fn list_notifier(&self, key: &str) -> ListWaiter {
+        let notify = self.list_notify.entry(key.to_string()).or_default().clone();
+        ListWaiter {
+            backend: self.clone(),
+            key: key.to_string(),
+            notify,
+        }
+    }
+
+    fn notify_list(&self, key: &str) {
+        if let Some(notify) = self.list_notify.get(key) {
+            notify.notify_waiters();
+        }
+    }
+}
+
+fn next_field_seq() -> u64 {
+    FIELD_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) fn ordered_fields(hash: &HashFields) -> Vec<(Vec<u8>, RespFrame)> {
+    let mut fields: Vec<(u64, Vec<u8>, RespFrame)> = hash
+        .iter()
+        .map(|e| (e.value().0, e.key().clone(), e.value().1.clone()))
+        .collect();
+    fields.sort_by_key(|(seq, _, _)| *seq);
+    fields
+        .into_iter()
+        .map(|(_, field, value)| (field, value))
+        .collect()
+}
+
+// fields in the given order, for hashes rebuilt from a dump
+pub(crate) fn hash_fields(fields: impl IntoIterator<Item = (Vec<u8>, RespFrame)>) -> HashFields {
+    fields
+        .into_iter()
+        .map(|(field, value)| (field, (next_field_seq(), value)))
+        .collect()
+}
+
+// where a key is in SCAN's order. DefaultHasher::new always hashes the same way,
+// so cursors stay valid between calls
+fn scan_hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+// whether a hash field and its value are short enough for a listpack
+fn fits_listpack(field: &[u8], value: &RespFrame) -> bool {
+    field.len() <= SET_MAX_LISTPACK_VALUE && frame_bytes(value).len() <= SET_MAX_LISTPACK_VALUE
+}
+
+// the bytes of a stored string, other frames by their display form
+pub(crate) fn frame_bytes(value: &RespFrame) -> Vec<u8> {
+    match value {
+        RespFrame::BulkString(v) => v.0.clone(),
+        RespFrame::SimpleString(v) => v.as_bytes().to_vec(),
+        v => v.to_string().into_bytes(),
+    }
+}
+
+fn sort_score(value: &RespFrame) -> Option<f64> {
+    match value {
+        RespFrame::Integer(v) => Some(*v as f64),
+        RespFrame::Double(v) => Some(**v),
+        v => std::str::from_utf8(&frame_bytes(v))
+            .ok()?
+            .parse::<f64>()
+            .ok()
+            .filter(|v| !v.is_nan()),
+    }
+}
+
+// members an intset can hold: integers, or strings that are exactly an integer's text
+fn is_set_int(value: &RespFrame) -> bool {
+    match value {
+        RespFrame::Integer(_) => true,
+        RespFrame::BulkString(v) => canonical_int(v).is_some(),
+        _ => false,
+    }
+}
+
+// the integer some bytes spell out exactly: "12", but not "012" or "+12"
+fn canonical_int(bytes: &[u8]) -> Option<i64> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    s.parse::<i64>().ok().filter(|n| n.to_string() == s)
+}
+
+// strings that are integers are kept as one, which INCR and DECR use as is. only
+// exact spellings qualify, so reading the value gives back the bytes that were set
+fn string_value(value: RespFrame) -> RespFrame {
+    match value {
+        RespFrame::BulkString(ref v) => match canonical_int(v) {
+            Some(n) => RespFrame::Integer(n),
+            None => value,
+        },
+        value => value,
+    }
+}
+
+// a stored string as clients see it, integers in their decimal form
+fn string_reply(value: &Arc<RespFrame>) -> Arc<RespFrame> {
+    match value.as_ref() {
+        RespFrame::Integer(n) => Arc::new(BulkString::new(n.to_string()).into()),
+        _ => value.clone(),
+    }
+}
+
+// shortcuts for tests that need some data to work on
+#[cfg(test)]
+impl Backend {
+    // a string "s", a hash "h", a set "set", a list "l" and a sorted set "z",
+    // each holding "v" or member "m"
+    pub(crate) fn populated() -> Self {
+        let backend = Backend::new();
+        backend.set("s".to_string(), BulkString::new("v").into());
+        backend.hset("h".to_string(), "f", BulkString::new("v").into());
+        backend.sadd("set".to_string(), BulkString::new("m").into());
+        backend.rpush("l".to_string(), BulkString::new("v").into());
+        backend.zadd("z".to_string(), vec![(1.0, "m".to_string())]);
+        backend
+    }
+
+    pub(crate) fn with_strings(pairs: &[(&str, &str)]) -> Self {
+        let backend = Backend::new();
+        for (key, value) in pairs {
+            backend.set(key.to_string(), BulkString::new(*value).into());
+        }
+        backend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear() {
+        let backend = Backend::populated();
+        backend.expire_at("s", Instant::now() + Duration::from_secs(100));
+        let other = backend.database(1).expect("database 1 exists");
+        other.set("kept".to_string(), BulkString::new("v").into());
+        assert_eq!(backend.approximate_len(), 5);
+
+        backend.clear();
+        assert_eq!(backend.approximate_len(), 0);
+        for key in ["s", "h", "set", "l", "z"] {
+            assert!(!backend.exists(key), "{} survived clear", key);
+        }
+        assert!(backend.expires.is_empty());
+        assert!(backend.set_encoding.is_empty());
+        assert!(backend.hashtable_encoded.is_empty());
+        assert!(backend.last_access.is_empty());
+        // only this database
+        assert!(other.exists("kept"));
+
+        let backend = Backend::with_strings(&[("a", "1"), ("b", "x")]);
+        assert_eq!(
+            backend.get("b").ok().flatten().as_deref(),
+            Some(&BulkString::new("x").into())
+        );
+        backend.clear();
+        assert!(!backend.exists("a"));
+    }
 }
@@ -0,0 +1,199 @@
+use crate::{glob_match, Backend, RespArrayBuilder, RespFrame};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc::UnboundedSender;
+
+pub type SubscriberId = u64;
+
+type Registry = DashMap<String, HashMap<SubscriberId, UnboundedSender<RespFrame>>>;
+
+// channel (or pattern) -> subscribers, each subscriber owns a queue drained by its connection
+#[derive(Debug, Default)]
+pub struct PubSubRegistry {
+    next_id: AtomicU64,
+    pub(crate) channels: Registry,
+    pub(crate) patterns: Registry,
+}
+
+// the per-connection side of pub/sub, unsubscribes everything when dropped
+#[derive(Debug)]
+pub struct Subscriber {
+    id: SubscriberId,
+    tx: UnboundedSender<RespFrame>,
+    backend: Backend,
+    channels: Vec<String>,
+    patterns: Vec<String>,
+}
+
+impl Backend {
+    // a message is queued as one whole frame and only its subscriber's connection writes it
+    // out, so messages never interleave and each publisher's arrive in the order it sent them
+    pub fn publish(&self, channel: &str, message: RespFrame) -> usize {
+        let mut receivers = 0;
+        if let Some(subscribers) = self.pubsub.channels.get(channel) {
+            let frame: RespFrame = RespArrayBuilder::with_capacity(3)
+                .push_bulk("message")
+                .push_bulk(channel)
+                .push_frame(message.clone())
+                .build()
+                .into();
+            receivers += subscribers
+                .values()
+                .filter(|tx| tx.send(frame.clone()).is_ok())
+                .count();
+        }
+        for entry in self.pubsub.patterns.iter() {
+            if !glob_match(entry.key().as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let frame: RespFrame = RespArrayBuilder::with_capacity(4)
+                .push_bulk("pmessage")
+                .push_bulk(entry.key().as_str())
+                .push_bulk(channel)
+                .push_frame(message.clone())
+                .build()
+                .into();
+            receivers += entry
+                .value()
+                .values()
+                .filter(|tx| tx.send(frame.clone()).is_ok())
+                .count();
+        }
+        receivers
+    }
+
+    // active channels, those with at least one subscriber
+    pub fn pubsub_channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .pubsub
+            .channels
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p.as_bytes(), channel.as_bytes())))
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    pub fn pubsub_numsub(&self, channel: &str) -> usize {
+        self.pubsub
+            .channels
+            .get(channel)
+            .map_or(0, |subscribers| subscribers.len())
+    }
+
+    pub fn pubsub_numpat(&self) -> usize {
+        self.pubsub.patterns.len()
+    }
+}
+
+impl Subscriber {
+    pub fn new(backend: &Backend, tx: UnboundedSender<RespFrame>) -> Self {
+        Self {
+            id: backend.pubsub.next_id.fetch_add(1, Ordering::Relaxed),
+            tx,
+            backend: backend.clone(),
+            channels: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+
+    // returns false if the channel was already subscribed
+    pub fn subscribe(&mut self, channel: &str) -> bool {
+        add(
+            &self.backend.pubsub.channels,
+            &mut self.channels,
+            channel,
+            self.id,
+            &self.tx,
+        )
+    }
+
+    // returns false if the channel was not subscribed
+    pub fn unsubscribe(&mut self, channel: &str) -> bool {
+        remove(
+            &self.backend.pubsub.channels,
+            &mut self.channels,
+            channel,
+            self.id,
+        )
+    }
+
+    pub fn psubscribe(&mut self, pattern: &str) -> bool {
+        add(
+            &self.backend.pubsub.patterns,
+            &mut self.patterns,
+            pattern,
+            self.id,
+            &self.tx,
+        )
+    }
+
+    pub fn punsubscribe(&mut self, pattern: &str) -> bool {
+        remove(
+            &self.backend.pubsub.patterns,
+            &mut self.patterns,
+            pattern,
+            self.id,
+        )
+    }
+
+    // drops every channel and pattern subscription
+    pub fn unsubscribe_all(&mut self) {
+        for channel in self.channels.clone() {
+            self.unsubscribe(&channel);
+        }
+        for pattern in self.patterns.clone() {
+            self.punsubscribe(&pattern);
+        }
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn subscription_count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
+
+fn add(
+    registry: &Registry,
+    names: &mut Vec<String>,
+    name: &str,
+    id: SubscriberId,
+    tx: &UnboundedSender<RespFrame>,
+) -> bool {
+    if names.iter().any(|n| n == name) {
+        return false;
+    }
+    registry
+        .entry(name.to_string())
+        .or_default()
+        .insert(id, tx.clone());
+    names.push(name.to_string());
+    true
+}
+
+fn remove(registry: &Registry, names: &mut Vec<String>, name: &str, id: SubscriberId) -> bool {
+    let Some(pos) = names.iter().position(|n| n == name) else {
+        return false;
+    };
+    names.remove(pos);
+    if let Some(mut subscribers) = registry.get_mut(name) {
+        subscribers.remove(&id);
+    }
+    registry.remove_if(name, |_, subscribers| subscribers.is_empty());
+    true
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.unsubscribe_all();
+    }
+}
@@ -0,0 +1,382 @@
+use super::Backend;
+use crate::Nf64;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+
+// what a ZRANGE selects, the bounds always run from low to high
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRangeBy {
+    // ranks, negative ones count from the end
+    Index(i64, i64),
+    Score(Bound<f64>, Bound<f64>),
+    // by member name, meant for sets where every score is the same
+    Lex(Bound<String>, Bound<String>),
+}
+
+// how ZUNIONSTORE and ZINTERSTORE combine the scores a member has in several sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn combine(self, a: f64, b: f64) -> f64 {
+        let score = match self {
+            Aggregate::Sum => a + b,
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        };
+        // inf + -inf, like redis it counts as 0
+        if score.is_nan() {
+            0.0
+        } else {
+            score
+        }
+    }
+}
+
+// members are looked up by name and iterated by (score, member)
+#[derive(Debug, Clone, Default)]
+pub struct ZSet {
+    scores: HashMap<String, Nf64>,
+    ordered: BTreeSet<(Nf64, String)>,
+}
+
+impl ZSet {
+    // returns true if the member is new, an existing member gets the new score
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        let score = Nf64::new(score);
+        let is_new = match self.scores.insert(member.clone(), score) {
+            Some(old) => {
+                self.ordered.remove(&(old, member.clone()));
+                false
+            }
+            None => true,
+        };
+        self.ordered.insert((score, member));
+        is_new
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).map(|score| **score)
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    // removes up to `count` members from the low end, or the high end if `max`
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(String, f64)> {
+        let mut popped = Vec::with_capacity(count.min(self.len()));
+        while popped.len() < count {
+            let next = if max {
+                self.ordered.pop_last()
+            } else {
+                self.ordered.pop_first()
+            };
+            let Some((score, member)) = next else {
+                break;
+            };
+            self.scores.remove(&member);
+            popped.push((member, *score));
+        }
+        popped
+    }
+
+    // members with a score between `min` and `max`
+    pub fn count(&self, min: Bound<f64>, max: Bound<f64>) -> usize {
+        let start = match min {
+            Bound::Included(score) | Bound::Excluded(score) => score,
+            Bound::Unbounded => f64::NEG_INFINITY,
+        };
+        // the empty member sorts before every other one with the same score
+        self.ordered
+            .range((Nf64::new(start), String::new())..)
+            .skip_while(|(score, _)| matches!(min, Bound::Excluded(min) if **score == min))
+            .take_while(|(score, _)| match max {
+                Bound::Included(max) => **score <= max,
+                Bound::Excluded(max) => **score < max,
+                Bound::Unbounded => true,
+            })
+            .count()
+    }
+
+    // the selected members, lowest first or highest first if `rev`. `limit` is
+    // (offset, count) over the selection, for score and lex ranges
+    pub fn range(
+        &self,
+        by: &ZRangeBy,
+        rev: bool,
+        limit: Option<(usize, usize)>,
+    ) -> Vec<(String, f64)> {
+        let members: Box<dyn Iterator<Item = (&str, f64)> + '_> = if rev {
+            Box::new(self.iter().rev())
+        } else {
+            Box::new(self.iter())
+        };
+        let selected: Vec<(&str, f64)> = match by {
+            ZRangeBy::Index(start, stop) => {
+                let len = self.len() as i64;
+                let start = if *start < 0 { *start + len } else { *start }.max(0);
+                let stop = if *stop < 0 { *stop + len } else { *stop }.min(len - 1);
+                if start > stop {
+                    return Vec::new();
+                }
+                members
+                    .skip(start as usize)
+                    .take((stop - start + 1) as usize)
+                    .collect()
+            }
+            ZRangeBy::Score(min, max) => members
+                .filter(|(_, score)| within(score, min.as_ref(), max.as_ref()))
+                .collect(),
+            ZRangeBy::Lex(min, max) => members
+                .filter(|(member, _)| {
+                    within(
+                        *member,
+                        min.as_ref().map(String::as_str),
+                        max.as_ref().map(String::as_str),
+                    )
+                })
+                .collect(),
+        };
+        let (offset, count) = limit.unwrap_or((0, usize::MAX));
+        selected
+            .into_iter()
+            .skip(offset)
+            .take(count)
+            .map(|(member, score)| (member.to_string(), score))
+            .collect()
+    }
+
+    // ascending by score, ties ordered by member
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&str, f64)> {
+        self.ordered
+            .iter()
+            .map(|(score, member)| (member.as_str(), **score))
+    }
+}
+
+fn within<T: PartialOrd + ?Sized>(value: &T, min: Bound<&T>, max: Bound<&T>) -> bool {
+    let above = match min {
+        Bound::Included(min) => value >= min,
+        Bound::Excluded(min) => value > min,
+        Bound::Unbounded => true,
+    };
+    let below = match max {
+        Bound::Included(max) => value <= max,
+        Bound::Excluded(max) => value < max,
+        Bound::Unbounded => true,
+    };
+    above && below
+}
+
+impl Backend {
+    // returns the number of members that were not in the set before
+    pub fn zadd(&self, key: String, members: Vec<(f64, String)>) -> usize {
+        self.prepare_write(&key);
+        let mut zset = self.zset.entry(key).or_default();
+        // a member that already has its score changes nothing
+        if members
+            .iter()
+            .any(|(score, member)| zset.score(member) != Some(*score))
+        {
+            self.changed();
+        }
+        members
+            .into_iter()
+            .filter(|(score, member)| zset.insert(member.clone(), *score))
+            .count()
+    }
+
+    pub fn zscore(&self, key: &str, member: &str) -> Option<f64> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.zset.get(key)?.score(member)
+    }
+
+    // lowest scores first, an emptied set is removed like an emptied list
+    pub fn zpopmin(&self, key: &str, count: usize) -> Vec<(String, f64)> {
+        self.zpop(key, count, false)
+    }
+
+    // highest scores first
+    pub fn zpopmax(&self, key: &str, count: usize) -> Vec<(String, f64)> {
+        self.zpop(key, count, true)
+    }
+
+    fn zpop(&self, key: &str, count: usize, max: bool) -> Vec<(String, f64)> {
+        if self.is_expired(key) {
+            return Vec::new();
+        }
+        let popped = match self.zset.get_mut(key) {
+            Some(mut zset) => zset.pop(count, max),
+            None => return Vec::new(),
+        };
+        self.touch(key);
+        if !popped.is_empty() {
+            self.changed();
+        }
+        if self
+            .zset
+            .remove_if(key, |_, zset| zset.is_empty())
+            .is_some()
+        {
+            self.last_access.remove(key);
+        }
+        popped
+    }
+
+    pub fn zcount(&self, key: &str, min: Bound<f64>, max: Bound<f64>) -> usize {
+        if self.is_expired(key) {
+            return 0;
+        }
+        self.zset.get(key).map_or(0, |zset| zset.count(min, max))
+    }
+
+    pub fn zrange(
+        &self,
+        key: &str,
+        by: &ZRangeBy,
+        rev: bool,
+        limit: Option<(usize, usize)>,
+    ) -> Vec<(String, f64)> {
+        if self.is_expired(key) {
+            return Vec::new();
+        }
+        self.zset
+            .get(key)
+            .map(|zset| zset.range(by, rev, limit))
+            .unwrap_or_default()
+    }
+
+    // the weights line up with the keys, a missing key is an empty set and a
+    // missing weight is 1. returns the number of members stored in `dest`
+    pub fn zunionstore(
+        &self,
+        dest: String,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> usize {
+        let mut union: HashMap<String, f64> = HashMap::new();
+        for zset in self.weighted_zsets(keys, weights) {
+            for (member, score) in zset {
+                union
+                    .entry(member)
+                    .and_modify(|acc| *acc = aggregate.combine(*acc, score))
+                    .or_insert(score);
+            }
+        }
+        self.zstore(dest, union)
+    }
+
+    pub fn zinterstore(
+        &self,
+        dest: String,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> usize {
+        let mut zsets = self.weighted_zsets(keys, weights).into_iter();
+        let mut inter = zsets.next().unwrap_or_default();
+        for zset in zsets {
+            inter = inter
+                .into_iter()
+                .filter_map(|(member, acc)| {
+                    let score = zset.get(&member)?;
+                    Some((member, aggregate.combine(acc, *score)))
+                })
+                .collect();
+        }
+        self.zstore(dest, inter)
+    }
+
+    // the first set's members, with their scores, that none of the others has.
+    // ordered like the first set
+    pub fn zdiff(&self, keys: &[String]) -> Vec<(String, f64)> {
+        let Some((first, others)) = keys.split_first() else {
+            return Vec::new();
+        };
+        if self.is_expired(first) {
+            return Vec::new();
+        }
+        // copied out first, looking at the other sets may remove expired keys
+        let members: Vec<(String, f64)> = match self.zset.get(first) {
+            Some(zset) => zset
+                .iter()
+                .map(|(member, score)| (member.to_string(), score))
+                .collect(),
+            None => return Vec::new(),
+        };
+        let others = self.weighted_zsets(others, &[]);
+        members
+            .into_iter()
+            .filter(|(member, _)| !others.iter().any(|zset| zset.contains_key(member)))
+            .collect()
+    }
+
+    pub fn zdiffstore(&self, dest: String, keys: &[String]) -> usize {
+        let diff = self.zdiff(keys);
+        self.zstore(dest, diff.into_iter().collect())
+    }
+
+    // a snapshot of each set with its scores already weighted
+    fn weighted_zsets(&self, keys: &[String], weights: &[f64]) -> Vec<HashMap<String, f64>> {
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let weight = weights.get(i).copied().unwrap_or(1.0);
+                if self.is_expired(key) {
+                    return HashMap::new();
+                }
+                let Some(zset) = self.zset.get(key) else {
+                    return HashMap::new();
+                };
+                zset.iter()
+                    .map(|(member, score)| {
+                        // 0 * inf, like redis it counts as 0
+                        let score = score * weight;
+                        (member.to_string(), if score.is_nan() { 0.0 } else { score })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // replaces `dest` with the members, an empty result just deletes it
+    fn zstore(&self, dest: String, members: HashMap<String, f64>) -> usize {
+        self.del(&dest);
+        if members.is_empty() {
+            return 0;
+        }
+        self.touch(&dest);
+        let mut zset = ZSet::default();
+        for (member, score) in members {
+            zset.insert(member, score);
+        }
+        let len = zset.len();
+        self.zset.insert(dest, zset);
+        self.changed();
+        len
+    }
+
+    // one score per member, in the order they were asked for
+    pub fn zmscore(&self, key: &str, members: &[String]) -> Vec<Option<f64>> {
+        if self.is_expired(key) {
+            return vec![None; members.len()];
+        }
+        match self.zset.get(key) {
+            Some(zset) => members.iter().map(|m| zset.score(m)).collect(),
+            None => vec![None; members.len()],
+        }
+    }
+}
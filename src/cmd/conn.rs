@@ -0,0 +1,198 @@
+use super::{
+    extract_args, extract_strings, validate_command, Client, CommandError, CommandExecutor,
+    StatefulExecutor, RESP_OK,
+};
+use crate::{
+    Backend, BulkString, ProtocolVersion, RespArray, RespFrame, RespNull, SimpleError, Subscriber,
+};
+
+// what a connection remembers between its commands. the commands that change
+// it run through StatefulExecutor, see network::connection_handler
+#[derive(Debug)]
+pub struct ConnState {
+    // the database picked with SELECT
+    pub db: usize,
+    // negotiated with HELLO, decides how replies are encoded
+    pub protocol: ProtocolVersion,
+    // whether AUTH, or HELLO with AUTH, has succeeded
+    pub authenticated: bool,
+    // set with CLIENT SETNAME
+    pub name: Option<String>,
+    pub subscriber: Subscriber,
+}
+
+impl ConnState {
+    pub fn new(subscriber: Subscriber) -> Self {
+        ConnState {
+            db: 0,
+            protocol: ProtocolVersion::default(),
+            authenticated: false,
+            name: None,
+            subscriber,
+        }
+    }
+
+    // what RESET goes back to, a fresh connection's state
+    pub fn reset(&mut self) {
+        self.subscriber.unsubscribe_all();
+        self.db = 0;
+        self.protocol = ProtocolVersion::default();
+        self.authenticated = false;
+        self.name = None;
+    }
+
+    pub fn in_subscribe_mode(&self) -> bool {
+        self.subscriber.subscription_count() > 0
+    }
+}
+
+// the name lives on the connection
+impl CommandExecutor for Client {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR CLIENT is only supported on client connections").into()
+    }
+}
+
+impl StatefulExecutor for Client {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        let reply = match self {
+            Client::SetName(name) => {
+                // the name shows up in space separated lists, so it can't contain any
+                if name.bytes().any(|b| !(b'!'..=b'~').contains(&b)) {
+                    SimpleError::new(
+                        "ERR Client names cannot contain spaces, newlines or special characters.",
+                    )
+                    .into()
+                } else {
+                    // an empty name removes it
+                    state.name = Some(name).filter(|name| !name.is_empty());
+                    RESP_OK.clone()
+                }
+            }
+            Client::GetName => match &state.name {
+                Some(name) => BulkString::new(name.as_str()).into(),
+                None => RespFrame::Null(RespNull),
+            },
+        };
+        vec![reply]
+    }
+}
+
+impl TryFrom<RespArray> for Client {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["client"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(sub), Some(name), None) if sub.eq_ignore_ascii_case("setname") => {
+                Ok(Client::SetName(name))
+            }
+            (Some(sub), None, None) if sub.eq_ignore_ascii_case("getname") => Ok(Client::GetName),
+            (Some(sub), _, _) => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'",
+                sub
+            ))),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, SimpleString};
+    use anyhow::Result;
+    use tokio::sync::mpsc;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn state(backend: &Backend) -> ConnState {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        ConnState::new(Subscriber::new(backend, tx))
+    }
+
+    #[test]
+    fn test_select_changes_state() -> Result<()> {
+        let backend = Backend::with_databases(4);
+        let mut state = state(&backend);
+
+        let Command::Select(cmd) = command(&["select", "3"])? else {
+            panic!("select must parse to Command::Select");
+        };
+        assert_eq!(
+            cmd.execute_with_state(&backend, &mut state),
+            vec![RESP_OK.clone()]
+        );
+        assert_eq!(state.db, 3);
+
+        // an index out of range leaves the connection where it was
+        let Command::Select(cmd) = command(&["select", "4"])? else {
+            panic!("select must parse to Command::Select");
+        };
+        let ret = cmd.execute_with_state(&backend, &mut state);
+        assert_eq!(
+            ret,
+            vec![RespFrame::from(SimpleError::new(
+                "ERR DB index is out of range"
+            ))]
+        );
+        assert_eq!(state.db, 3);
+
+        let Command::Reset(cmd) = command(&["reset"])? else {
+            panic!("reset must parse to Command::Reset");
+        };
+        let ret = cmd.execute_with_state(&backend, &mut state);
+        assert_eq!(ret, vec![RespFrame::from(SimpleString::new("RESET"))]);
+        assert_eq!(state.db, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_setname() -> Result<()> {
+        let backend = Backend::new();
+        let mut state = state(&backend);
+        let mut run = |args: &[&str]| -> Result<Vec<RespFrame>> {
+            let Command::Client(cmd) = command(args)? else {
+                panic!("client must parse to Command::Client");
+            };
+            Ok(cmd.execute_with_state(&backend, &mut state))
+        };
+
+        assert_eq!(
+            run(&["client", "getname"])?,
+            vec![RespFrame::Null(RespNull)]
+        );
+        assert_eq!(
+            run(&["client", "setname", "worker-1"])?,
+            vec![RESP_OK.clone()]
+        );
+        assert_eq!(
+            run(&["CLIENT", "GETNAME"])?,
+            vec![RespFrame::from(BulkString::new("worker-1"))]
+        );
+
+        let ret = run(&["client", "setname", "has space"])?;
+        assert!(matches!(ret[..], [RespFrame::Error(_)]));
+        assert_eq!(
+            run(&["client", "getname"])?,
+            vec![RespFrame::from(BulkString::new("worker-1"))]
+        );
+
+        assert_eq!(run(&["client", "setname", ""])?, vec![RESP_OK.clone()]);
+        assert_eq!(
+            run(&["client", "getname"])?,
+            vec![RespFrame::Null(RespNull)]
+        );
+
+        assert!(command(&["client", "setname"]).is_err());
+        assert!(command(&["client", "getname", "x"]).is_err());
+        assert!(command(&["client", "kill"]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use super::{
+    expire::{absolute_deadline, relative_deadline},
+    extract_args, parse_int, validate_command, CommandExecutor, Dump, Restore, RESP_OK,
+};
+use crate::{cmd::CommandError, Backend, BulkString, RespArray, RespFrame, RespNull, SimpleError};
+
+impl CommandExecutor for Dump {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.dump(&self.key) {
+            Some(payload) => BulkString::new(payload).into(),
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = match (self.ttl, self.absttl) {
+            (0, _) => None,
+            (ttl, true) => absolute_deadline(ttl),
+            (ttl, false) => relative_deadline(ttl),
+        };
+        if self.ttl != 0 && deadline.is_none() {
+            return SimpleError::new("ERR invalid expire time in 'restore' command").into();
+        }
+        match backend.restore(self.key, &self.payload, deadline, self.replace) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dump"], 1)?;
+        match extract_args(value, 1)?.into_iter().next() {
+            Some(key) => Ok(Dump {
+                key: key.into_string()?,
+            }),
+            None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+// key ttl serialized-value [REPLACE] [ABSTTL]
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if n_args < 3 {
+            return Err(CommandError::InvalidArgument(
+                "restore command must have at least 3 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["restore"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let ttl = parse_int(args.next())?;
+        if ttl < 0 {
+            return Err(CommandError::InvalidArgument(
+                "Invalid TTL value, must be >= 0".to_string(),
+            ));
+        }
+        let payload = match args.next() {
+            Some(RespFrame::BulkString(payload)) => payload.0,
+            _ => return Err(CommandError::InvalidArgument("Invalid payload".to_string())),
+        };
+
+        let mut restore = Restore {
+            key,
+            ttl,
+            payload,
+            replace: false,
+            absttl: false,
+        };
+        for arg in args {
+            let RespFrame::BulkString(flag) = arg else {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            };
+            match flag.to_ascii_lowercase().as_slice() {
+                b"replace" => restore.replace = true,
+                b"absttl" => restore.absttl = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use anyhow::Result;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    fn command(args: &[&[u8]]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn dump(backend: &Backend, key: &str) -> Result<Vec<u8>> {
+        match command(&[b"dump", key.as_bytes()])?.execute(backend) {
+            RespFrame::BulkString(payload) => Ok(payload.0),
+            frame => panic!("unexpected dump reply: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_dump_restore_round_trip() -> Result<()> {
+        let src = Backend::new();
+        src.set("s".to_string(), BulkString::new("v").into());
+        src.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        src.sadd("set".to_string(), BulkString::new("m").into());
+        src.rpush("l".to_string(), BulkString::new("a").into());
+        src.rpush("l".to_string(), BulkString::new("b").into());
+
+        let dest = Backend::new();
+        for key in ["s", "h", "set", "l"] {
+            let payload = dump(&src, key)?;
+            let ret = command(&[b"restore", key.as_bytes(), b"0", &payload])?.execute(&dest);
+            assert_eq!(ret, RESP_OK.clone());
+            assert_eq!(dest.key_type(key), src.key_type(key));
+            assert_eq!(dest.expire_deadline(key), None);
+        }
+        assert_eq!(
+            dest.get("s")?.as_deref(),
+            Some(&BulkString::new("v").into())
+        );
+        assert_eq!(dest.hget("h", "f"), Some(BulkString::new("v").into()));
+        assert_eq!(
+            dest.smembers("set"),
+            vec![RespFrame::from(BulkString::new("m"))]
+        );
+        assert_eq!(dest.lpop("l"), Some(BulkString::new("a").into()));
+
+        assert_eq!(
+            command(&[b"dump", b"missing"])?.execute(&src),
+            RespFrame::Null(RespNull)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_busy_key_and_bad_payload() -> Result<()> {
+        let backend = Backend::with_strings(&[("s", "v")]);
+        let payload = dump(&backend, "s")?;
+
+        let ret = command(&[b"restore", b"s", b"0", &payload])?.execute(&backend);
+        assert_eq!(ret, RespFrame::from(CommandError::BusyKey));
+        let ret = command(&[b"restore", b"s", b"0", &payload, b"REPLACE"])?.execute(&backend);
+        assert_eq!(ret, RESP_OK.clone());
+
+        let ret = command(&[b"restore", b"x", b"0", b"garbage"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        assert!(!backend.exists("x"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_keeps_ttl() -> Result<()> {
+        let src = Backend::new();
+        src.set("k".to_string(), BulkString::new("v").into());
+        src.expire_at("k", Instant::now() + Duration::from_secs(100));
+        let payload = dump(&src, "k")?;
+
+        // a ttl of 0 picks up the one stored in the payload
+        let dest = Backend::new();
+        command(&[b"restore", b"k", b"0", &payload])?.execute(&dest);
+        let left = dest.expire_deadline("k").unwrap() - Instant::now();
+        assert!(left > Duration::from_secs(98) && left <= Duration::from_secs(100));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_absttl() -> Result<()> {
+        let src = Backend::new();
+        src.set("k".to_string(), BulkString::new("v").into());
+        src.expire_at("k", Instant::now() + Duration::from_secs(100));
+        let payload = dump(&src, "k")?;
+
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let at = (now_ms + 50_000).to_string();
+        let dest = Backend::new();
+        let ret = command(&[b"restore", b"k", at.as_bytes(), &payload, b"ABSTTL"])?.execute(&dest);
+        assert_eq!(ret, RESP_OK.clone());
+        let left = dest.expire_deadline("k").unwrap() - Instant::now();
+        assert!(left > Duration::from_secs(48) && left <= Duration::from_secs(50));
+
+        // a timestamp in the past restores an already expired key
+        let ret = command(&[b"restore", b"old", b"1000", &payload, b"ABSTTL"])?.execute(&dest);
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(!dest.exists("old"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+use super::{
+    extract_args, parse_int, validate_command, CommandExecutor, Expire, ExpireAt, ExpireOptions,
+    ExpireTime, PExpire, PExpireAt, PExpireTime, PTtl, Ttl,
+};
+use crate::{cmd::CommandError, Backend, RespArray, RespFrame, SimpleError};
+use lazy_static::lazy_static;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    // both clocks read at the same moment, unix timestamps go through it in either
+    // direction, so a timestamp turned into a deadline comes back out unchanged
+    static ref CLOCK_BASE: (Instant, i64) = (
+        Instant::now(),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default(),
+    );
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = relative_deadline(self.seconds.saturating_mul(1000));
+        if !self
+            .options
+            .allows(backend.expire_deadline(&self.key), deadline)
+        {
+            return RespFrame::Integer(0);
+        }
+        apply_deadline(backend, &self.key, deadline, "expire")
+    }
+}
+
+impl CommandExecutor for PExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = relative_deadline(self.ms);
+        if !self
+            .options
+            .allows(backend.expire_deadline(&self.key), deadline)
+        {
+            return RespFrame::Integer(0);
+        }
+        apply_deadline(backend, &self.key, deadline, "pexpire")
+    }
+}
+
+impl CommandExecutor for ExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = absolute_deadline(self.ts_secs.saturating_mul(1000));
+        apply_deadline(backend, &self.key, deadline, "expireat")
+    }
+}
+
+impl CommandExecutor for PExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let deadline = absolute_deadline(self.ts_ms);
+        apply_deadline(backend, &self.key, deadline, "pexpireat")
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // round to the nearest second like redis does
+        match remaining_ms(backend, &self.key) {
+            ms if ms < 0 => RespFrame::Integer(ms),
+            ms => RespFrame::Integer((ms + 500) / 1000),
+        }
+    }
+}
+
+impl CommandExecutor for PTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(remaining_ms(backend, &self.key))
+    }
+}
+
+impl CommandExecutor for ExpireTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match expire_time_ms(backend, &self.key) {
+            ms if ms < 0 => RespFrame::Integer(ms),
+            ms => RespFrame::Integer(ms / 1000),
+        }
+    }
+}
+
+impl CommandExecutor for PExpireTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(expire_time_ms(backend, &self.key))
+    }
+}
+
+// -2 if the key does not exist, -1 if it has no expiry
+fn expire_time_ms(backend: &Backend, key: &str) -> i64 {
+    if !backend.exists(key) {
+        return -2;
+    }
+    backend.expire_deadline(key).map_or(-1, unix_ms)
+}
+
+// -2 if the key does not exist, -1 if it has no expiry
+fn remaining_ms(backend: &Backend, key: &str) -> i64 {
+    if !backend.exists(key) {
+        return -2;
+    }
+    match backend.expire_deadline(key) {
+        Some(deadline) => deadline
+            .saturating_duration_since(Instant::now())
+            .as_millis() as i64,
+        None => -1,
+    }
+}
+
+pub(super) fn relative_deadline(ms: i64) -> Option<Instant> {
+    Instant::now().checked_add(Duration::from_millis(ms.max(0) as u64))
+}
+
+// map a unix timestamp onto the monotonic clock the backend uses
+pub(super) fn absolute_deadline(ts_ms: i64) -> Option<Instant> {
+    let (base, base_ms) = *CLOCK_BASE;
+    let offset = ts_ms.saturating_sub(base_ms);
+    if offset >= 0 {
+        base.checked_add(Duration::from_millis(offset as u64))
+    } else {
+        // anything before the base is in the past already
+        Some(
+            base.checked_sub(Duration::from_millis(offset.unsigned_abs()))
+                .unwrap_or(base),
+        )
+    }
+}
+
+// and back to a unix timestamp
+fn unix_ms(deadline: Instant) -> i64 {
+    let (base, base_ms) = *CLOCK_BASE;
+    match deadline.checked_duration_since(base) {
+        Some(offset) => base_ms.saturating_add(offset.as_millis() as i64),
+        None => base_ms.saturating_sub(base.duration_since(deadline).as_millis() as i64),
+    }
+}
+
+impl ExpireOptions {
+    // a key without a ttl counts as an infinite ttl for GT and LT
+    fn allows(&self, current: Option<Instant>, new: Option<Instant>) -> bool {
+        if self.nx && current.is_some() || self.xx && current.is_none() {
+            return false;
+        }
+        match (current, new) {
+            (Some(current), Some(new)) => {
+                (!self.gt || new > current) && (!self.lt || new < current)
+            }
+            (None, _) => !self.gt,
+            (Some(_), None) => true,
+        }
+    }
+
+    fn parse(args: impl Iterator<Item = RespFrame>) -> Result<Self, CommandError> {
+        let mut options = ExpireOptions::default();
+        for arg in args {
+            let flag = match arg {
+                RespFrame::BulkString(flag) => flag,
+                _ => return Err(CommandError::InvalidArgument("Invalid flag".to_string())),
+            };
+            if flag.eq_ignore_ascii_case("nx") {
+                options.nx = true;
+            } else if flag.eq_ignore_ascii_case("xx") {
+                options.xx = true;
+            } else if flag.eq_ignore_ascii_case("gt") {
+                options.gt = true;
+            } else if flag.eq_ignore_ascii_case("lt") {
+                options.lt = true;
+            } else {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Unsupported option {}",
+                    flag.as_str()?
+                )));
+            }
+        }
+
+        if options.nx && (options.xx || options.gt || options.lt) {
+            return Err(CommandError::InvalidArgument(
+                "NX and XX, GT or LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        if options.gt && options.lt {
+            return Err(CommandError::InvalidArgument(
+                "GT and LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        Ok(options)
+    }
+}
+
+fn apply_deadline(
+    backend: &Backend,
+    key: &str,
+    deadline: Option<Instant>,
+    name: &str,
+) -> RespFrame {
+    match deadline {
+        Some(deadline) => RespFrame::Integer(backend.expire_at(key, deadline) as i64),
+        None => SimpleError::new(format!("ERR invalid expire time in '{}' command", name)).into(),
+    }
+}
+
+fn parse_key_and_int(value: RespArray, name: &'static str) -> Result<(String, i64), CommandError> {
+    validate_command(&value, &[name], 2)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    Ok((key, parse_int(args.next())?))
+}
+
+// key value [NX | XX] [GT | LT]
+fn parse_expire_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, i64, ExpireOptions), CommandError> {
+    let n_args = value.len() - 1;
+    if !(2..=4).contains(&n_args) {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have 2 to 4 arguments",
+            name
+        )));
+    }
+    validate_command(&value, &[name], n_args)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = match args.next() {
+        Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+        _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    };
+    let ttl = parse_int(args.next())?;
+    Ok((key, ttl, ExpireOptions::parse(args)?))
+}
+
+fn parse_key(value: RespArray, name: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &[name], 1)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(key) => key.into_string(),
+        None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, seconds, options) = parse_expire_args(value, "expire")?;
+        Ok(Expire {
+            key,
+            seconds,
+            options,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PExpire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, ms, options) = parse_expire_args(value, "pexpire")?;
+        Ok(PExpire { key, ms, options })
+    }
+}
+
+impl TryFrom<RespArray> for ExpireAt {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, ts_secs) = parse_key_and_int(value, "expireat")?;
+        Ok(ExpireAt { key, ts_secs })
+    }
+}
+
+impl TryFrom<RespArray> for PExpireAt {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, ts_ms) = parse_key_and_int(value, "pexpireat")?;
+        Ok(PExpireAt { key, ts_ms })
+    }
+}
+
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Ttl {
+            key: parse_key(value, "ttl")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PTtl {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PTtl {
+            key: parse_key(value, "pttl")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ExpireTime {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ExpireTime {
+            key: parse_key(value, "expiretime")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PExpireTime {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PExpireTime {
+            key: parse_key(value, "pexpiretime")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString, RespNull};
+    use anyhow::Result;
+
+    fn now_unix_secs() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    #[test]
+    fn test_expire_and_ttl() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(-1));
+        let ret = command(&["expire", "k", "100"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(100));
+
+        let ret = command(&["expire", "missing", "100"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["ttl", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(-2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expireat_future_timestamp() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        let ts = (now_unix_secs() + 100).to_string();
+        let ret = command(&["expireat", "k", &ts])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+
+        let RespFrame::Integer(ttl) = command(&["ttl", "k"])?.execute(&backend) else {
+            panic!("ttl must be an integer");
+        };
+        assert!((99..=100).contains(&ttl));
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("v").into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pexpireat_future_timestamp() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+
+        let ts = ((now_unix_secs() + 10) * 1000).to_string();
+        let ret = command(&["pexpireat", "h", &ts])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+
+        let RespFrame::Integer(pttl) = command(&["pttl", "h"])?.execute(&backend) else {
+            panic!("pttl must be an integer");
+        };
+        assert!(pttl > 8000 && pttl <= 10000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expireat_past_timestamp_deletes() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        let ts = (now_unix_secs() - 10).to_string();
+        let ret = command(&["expireat", "k", &ts])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert!(!backend.exists("k"));
+        let ret = command(&["get", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Null(RespNull));
+
+        let ret = command(&["pexpireat", "k", "1000"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_nx() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        let ret = command(&["expire", "k", "100", "NX"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["expire", "k", "200", "nx"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(100));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_xx() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        let ret = command(&["expire", "k", "100", "XX"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(-1));
+
+        command(&["expire", "k", "100"])?.execute(&backend);
+        let ret = command(&["expire", "k", "200", "XX"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(200));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_gt() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        // no ttl means infinite, nothing is greater
+        let ret = command(&["expire", "k", "100", "GT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        command(&["expire", "k", "100"])?.execute(&backend);
+        let ret = command(&["expire", "k", "50", "GT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["expire", "k", "200", "GT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(200));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pexpire_lt() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+
+        // no ttl means infinite, everything is less
+        let ret = command(&["pexpire", "k", "100000", "LT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+
+        let ret = command(&["pexpire", "k", "200000", "LT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["pexpire", "k", "50000", "lt"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["ttl", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(50));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_flag_combinations() -> Result<()> {
+        assert!(command(&["expire", "k", "10", "NX", "XX"]).is_err());
+        assert!(command(&["expire", "k", "10", "NX", "GT"]).is_err());
+        assert!(command(&["expire", "k", "10", "GT", "LT"]).is_err());
+        assert!(command(&["expire", "k", "10", "YY"]).is_err());
+
+        let backend = Backend::with_strings(&[("k", "v")]);
+        command(&["expire", "k", "100"])?.execute(&backend);
+        let ret = command(&["expire", "k", "200", "XX", "GT"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_key_invisible_to_reads() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("s".to_string(), BulkString::new("v").into());
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        backend.sadd("set".to_string(), BulkString::new("m").into());
+        backend.rpush("l".to_string(), BulkString::new("v").into());
+        let past = Instant::now() - Duration::from_millis(1);
+        for key in ["s", "h", "set", "l"] {
+            backend.expires.insert(key.to_string(), past);
+        }
+
+        let null = RespFrame::Null(RespNull);
+        let empty: RespFrame = RespArray::new([]).into();
+        assert_eq!(command(&["get", "s"])?.execute(&backend), null);
+        assert_eq!(command(&["hget", "h", "f"])?.execute(&backend), null);
+        assert_eq!(command(&["hmget", "h", "f"])?.execute(&backend), empty);
+        assert_eq!(command(&["hgetall", "h"])?.execute(&backend), empty);
+        assert_eq!(
+            command(&["sismember", "set", "m"])?.execute(&backend),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(command(&["lpop", "l"])?.execute(&backend), null);
+        assert_eq!(
+            command(&["ttl", "s"])?.execute(&backend),
+            RespFrame::Integer(-2)
+        );
+
+        // lazily removed from the keyspace and the expires map
+        for key in ["s", "h", "set", "l"] {
+            assert!(!backend.exists(key));
+            assert!(!backend.expires.contains_key(key));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_after_expiry_starts_fresh() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset(
+            "h".to_string(),
+            "old".to_string(),
+            BulkString::new("v").into(),
+        );
+        backend
+            .expires
+            .insert("h".to_string(), Instant::now() - Duration::from_millis(1));
+
+        command(&["hset", "h", "new", "v"])?.execute(&backend);
+        assert_eq!(backend.hget("h", "old"), None);
+        assert_eq!(backend.expire_deadline("h"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_invalid_argument() {
+        assert!(command(&["expireat", "k", "soon"]).is_err());
+        assert!(command(&["expire", "k"]).is_err());
+    }
+
+    #[test]
+    fn test_expiretime() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "v")]);
+        let ret = command(&["expiretime", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(-1));
+        let ret = command(&["pexpiretime", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(-2));
+
+        command(&["expire", "k", "100"])?.execute(&backend);
+        let RespFrame::Integer(at) = command(&["expiretime", "k"])?.execute(&backend) else {
+            panic!("expiretime must reply with an integer");
+        };
+        assert!((at - (now_unix_secs() + 100)).abs() <= 1);
+        let RespFrame::Integer(at_ms) = command(&["pexpiretime", "k"])?.execute(&backend) else {
+            panic!("pexpiretime must reply with an integer");
+        };
+        assert_eq!(at_ms / 1000, at);
+
+        // an absolute deadline comes back exactly as it was given
+        let ts = (now_unix_secs() + 1000) * 1000 + 123;
+        command(&["pexpireat", "k", &ts.to_string()])?.execute(&backend);
+        let ret = command(&["pexpiretime", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(ts));
+        let ret = command(&["expiretime", "k"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(ts / 1000));
+
+        assert!(command(&["expiretime"]).is_err());
+
+        Ok(())
+    }
+}
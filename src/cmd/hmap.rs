@@ -1,27 +1,33 @@
 use super::{
-    extract_args, validate_command, CommandExecutor, HGet, HGetAll, HMGet, HSet, Sadd, Sismember,
-    RESP_OK,
+    extract_args, extract_strings, parse_int, validate_command, value_or_null, CommandExecutor,
+    HGet, HGetAll, HKeys, HMGet, HRandField, HScan, HSet, HVals, Sadd, Sismember, RESP_OK,
 };
-use crate::{cmd::CommandError, RespArray, RespFrame, RespMap};
+use crate::{
+    cmd::CommandError, glob_match, BulkString, KeyType, RespArray, RespArrayBuilder, RespFrame,
+    RespMap, RespNull, HRANDFIELD_MAX_COUNT,
+};
+use std::collections::BTreeMap;
 
 impl CommandExecutor for HGet {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
-        match backend.hget(&self.key, &self.field) {
-            Some(value) => value,
-            None => RespFrame::Null(crate::RespNull),
-        }
+        value_or_null(
+            backend.get_typed(&self.key, KeyType::Hash, |b| b.hget(&self.key, &self.field)),
+        )
     }
 }
 
 impl CommandExecutor for HGetAll {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
-        let hmap = backend.hmap.get(&self.key);
+        let hmap = backend.hgetall(&self.key);
         match hmap {
             Some(hmap) => {
-                let mut map = RespMap::new();
-                for v in hmap.iter() {
-                    let key = v.key().to_owned();
-                    map.insert(key, v.value().clone());
+                let mut map = if backend.hash_insertion_order() {
+                    RespMap::insertion_ordered()
+                } else {
+                    RespMap::new()
+                };
+                for (field, value) in hmap {
+                    map.insert(field, value);
                 }
                 map.into()
             }
@@ -30,6 +36,113 @@ impl CommandExecutor for HGetAll {
     }
 }
 
+impl CommandExecutor for HKeys {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match listed_fields(backend, &self.key) {
+            Ok(fields) => {
+                let keys: Vec<RespFrame> = fields
+                    .into_iter()
+                    .map(|(field, _)| BulkString::new(field).into())
+                    .collect();
+                RespArray::new(keys).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HVals {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match listed_fields(backend, &self.key) {
+            Ok(fields) => {
+                RespArray::new(fields.into_iter().map(|(_, v)| v).collect::<Vec<_>>()).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// the fields HKEYS and HVALS list: sorted by name by default, in insertion order
+// when the server lists HGETALL that way too
+fn listed_fields(
+    backend: &crate::Backend,
+    key: &str,
+) -> Result<Vec<(Vec<u8>, RespFrame)>, CommandError> {
+    if !backend.hash_insertion_order() {
+        return Ok(sorted_fields(backend, key)?.into_iter().collect());
+    }
+    let hash = backend.get_typed(key, KeyType::Hash, |b| b.hgetall(key))?;
+    Ok(hash.unwrap_or_default())
+}
+
+// the hash's fields sorted by name, empty for a missing key
+fn sorted_fields(
+    backend: &crate::Backend,
+    key: &str,
+) -> Result<BTreeMap<Vec<u8>, RespFrame>, CommandError> {
+    let hash = backend.get_typed(key, KeyType::Hash, |b| b.hgetall(key))?;
+    Ok(hash
+        .map(|hash| hash.into_iter().collect())
+        .unwrap_or_default())
+}
+
+// pages through the sorted fields the same way SCAN pages through the keys
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let fields = match sorted_fields(backend, &self.key) {
+            Ok(fields) => fields,
+            Err(e) => return e.into(),
+        };
+        let start = (self.cursor as usize).min(fields.len());
+        let end = start.saturating_add(self.count).min(fields.len());
+        let next = if end == fields.len() { 0 } else { end };
+
+        let mut page = RespArrayBuilder::new();
+        for (field, value) in fields.into_iter().skip(start).take(end - start) {
+            if let Some(pattern) = &self.pattern {
+                if !glob_match(pattern.as_bytes(), &field) {
+                    continue;
+                }
+            }
+            page.push_bulk(field);
+            if !self.novalues {
+                page.push_frame(value);
+            }
+        }
+        RespArrayBuilder::with_capacity(2)
+            .push_bulk(next.to_string())
+            .push_frame(page.build())
+            .build()
+            .into()
+    }
+}
+
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let picked = backend.get_typed(&self.key, KeyType::Hash, |b| {
+            Some(b.hrandfield(&self.key, self.count.unwrap_or(1))).filter(|p| !p.is_empty())
+        });
+        let picked = match picked {
+            Ok(picked) => picked.unwrap_or_default(),
+            Err(e) => return e.into(),
+        };
+        if self.count.is_none() {
+            return match picked.into_iter().next() {
+                Some((field, _)) => BulkString::new(field).into(),
+                None => RespFrame::Null(RespNull),
+            };
+        }
+        let mut frames = Vec::new();
+        for (field, value) in picked {
+            frames.push(BulkString::new(field).into());
+            if self.with_values {
+                frames.push(value);
+            }
+        }
+        RespArray::new(frames).into()
+    }
+}
+
 impl CommandExecutor for HSet {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
         backend.hset(self.key, self.field, self.value);
@@ -49,6 +162,9 @@ impl CommandExecutor for HMGet {
 
 impl CommandExecutor for Sadd {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::Set) {
+            return e.into();
+        }
         let ret = backend.sadd(self.key, self.item);
         match ret {
             Some(_) => RespFrame::Integer(1),
@@ -58,6 +174,9 @@ impl CommandExecutor for Sadd {
 }
 impl CommandExecutor for Sismember {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::Set) {
+            return e.into();
+        }
         match backend.sismember(self.key, self.item) {
             Some(v) => {
                 println!("[sismember.execute] v: {:?}", v);
@@ -77,7 +196,7 @@ impl TryFrom<RespArray> for HGet {
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
                 key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                field: field.0,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -92,14 +211,79 @@ impl TryFrom<RespArray> for HGetAll {
         validate_command(&value, &["hgetall"], 1)?;
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
+            Some(key) => Ok(HGetAll {
+                key: key.into_string()?,
             }),
-            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
 
+impl TryFrom<RespArray> for HKeys {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(HKeys {
+            key: single_key(value, "hkeys")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HVals {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(HVals {
+            key: single_key(value, "hvals")?,
+        })
+    }
+}
+
+fn single_key(value: RespArray, name: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &[name], 1)?;
+    match extract_args(value, 1)?.into_iter().next() {
+        Some(key) => key.into_string(),
+        None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+// key [count [WITHVALUES]]
+impl TryFrom<RespArray> for HRandField {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if !(1..=3).contains(&n_args) {
+            return Err(CommandError::InvalidArgument(
+                "hrandfield command must have 1 to 3 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["hrandfield"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let count = args
+            .next()
+            .map(|count| parse_int(Some(count)))
+            .transpose()?;
+        // like redis, so a negative count can't ask for more repeats than fit in memory
+        if count.is_some_and(|count| count.unsigned_abs() > HRANDFIELD_MAX_COUNT as u64) {
+            return Err(CommandError::InvalidArgument(
+                "value is out of range".to_string(),
+            ));
+        }
+        let with_values = match args.next() {
+            None => false,
+            Some(RespFrame::BulkString(opt)) if opt.eq_ignore_ascii_case("withvalues") => true,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
 impl TryFrom<RespArray> for HSet {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
@@ -110,7 +294,7 @@ impl TryFrom<RespArray> for HSet {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
                 Ok(HSet {
                     key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
+                    field: field.0,
                     value,
                 })
             }
@@ -133,9 +317,7 @@ impl TryFrom<RespArray> for HMGet {
             Some(RespFrame::BulkString(key)) => {
                 let fields: Result<Vec<_>, _> = args
                     .map(|x| match x {
-                        RespFrame::BulkString(bs) => {
-                            String::from_utf8(bs.0).map_err(CommandError::from)
-                        }
+                        RespFrame::BulkString(bs) => Ok(bs.0),
                         _ => Err(CommandError::InvalidArgument("Invalid field".to_string())),
                     })
                     .collect();
@@ -181,3 +363,352 @@ impl TryFrom<RespArray> for Sismember {
         }
     }
 }
+
+impl TryFrom<RespArray> for HScan {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if n_args < 2 {
+            return Err(CommandError::InvalidArgument(
+                "hscan command must have at least 2 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["hscan"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let cursor = args
+            .next()
+            .and_then(|c| c.parse::<u64>().ok())
+            .ok_or_else(|| CommandError::InvalidArgument("invalid cursor".to_string()))?;
+        let mut scan = HScan {
+            key,
+            cursor,
+            pattern: None,
+            count: 10,
+            novalues: false,
+        };
+        while let Some(opt) = args.next() {
+            if opt.eq_ignore_ascii_case("novalues") {
+                scan.novalues = true;
+                continue;
+            }
+            let Some(arg) = args.next() else {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            };
+            match opt.to_ascii_lowercase().as_str() {
+                "match" => scan.pattern = Some(arg),
+                "count" => {
+                    scan.count = arg
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| {
+                            CommandError::InvalidArgument(
+                                "value is not an integer or out of range".to_string(),
+                            )
+                        })?
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, Backend, BulkString, RespNull};
+    use anyhow::Result;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    #[test]
+    fn test_hget_wrong_type() -> Result<()> {
+        let backend = Backend::new();
+        command(&["set", "s", "v"])?.execute(&backend);
+        command(&["hset", "h", "f", "v"])?.execute(&backend);
+
+        let ret = command(&["hget", "s", "f"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::from(CommandError::WrongType));
+        let ret = command(&["hget", "h", "f"])?.execute(&backend);
+        assert_eq!(ret, BulkString::new("v").into());
+        let ret = command(&["hget", "missing", "f"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_field() -> Result<()> {
+        let backend = Backend::new();
+        let field = BulkString::new(b"f\xff\xfe".to_vec());
+        let frames: Vec<RespFrame> = vec![
+            BulkString::new("hset").into(),
+            BulkString::new("h").into(),
+            field.clone().into(),
+            BulkString::new("v").into(),
+        ];
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        let frames: Vec<RespFrame> = vec![
+            BulkString::new("hget").into(),
+            BulkString::new("h").into(),
+            field.clone().into(),
+        ];
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        assert_eq!(cmd.execute(&backend), BulkString::new("v").into());
+
+        let ret = command(&["hkeys", "h"])?.execute(&backend);
+        assert_eq!(ret, RespArray::new([field.into()]).into());
+
+        // fields that aren't text keep apart in HGETALL too
+        backend.hset(
+            "h".to_string(),
+            b"f\xfe\xff".as_slice(),
+            BulkString::new("w").into(),
+        );
+        let RespFrame::Map(map) = command(&["hgetall", "h"])?.execute(&backend) else {
+            panic!("hgetall must reply with a map");
+        };
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(b"f\xff\xfe"), Some(&BulkString::new("v").into()));
+        assert_eq!(map.get(b"f\xfe\xff"), Some(&BulkString::new("w").into()));
+
+        let payload = backend.dump("h").expect("hash must dump");
+        let dest = Backend::new();
+        dest.restore("h".to_string(), &payload, None, false)?;
+        assert_eq!(
+            dest.hget("h", b"f\xff\xfe".as_slice()),
+            Some(BulkString::new("v").into())
+        );
+        assert_eq!(payload[0], 2);
+
+        Ok(())
+    }
+
+    fn fill(backend: &Backend) -> Result<()> {
+        for (f, v) in [("f1", "v1"), ("f2", "v2"), ("f3", "v3")] {
+            command(&["hset", "h", f, v])?.execute(backend);
+        }
+        Ok(())
+    }
+
+    fn fields() -> Vec<RespFrame> {
+        ["f1", "f2", "f3"]
+            .iter()
+            .map(|f| BulkString::new(*f).into())
+            .collect()
+    }
+
+    #[test]
+    fn test_hgetall_ordering() -> Result<()> {
+        let backend = Backend::new();
+        for (f, v) in [("b", "2"), ("c", "3"), ("a", "1")] {
+            command(&["hset", "h", f, v])?.execute(&backend);
+        }
+        command(&["hset", "h", "c", "33"])?.execute(&backend);
+
+        let fields = |frame: RespFrame| -> Vec<(Vec<u8>, RespFrame)> {
+            let RespFrame::Map(map) = frame else {
+                panic!("hgetall must reply with a map");
+            };
+            map.entries()
+                .map(|(k, v)| (k.to_vec(), v.clone()))
+                .collect()
+        };
+        let field = |f: &str, v: &str| (f.as_bytes().to_vec(), BulkString::new(v).into());
+
+        // sorted by name unless configured otherwise
+        let ret = command(&["hgetall", "h"])?.execute(&backend);
+        assert_eq!(
+            fields(ret),
+            vec![field("a", "1"), field("b", "2"), field("c", "33")]
+        );
+
+        // an overwritten field keeps the place of its first insert
+        backend.set_hash_insertion_order(true);
+        let ret = command(&["hgetall", "h"])?.execute(&backend);
+        assert_eq!(
+            fields(ret),
+            vec![field("b", "2"), field("c", "33"), field("a", "1")]
+        );
+
+        // and so does a restored copy
+        let dest = Backend::new();
+        dest.set_hash_insertion_order(true);
+        dest.restore(
+            "h".to_string(),
+            &backend.dump("h").expect("hash must dump"),
+            None,
+            false,
+        )?;
+        let ret = command(&["hgetall", "h"])?.execute(&dest);
+        assert_eq!(
+            fields(ret),
+            vec![field("b", "2"), field("c", "33"), field("a", "1")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hkeys_hvals_order() -> Result<()> {
+        let backend = Backend::new();
+        for (f, v) in [("b", "2"), ("c", "3"), ("a", "1"), ("aa", "4")] {
+            command(&["hset", "h", f, v])?.execute(&backend);
+        }
+
+        let bulks = |values: &[&str]| -> RespFrame {
+            let frames: Vec<RespFrame> =
+                values.iter().map(|v| BulkString::new(*v).into()).collect();
+            RespArray::new(frames).into()
+        };
+        let ret = command(&["hkeys", "h"])?.execute(&backend);
+        assert_eq!(ret, bulks(&["a", "aa", "b", "c"]));
+        let ret = command(&["hvals", "h"])?.execute(&backend);
+        assert_eq!(ret, bulks(&["1", "4", "2", "3"]));
+
+        let ret = command(&["hkeys", "missing"])?.execute(&backend);
+        assert_eq!(ret, bulks(&[]));
+        backend.set("s".to_string(), BulkString::new("v").into());
+        let ret = command(&["hvals", "s"])?.execute(&backend);
+        assert_eq!(ret, CommandError::WrongType.into());
+        assert!(command(&["hkeys"]).is_err());
+
+        // with insertion order on they come as they were added
+        backend.set_hash_insertion_order(true);
+        let ret = command(&["hkeys", "h"])?.execute(&backend);
+        assert_eq!(ret, bulks(&["b", "c", "a", "aa"]));
+        let ret = command(&["hvals", "h"])?.execute(&backend);
+        assert_eq!(ret, bulks(&["2", "3", "1", "4"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_single() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend)?;
+
+        let ret = command(&["hrandfield", "h"])?.execute(&backend);
+        assert!(fields().contains(&ret));
+        let ret = command(&["hrandfield", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Null(RespNull));
+        let ret = command(&["hrandfield", "missing", "2"])?.execute(&backend);
+        assert_eq!(ret, RespArray::new(vec![]).into());
+
+        backend.set("s".to_string(), BulkString::new("v").into());
+        for args in [&["hrandfield", "s"][..], &["hrandfield", "s", "2"]] {
+            let ret = command(args)?.execute(&backend);
+            assert_eq!(ret, CommandError::WrongType.into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_count() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend)?;
+
+        // a positive count never repeats and stops at the size of the hash
+        let RespFrame::Array(ret) = command(&["hrandfield", "h", "2"])?.execute(&backend) else {
+            panic!("hrandfield with a count must reply with an array");
+        };
+        assert_eq!(ret.len(), 2);
+        assert_ne!(ret[0], ret[1]);
+        assert!(ret.iter().all(|f| fields().contains(f)));
+        let RespFrame::Array(mut ret) = command(&["hrandfield", "h", "10"])?.execute(&backend)
+        else {
+            panic!("hrandfield with a count must reply with an array");
+        };
+        ret.0.sort();
+        assert_eq!(ret.0, fields());
+
+        // a negative count may repeat fields
+        let RespFrame::Array(ret) = command(&["hrandfield", "h", "-10"])?.execute(&backend) else {
+            panic!("hrandfield with a count must reply with an array");
+        };
+        assert_eq!(ret.len(), 10);
+        assert!(ret.iter().all(|f| fields().contains(f)));
+
+        assert!(command(&["hrandfield", "h", "-9223372036854775808"]).is_err());
+        assert!(command(&["hrandfield", "h", "4611686018427387904"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_withvalues() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend)?;
+
+        let RespFrame::Array(ret) =
+            command(&["hrandfield", "h", "3", "WITHVALUES"])?.execute(&backend)
+        else {
+            panic!("hrandfield with a count must reply with an array");
+        };
+        assert_eq!(ret.len(), 6);
+        for pair in ret.chunks(2) {
+            let (RespFrame::BulkString(field), RespFrame::BulkString(value)) = (&pair[0], &pair[1])
+            else {
+                panic!("unexpected pair: {:?}", pair);
+            };
+            assert_eq!(field[1..], value[1..]);
+        }
+
+        assert!(command(&["hrandfield", "h", "1", "VALUES"]).is_err());
+        assert!(command(&["hrandfield", "h", "x"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hscan_novalues() -> Result<()> {
+        let backend = Backend::new();
+        for (field, value) in [("a", "1"), ("b", "2"), ("c", "3")] {
+            backend.hset(
+                "h".to_string(),
+                field.to_string(),
+                BulkString::new(value).into(),
+            );
+        }
+
+        let bulks = |items: &[&str]| -> RespFrame {
+            RespArray::new(
+                items
+                    .iter()
+                    .map(|v| BulkString::new(*v).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into()
+        };
+        let reply = |cursor: &str, page: RespFrame| -> RespFrame {
+            RespArray::new(vec![BulkString::new(cursor).into(), page]).into()
+        };
+
+        let ret = command(&["hscan", "h", "0"])?.execute(&backend);
+        assert_eq!(ret, reply("0", bulks(&["a", "1", "b", "2", "c", "3"])));
+        let ret = command(&["hscan", "h", "0", "NOVALUES"])?.execute(&backend);
+        assert_eq!(ret, reply("0", bulks(&["a", "b", "c"])));
+
+        let ret = command(&["hscan", "h", "0", "count", "2", "novalues"])?.execute(&backend);
+        assert_eq!(ret, reply("2", bulks(&["a", "b"])));
+        let ret = command(&["hscan", "h", "2", "count", "2"])?.execute(&backend);
+        assert_eq!(ret, reply("0", bulks(&["c", "3"])));
+        let ret = command(&["hscan", "h", "0", "match", "b*", "novalues"])?.execute(&backend);
+        assert_eq!(ret, reply("0", bulks(&["b"])));
+
+        let ret = command(&["hscan", "missing", "0"])?.execute(&backend);
+        assert_eq!(ret, reply("0", bulks(&[])));
+        assert!(command(&["hscan", "h", "x"]).is_err());
+        assert!(command(&["hscan", "h", "0", "count"]).is_err());
+
+        Ok(())
+    }
+}
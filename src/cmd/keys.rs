@@ -0,0 +1,834 @@
+use super::server::parse_db_index;
+use super::{
+    extract_args, extract_strings, parse_keys, validate_command, CommandExecutor, CopyCmd, Del,
+    Move, Object, RandomKey, Rename, Scan, Type, Unlink,
+};
+use crate::{
+    cmd::CommandError, Backend, BulkString, KeyType, RespArray, RespArrayBuilder, RespFrame,
+    RespNull, SimpleError, SimpleString,
+};
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let removed = self.keys.iter().filter(|key| backend.del(key)).count();
+        RespFrame::Integer(removed as i64)
+    }
+}
+
+impl CommandExecutor for Unlink {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let removed = self.keys.iter().filter(|key| backend.unlink(key)).count();
+        RespFrame::Integer(removed as i64)
+    }
+}
+
+impl CommandExecutor for Move {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.move_key(&self.key, self.db) {
+            Ok(moved) => RespFrame::Integer(moved as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Rename {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.rename_key(&self.key, &self.newkey) {
+            return SimpleError::new("ERR no such key").into();
+        }
+        SimpleString::new("OK").into()
+    }
+}
+
+impl CommandExecutor for CopyCmd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.source == self.destination {
+            return SimpleError::new("ERR source and destination objects are the same").into();
+        }
+        let copied = backend.copy_key(&self.source, &self.destination, self.replace);
+        RespFrame::Integer(copied as i64)
+    }
+}
+
+impl CommandExecutor for Type {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let name = backend.key_type(&self.key).map_or("none", |ty| ty.as_str());
+        SimpleString::new(name).into()
+    }
+}
+
+impl CommandExecutor for Scan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (next, keys) = backend.scan(
+            self.cursor,
+            self.pattern.as_deref(),
+            self.count,
+            self.key_type,
+        );
+        let mut page = RespArrayBuilder::with_capacity(keys.len());
+        for key in keys {
+            page.push_bulk(key);
+        }
+        RespArrayBuilder::with_capacity(2)
+            .push_bulk(next.to_string())
+            .push_frame(page.build())
+            .build()
+            .into()
+    }
+}
+
+impl CommandExecutor for RandomKey {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.random_key() {
+            Some(key) => BulkString::new(key).into(),
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for Object {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            Object::Encoding(key) => match backend.object_encoding(&key) {
+                Some(encoding) => BulkString::new(encoding).into(),
+                None => RespFrame::Null(RespNull),
+            },
+            Object::IdleTime(key) => match backend.object_idletime(&key) {
+                Some(secs) => RespFrame::Integer(secs as i64),
+                None => RespFrame::Null(RespNull),
+            },
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Del {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Del {
+            keys: parse_keys(value, "del")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Unlink {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Unlink {
+            keys: parse_keys(value, "unlink")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Move {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["move"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let db = parse_db_index(args.next())?;
+        Ok(Move { key, db })
+    }
+}
+
+impl TryFrom<RespArray> for Rename {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["rename"], 2)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(newkey)) => Ok(Rename { key, newkey }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for CopyCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["copy"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        let (Some(source), Some(destination)) = (args.next(), args.next()) else {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'copy' command".to_string(),
+            ));
+        };
+        let mut replace = false;
+        for option in args {
+            if !option.eq_ignore_ascii_case("replace") {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            }
+            replace = true;
+        }
+        Ok(CopyCmd {
+            source,
+            destination,
+            replace,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Type {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["type"], 1)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        Ok(Type {
+            key: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+// cursor [MATCH pattern] [COUNT count] [TYPE type]
+impl TryFrom<RespArray> for Scan {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "scan")?.into_iter();
+        let cursor = args
+            .next()
+            .and_then(|c| c.parse::<u64>().ok())
+            .ok_or_else(|| CommandError::InvalidArgument("invalid cursor".to_string()))?;
+        let mut scan = Scan {
+            cursor,
+            pattern: None,
+            count: 10,
+            key_type: None,
+        };
+        while let Some(opt) = args.next() {
+            let Some(arg) = args.next() else {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            };
+            match opt.to_ascii_lowercase().as_str() {
+                "match" => scan.pattern = Some(arg),
+                "count" => {
+                    scan.count = arg
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| {
+                            CommandError::InvalidArgument(
+                                "value is not an integer or out of range".to_string(),
+                            )
+                        })?
+                }
+                "type" => {
+                    scan.key_type = Some(KeyType::from_name(&arg).ok_or_else(|| {
+                        CommandError::InvalidArgument(format!("unknown type name '{}'", arg))
+                    })?)
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+impl TryFrom<RespArray> for RandomKey {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["randomkey"], 0)?;
+        Ok(RandomKey)
+    }
+}
+
+impl TryFrom<RespArray> for Object {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "object")?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(sub), Some(key), None) if sub.eq_ignore_ascii_case("encoding") => {
+                Ok(Object::Encoding(key))
+            }
+            (Some(sub), Some(key), None) if sub.eq_ignore_ascii_case("idletime") => {
+                Ok(Object::IdleTime(key))
+            }
+            (Some(sub), _, _) => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'",
+                sub
+            ))),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString};
+    use anyhow::Result;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn fill(backend: &Backend) {
+        backend.set("s".to_string(), BulkString::new("v").into());
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        backend.rpush("l".to_string(), BulkString::new("v").into());
+    }
+
+    #[test]
+    fn test_del() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+
+        let ret = command(&["del", "s", "h", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        assert!(!backend.exists("s"));
+        assert!(!backend.exists("h"));
+        assert!(backend.exists("l"));
+
+        let ret = command(&["del", "s"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert!(command(&["del"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let db1 = backend.database(1).expect("database 1 exists");
+        fill(&backend);
+        let deadline = Instant::now() + Duration::from_secs(100);
+        backend.expire_at("h", deadline);
+
+        let ret = command(&["move", "h", "1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert!(!backend.exists("h"));
+        assert_eq!(db1.hget("h", "f"), Some(BulkString::new("v").into()));
+        assert_eq!(db1.expire_deadline("h"), Some(deadline));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_keeps_ttl() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        let deadline = Instant::now() + Duration::from_secs(100);
+        backend.expire_at("h", deadline);
+
+        let ret = command(&["rename", "h", "s"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert!(!backend.exists("h"));
+        assert_eq!(backend.expire_deadline("h"), None);
+        // the old string under the new name is gone, the hash took its place
+        assert_eq!(backend.hget("s", "f"), Some(BulkString::new("v").into()));
+        assert_eq!(backend.expire_deadline("s"), Some(deadline));
+
+        // renaming over a key with a ttl doesn't keep that ttl
+        backend.expire_at("s", deadline);
+        let ret = command(&["rename", "l", "s"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert_eq!(backend.key_type("s"), Some(KeyType::List));
+        assert_eq!(backend.expire_deadline("s"), None);
+
+        let ret = command(&["rename", "missing", "s"])?.execute(&backend);
+        assert_eq!(ret, SimpleError::new("ERR no such key").into());
+        let ret = command(&["rename", "s", "s"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert_eq!(backend.key_type("s"), Some(KeyType::List));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_drops_ttl() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        let deadline = Instant::now() + Duration::from_secs(100);
+        backend.expire_at("h", deadline);
+
+        let ret = command(&["copy", "h", "h2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert_eq!(backend.hget("h2", "f"), Some(BulkString::new("v").into()));
+        assert_eq!(backend.expire_deadline("h2"), None);
+        // the source is untouched, and the copy doesn't share its fields
+        assert_eq!(backend.expire_deadline("h"), Some(deadline));
+        backend.hset(
+            "h2".to_string(),
+            "f".to_string(),
+            BulkString::new("w").into(),
+        );
+        assert_eq!(backend.hget("h", "f"), Some(BulkString::new("v").into()));
+
+        let ret = command(&["copy", "s", "h2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert_eq!(backend.key_type("h2"), Some(KeyType::Hash));
+        let ret = command(&["copy", "s", "h2", "REPLACE"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert_eq!(
+            backend.get("h2")?.as_deref(),
+            Some(&BulkString::new("v").into())
+        );
+
+        let ret = command(&["copy", "missing", "x"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["copy", "s", "s"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        assert!(command(&["copy", "s", "x", "db"]).is_err());
+        assert!(command(&["copy", "s"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_missing_or_taken() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let db1 = backend.database(1).expect("database 1 exists");
+        fill(&backend);
+        db1.set("s".to_string(), BulkString::new("taken").into());
+
+        let ret = command(&["move", "missing", "1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        let ret = command(&["move", "s", "1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        // neither side was touched
+        assert_eq!(
+            backend.get("s")?.as_deref(),
+            Some(&BulkString::new("v").into())
+        );
+        assert_eq!(
+            db1.get("s")?.as_deref(),
+            Some(&BulkString::new("taken").into())
+        );
+
+        let ret = command(&["move", "s", "0"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        let ret = command(&["move", "s", "2"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        assert!(command(&["move", "s"]).is_err());
+        // the same error as SELECT's
+        let err = command(&["move", "s", "-1"]).expect_err("negative index");
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument: DB index is out of range"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlink() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+
+        let ret = command(&["unlink", "s", "h", "l", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        assert!(!backend.exists("s"));
+        assert!(!backend.exists("h"));
+        assert!(!backend.exists("l"));
+
+        let ret = command(&["unlink", "s"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert!(command(&["unlink"]).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlink_large_set() -> Result<()> {
+        let backend = Backend::new();
+        let set = backend.dset.entry("big".to_string()).or_default();
+        for i in 0..200_000 {
+            set.insert(RespFrame::Integer(i));
+        }
+        drop(set);
+
+        let start = Instant::now();
+        let ret = command(&["unlink", "big"])?.execute(&backend);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert!(!backend.exists("big"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type() -> Result<()> {
+        let backend = Backend::populated();
+
+        for (key, name) in [
+            ("s", "string"),
+            ("h", "hash"),
+            ("l", "list"),
+            ("set", "set"),
+            ("z", "zset"),
+            ("missing", "none"),
+        ] {
+            let ret = command(&["type", key])?.execute(&backend);
+            assert_eq!(ret, SimpleString::new(name).into());
+        }
+
+        Ok(())
+    }
+
+    fn scan_all(backend: &Backend, args: &[&str]) -> Result<Vec<RespFrame>> {
+        let mut cursor = "0".to_string();
+        let mut keys = Vec::new();
+        loop {
+            let mut cmd = vec!["scan", cursor.as_str(), "COUNT", "2"];
+            cmd.extend_from_slice(args);
+            let RespFrame::Array(reply) = command(&cmd)?.execute(backend) else {
+                panic!("scan should reply with an array");
+            };
+            let (RespFrame::BulkString(next), RespFrame::Array(page)) = (&reply[0], &reply[1])
+            else {
+                panic!("unexpected scan reply: {:?}", reply);
+            };
+            keys.extend(page.iter().cloned());
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                // pages come in hash order
+                keys.sort();
+                return Ok(keys);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        backend.hset(
+            "h2".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        command(&["sadd", "set", "m"])?.execute(&backend);
+
+        let keys = scan_all(&backend, &[])?;
+        let expected: Vec<RespFrame> = ["h", "h2", "l", "s", "set"]
+            .iter()
+            .map(|k| BulkString::new(*k).into())
+            .collect();
+        assert_eq!(keys, expected);
+
+        let keys = scan_all(&backend, &["MATCH", "s*"])?;
+        assert_eq!(
+            keys,
+            vec![BulkString::new("s").into(), BulkString::new("set").into()]
+        );
+
+        assert!(command(&["scan"]).is_err());
+        assert!(command(&["scan", "x"]).is_err());
+        assert!(command(&["scan", "0", "COUNT"]).is_err());
+
+        command(&["zadd", "z", "1", "m"])?.execute(&backend);
+        let keys = scan_all(&backend, &["TYPE", "zset"])?;
+        assert_eq!(keys, vec![BulkString::new("z").into()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_keeps_place_after_deletes() -> Result<()> {
+        let backend = Backend::new();
+        for i in 0..20 {
+            backend.set(format!("k{}", i), BulkString::new("v").into());
+        }
+        let (mut cursor, first) = backend.scan(0, None, 5, None);
+        assert_eq!(first.len(), 5);
+        // removing what was already returned doesn't make the scan skip anything
+        for key in &first {
+            backend.del(key);
+        }
+        let mut seen: HashSet<String> = first.into_iter().collect();
+        while cursor != 0 {
+            let (next, page) = backend.scan(cursor, None, 5, None);
+            seen.extend(page);
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_concurrent_writes() -> Result<()> {
+        let backend = Backend::new();
+        let stable: Vec<String> = (0..200).map(|i| format!("stable:{}", i)).collect();
+        for key in &stable {
+            backend.set(key.clone(), BulkString::new("v").into());
+        }
+
+        // keeps adding and deleting other keys while the scans run
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (backend, done) = (backend.clone(), done.clone());
+            thread::spawn(move || {
+                let mut i = 0u64;
+                while !done.load(Ordering::Relaxed) {
+                    backend.set(format!("churn:{}", i), BulkString::new("v").into());
+                    if i >= 50 {
+                        backend.del(&format!("churn:{}", i - 50));
+                    }
+                    i += 1;
+                }
+            })
+        };
+
+        for _ in 0..20 {
+            let mut seen = HashSet::new();
+            let mut cursor = 0;
+            loop {
+                let (next, page) = backend.scan(cursor, None, 7, None);
+                seen.extend(page);
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+            for key in &stable {
+                assert!(seen.contains(key), "{} was skipped", key);
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+        writer.join().expect("writer thread panicked");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_type() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        backend.hset(
+            "h2".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        command(&["sadd", "set", "m"])?.execute(&backend);
+
+        let keys = scan_all(&backend, &["TYPE", "hash"])?;
+        assert_eq!(
+            keys,
+            vec![BulkString::new("h").into(), BulkString::new("h2").into()]
+        );
+        let keys = scan_all(&backend, &["type", "LIST"])?;
+        assert_eq!(keys, vec![BulkString::new("l").into()]);
+
+        Ok(())
+    }
+
+    fn encoding(backend: &Backend, key: &str) -> Result<RespFrame> {
+        Ok(command(&["object", "encoding", key])?.execute(backend))
+    }
+
+    #[test]
+    fn test_object_encoding_set() -> Result<()> {
+        let backend = Backend::new();
+        for i in 0..200 {
+            command(&["sadd", "ints", &i.to_string()])?.execute(&backend);
+        }
+        assert_eq!(
+            encoding(&backend, "ints")?,
+            BulkString::new("intset").into()
+        );
+
+        // too many members for a listpack
+        command(&["sadd", "ints", "x"])?.execute(&backend);
+        assert_eq!(
+            encoding(&backend, "ints")?,
+            BulkString::new("hashtable").into()
+        );
+        // removing the member doesn't convert the set back
+        backend
+            .dset
+            .get("ints")
+            .unwrap()
+            .remove(&RespFrame::from(BulkString::new("x")));
+        assert_eq!(
+            encoding(&backend, "ints")?,
+            BulkString::new("hashtable").into()
+        );
+
+        for m in ["1", "2"] {
+            command(&["sadd", "small", m])?.execute(&backend);
+        }
+        assert_eq!(
+            encoding(&backend, "small")?,
+            BulkString::new("intset").into()
+        );
+        command(&["sadd", "small", "a"])?.execute(&backend);
+        assert_eq!(
+            encoding(&backend, "small")?,
+            BulkString::new("listpack").into()
+        );
+
+        // "01" is not how an integer prints, so it can't live in an intset
+        command(&["sadd", "padded", "01"])?.execute(&backend);
+        assert_eq!(
+            encoding(&backend, "padded")?,
+            BulkString::new("listpack").into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_encoding() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        backend.set("n".to_string(), BulkString::new("12").into());
+        assert_eq!(encoding(&backend, "s")?, BulkString::new("embstr").into());
+        assert_eq!(encoding(&backend, "n")?, BulkString::new("int").into());
+        assert_eq!(encoding(&backend, "h")?, BulkString::new("listpack").into());
+        assert_eq!(encoding(&backend, "l")?, BulkString::new("listpack").into());
+        assert_eq!(encoding(&backend, "missing")?, RespFrame::Null(RespNull));
+
+        assert!(command(&["object", "encoding"]).is_err());
+        assert!(command(&["object", "freq", "s"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_encoding_thresholds() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.encoding_threshold("hash-max-listpack-entries"),
+            Some(128)
+        );
+        assert!(backend.set_encoding_threshold("hash-max-listpack-entries", 2));
+        assert!(backend.set_encoding_threshold("SET-MAX-INTSET-ENTRIES", 2));
+        assert!(backend.set_encoding_threshold("list-max-listpack-size", 2));
+        assert!(!backend.set_encoding_threshold("zset-max-listpack-entries", 2));
+        assert_eq!(backend.encoding_threshold("nosuch"), None);
+
+        let listpack: RespFrame = BulkString::new("listpack").into();
+        for field in ["a", "b"] {
+            command(&["hset", "h", field, "v"])?.execute(&backend);
+        }
+        assert_eq!(encoding(&backend, "h")?, listpack);
+        command(&["hset", "h", "c", "v"])?.execute(&backend);
+        assert_eq!(
+            encoding(&backend, "h")?,
+            BulkString::new("hashtable").into()
+        );
+
+        for member in ["1", "2"] {
+            command(&["sadd", "s", member])?.execute(&backend);
+        }
+        assert_eq!(encoding(&backend, "s")?, BulkString::new("intset").into());
+        command(&["sadd", "s", "3"])?.execute(&backend);
+        assert_eq!(encoding(&backend, "s")?, listpack);
+
+        command(&["rpush", "l", "a", "b"])?.execute(&backend);
+        assert_eq!(encoding(&backend, "l")?, listpack);
+        command(&["rpush", "l", "c"])?.execute(&backend);
+        assert_eq!(
+            encoding(&backend, "l")?,
+            BulkString::new("quicklist").into()
+        );
+
+        // raising the threshold again doesn't turn the hash back into a listpack
+        backend.set_encoding_threshold("hash-max-listpack-entries", 3);
+        assert_eq!(
+            encoding(&backend, "h")?,
+            BulkString::new("hashtable").into()
+        );
+
+        Ok(())
+    }
+
+    fn idletime(backend: &Backend, key: &str) -> Result<RespFrame> {
+        Ok(command(&["object", "idletime", key])?.execute(backend))
+    }
+
+    // pretend nothing touched the key for a while
+    fn age(backend: &Backend, key: &str) {
+        let past = Instant::now() - Duration::from_secs(100);
+        backend.last_access.insert(key.to_string(), past);
+    }
+
+    #[test]
+    fn test_object_idletime() -> Result<()> {
+        let backend = Backend::new();
+        fill(&backend);
+        age(&backend, "s");
+        assert_eq!(idletime(&backend, "s")?, RespFrame::Integer(100));
+        // asking doesn't count as an access
+        assert_eq!(idletime(&backend, "s")?, RespFrame::Integer(100));
+        assert_eq!(idletime(&backend, "missing")?, RespFrame::Null(RespNull));
+
+        backend.get("s")?;
+        assert_eq!(idletime(&backend, "s")?, RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_idletime_reset_by_writes() -> Result<()> {
+        let backend = Backend::populated();
+        let writes: [(&str, &[&str]); 5] = [
+            ("s", &["set", "s", "w"]),
+            ("h", &["hset", "h", "f", "w"]),
+            ("set", &["sadd", "set", "w"]),
+            ("l", &["rpush", "l", "w"]),
+            ("s", &["expire", "s", "100"]),
+        ];
+        for (key, write) in writes {
+            age(&backend, key);
+            command(write)?.execute(&backend);
+            assert_eq!(
+                idletime(&backend, key)?,
+                RespFrame::Integer(0),
+                "{:?}",
+                write
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_randomkey_skips_expired() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            command(&["randomkey"])?.execute(&backend),
+            RespFrame::Null(RespNull)
+        );
+
+        backend.set("live".to_string(), BulkString::new("v").into());
+        backend.expire_at("live", Instant::now() + Duration::from_secs(100));
+        for i in 0..200 {
+            let key = format!("dead{}", i);
+            backend.set(key.clone(), BulkString::new("v").into());
+            // expired, but nothing has looked at it yet
+            backend
+                .expires
+                .insert(key, Instant::now() - Duration::from_secs(1));
+        }
+
+        for _ in 0..10 {
+            let ret = command(&["randomkey"])?.execute(&backend);
+            assert_eq!(ret, BulkString::new("live").into());
+        }
+        // the expired picks were reclaimed along the way
+        assert!(backend.map.len() < 201);
+
+        backend.del("live");
+        assert_eq!(
+            command(&["randomkey"])?.execute(&backend),
+            RespFrame::Null(RespNull)
+        );
+        assert!(command(&["randomkey", "x"]).is_err());
+
+        Ok(())
+    }
+}
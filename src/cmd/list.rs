@@ -0,0 +1,636 @@
+use super::{
+    extract_args, parse_int, validate_command, BLPop, BRPop, CommandExecutor, LInsert, LPop, LPos,
+    LPush, RPop, RPush,
+};
+use crate::{
+    cmd::CommandError, Backend, BulkString, KeyType, RespArray, RespFrame, RespNull, RespNullArray,
+    SimpleError,
+};
+use futures::future::select_all;
+use std::time::Duration;
+use tokio::time::{timeout_at, Instant};
+
+impl CommandExecutor for LPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.lpush_many(self.key, self.values))
+    }
+}
+
+impl CommandExecutor for RPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.rpush_many(self.key, self.values))
+    }
+}
+
+impl CommandExecutor for LPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lpop(&self.key) {
+            Some(value) => value,
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for RPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rpop(&self.key) {
+            Some(value) => value,
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for LPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let rank = self.rank.unwrap_or(1);
+        match self.count {
+            Some(count) => {
+                let found: Vec<RespFrame> = backend
+                    .lpos(&self.key, &self.element, rank, count)
+                    .into_iter()
+                    .map(|i| RespFrame::Integer(i as i64))
+                    .collect();
+                RespArray::new(found).into()
+            }
+            None => match backend.lpos(&self.key, &self.element, rank, 1).first() {
+                Some(i) => RespFrame::Integer(*i as i64),
+                None => RespFrame::Null(RespNull),
+            },
+        }
+    }
+}
+
+impl CommandExecutor for LInsert {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::List) {
+            return e.into();
+        }
+        RespFrame::Integer(backend.linsert(&self.key, self.before, &self.pivot, self.element))
+    }
+}
+
+// without a connection to block on, the blocking pops only try once
+impl CommandExecutor for BLPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        try_pop(backend, &self.keys, Backend::lpop).unwrap_or(RespFrame::NullArray(RespNullArray))
+    }
+}
+
+impl CommandExecutor for BRPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        try_pop(backend, &self.keys, Backend::rpop).unwrap_or(RespFrame::NullArray(RespNullArray))
+    }
+}
+
+impl BLPop {
+    pub async fn wait(self, backend: &Backend) -> RespFrame {
+        blocking_pop(backend, &self.keys, self.timeout, Backend::lpop).await
+    }
+}
+
+impl BRPop {
+    pub async fn wait(self, backend: &Backend) -> RespFrame {
+        blocking_pop(backend, &self.keys, self.timeout, Backend::rpop).await
+    }
+}
+
+fn try_pop(
+    backend: &Backend,
+    keys: &[String],
+    pop: fn(&Backend, &str) -> Option<RespFrame>,
+) -> Option<RespFrame> {
+    keys.iter().find_map(|key| {
+        pop(backend, key)
+            .map(|value| RespArray::new(vec![BulkString::new(key.as_str()).into(), value]).into())
+    })
+}
+
+async fn blocking_pop(
+    backend: &Backend,
+    keys: &[String],
+    timeout: f64,
+    pop: fn(&Backend, &str) -> Option<RespFrame>,
+) -> RespFrame {
+    let notifiers: Vec<_> = keys.iter().map(|key| backend.list_notifier(key)).collect();
+    // a timeout too far out to be an instant is refused rather than waited on
+    let deadline = if timeout > 0.0 {
+        match Duration::try_from_secs_f64(timeout)
+            .ok()
+            .and_then(|timeout| Instant::now().checked_add(timeout))
+        {
+            Some(deadline) => Some(deadline),
+            None => return SimpleError::new("ERR timeout is not a float or out of range").into(),
+        }
+    } else {
+        None
+    };
+    loop {
+        // register interest before checking, so a push in between is not missed
+        let notified = select_all(notifiers.iter().map(|n| Box::pin(n.notified())));
+        if let Some(frame) = try_pop(backend, keys, pop) {
+            return frame;
+        }
+        match deadline {
+            Some(deadline) => {
+                if timeout_at(deadline, notified).await.is_err() {
+                    return RespFrame::NullArray(RespNullArray);
+                }
+            }
+            None => {
+                notified.await;
+            }
+        }
+    }
+}
+
+impl TryFrom<RespArray> for LPush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = parse_push_args(value, "lpush")?;
+        Ok(LPush { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for RPush {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = parse_push_args(value, "rpush")?;
+        Ok(RPush { key, values })
+    }
+}
+
+// key element [element ...]
+fn parse_push_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<RespFrame>), CommandError> {
+    let n_args = value.len() - 1;
+    if n_args < 2 {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have at least 2 arguments",
+            name
+        )));
+    }
+    validate_command(&value, &[name], n_args)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok((String::from_utf8(key.0)?, args.collect())),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid key or value".to_string(),
+        )),
+    }
+}
+
+impl TryFrom<RespArray> for LPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lpop"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(LPop {
+                key: key.into_string()?,
+            }),
+            None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for RPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["rpop"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(RPop {
+                key: key.into_string()?,
+            }),
+            None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+// key element [RANK rank] [COUNT num-matches]
+impl TryFrom<RespArray> for LPos {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if n_args < 2 {
+            return Err(CommandError::InvalidArgument(
+                "lpos command must have at least 2 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["lpos"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (key, element) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(element)) => {
+                (String::from_utf8(key.0)?, element)
+            }
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let mut lpos = LPos {
+            key,
+            element,
+            rank: None,
+            count: None,
+        };
+        while let Some(opt) = args.next() {
+            let RespFrame::BulkString(opt) = opt else {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            };
+            match opt.to_ascii_lowercase().as_slice() {
+                b"rank" => match parse_int(args.next())? {
+                    0 => {
+                        return Err(CommandError::InvalidArgument(
+                            "RANK can't be zero".to_string(),
+                        ))
+                    }
+                    rank => lpos.rank = Some(rank),
+                },
+                b"count" => {
+                    let count = usize::try_from(parse_int(args.next())?).map_err(|_| {
+                        CommandError::InvalidArgument("COUNT can't be negative".to_string())
+                    })?;
+                    lpos.count = Some(count);
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(lpos)
+    }
+}
+
+impl TryFrom<RespArray> for BLPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = parse_blocking_args(value, "blpop")?;
+        Ok(BLPop { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for BRPop {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = parse_blocking_args(value, "brpop")?;
+        Ok(BRPop { keys, timeout })
+    }
+}
+
+// key [key ...] timeout
+fn parse_blocking_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Vec<String>, f64), CommandError> {
+    let n_args = value.len() - 1;
+    if n_args < 2 {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have at least 2 arguments",
+            name
+        )));
+    }
+    validate_command(&value, &[name], n_args)?;
+
+    let mut args = extract_args(value, 1)?;
+    let timeout = match args.pop() {
+        Some(RespFrame::BulkString(timeout)) => String::from_utf8(timeout.0)?
+            .parse::<f64>()
+            .ok()
+            .filter(|t| t.is_finite() && *t >= 0.0)
+            .ok_or_else(|| CommandError::InvalidArgument("Invalid timeout".to_string()))?,
+        _ => return Err(CommandError::InvalidArgument("Invalid timeout".to_string())),
+    };
+    let keys = args
+        .into_iter()
+        .map(|x| match x {
+            RespFrame::BulkString(key) => String::from_utf8(key.0).map_err(CommandError::from),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((keys, timeout))
+}
+
+impl TryFrom<RespArray> for LInsert {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["linsert"], 4)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (Some(key), Some(RespFrame::BulkString(place)), Some(pivot), Some(element)) => {
+                let before = if place.eq_ignore_ascii_case("before") {
+                    true
+                } else if place.eq_ignore_ascii_case("after") {
+                    false
+                } else {
+                    return Err(CommandError::InvalidArgument("syntax error".to_string()));
+                };
+                Ok(LInsert {
+                    key: key.into_string()?,
+                    before,
+                    pivot,
+                    element,
+                })
+            }
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn parse(raw: &[u8]) -> Result<Command> {
+        let mut buf = BytesMut::from(raw);
+        let frame = RespArray::decode(&mut buf)?;
+        Ok(frame.try_into()?)
+    }
+
+    #[test]
+    fn test_push_pop() -> Result<()> {
+        let backend = Backend::new();
+        let ret = parse(b"*3\r\n$5\r\nlpush\r\n$4\r\nlkey\r\n$1\r\na\r\n")?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = parse(b"*3\r\n$5\r\nrpush\r\n$4\r\nlkey\r\n$1\r\nb\r\n")?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+
+        let ret = parse(b"*2\r\n$4\r\nrpop\r\n$4\r\nlkey\r\n")?.execute(&backend);
+        assert_eq!(ret, BulkString::new("b").into());
+        let ret = parse(b"*2\r\n$4\r\nlpop\r\n$4\r\nlkey\r\n")?.execute(&backend);
+        assert_eq!(ret, BulkString::new("a").into());
+        let ret = parse(b"*2\r\n$4\r\nlpop\r\n$4\r\nlkey\r\n")?.execute(&backend);
+        assert_eq!(ret, RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+
+    fn list(backend: &Backend, key: &str) -> Vec<RespFrame> {
+        std::iter::from_fn(|| backend.lpop(key)).collect()
+    }
+
+    fn bulks(items: &[&str]) -> Vec<RespFrame> {
+        items.iter().map(|v| BulkString::new(*v).into()).collect()
+    }
+
+    #[test]
+    fn test_push_many() -> Result<()> {
+        let backend = Backend::new();
+        let ret = parse(b"*5\r\n$5\r\nlpush\r\n$1\r\nl\r\n$2\r\nv1\r\n$2\r\nv2\r\n$2\r\nv3\r\n")?
+            .execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        // pushed one at a time to the head, so the last value comes first
+        assert_eq!(list(&backend, "l"), bulks(&["v3", "v2", "v1"]));
+
+        let ret = parse(b"*5\r\n$5\r\nrpush\r\n$1\r\nr\r\n$2\r\nv1\r\n$2\r\nv2\r\n$2\r\nv3\r\n")?
+            .execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        let ret =
+            parse(b"*4\r\n$5\r\nlpush\r\n$1\r\nr\r\n$2\r\nv0\r\n$3\r\nv-1\r\n")?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(5));
+        assert_eq!(list(&backend, "r"), bulks(&["v-1", "v0", "v1", "v2", "v3"]));
+
+        assert!(parse(b"*2\r\n$5\r\nlpush\r\n$1\r\nl\r\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blpop_parse() -> Result<()> {
+        let cmd: BLPop = match parse(b"*4\r\n$5\r\nblpop\r\n$2\r\nk1\r\n$2\r\nk2\r\n$3\r\n0.5\r\n")?
+        {
+            Command::BLPop(cmd) => cmd,
+            cmd => panic!("unexpected command: {:?}", cmd),
+        };
+        assert_eq!(cmd.keys, vec!["k1".to_string(), "k2".to_string()]);
+        assert_eq!(cmd.timeout, 0.5);
+
+        assert!(parse(b"*2\r\n$5\r\nblpop\r\n$2\r\nk1\r\n").is_err());
+        assert!(parse(b"*3\r\n$5\r\nbrpop\r\n$2\r\nk1\r\n$2\r\n-1\r\n").is_err());
+        assert!(parse(b"*3\r\n$5\r\nblpop\r\n$2\r\nk1\r\n$3\r\ninf\r\n").is_err());
+        assert!(parse(b"*3\r\n$5\r\nblpop\r\n$2\r\nk1\r\n$3\r\nnan\r\n").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_timeout() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = BLPop {
+            keys: vec!["empty".to_string()],
+            timeout: 0.05,
+        };
+        assert_eq!(
+            cmd.wait(&backend).await,
+            RespFrame::NullArray(RespNullArray)
+        );
+
+        // finite, but no instant is that far out
+        let cmd = BLPop {
+            keys: vec!["empty".to_string()],
+            timeout: 1e300,
+        };
+        assert_eq!(
+            cmd.wait(&backend).await,
+            SimpleError::new("ERR timeout is not a float or out of range").into()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_unblocked_by_lpush() -> Result<()> {
+        let backend = Backend::new();
+        let waiter = {
+            let backend = backend.clone();
+            tokio::spawn(async move {
+                let cmd = BLPop {
+                    keys: vec!["other".to_string(), "queue".to_string()],
+                    timeout: 0.0,
+                };
+                cmd.wait(&backend).await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        parse(b"*3\r\n$5\r\nlpush\r\n$5\r\nqueue\r\n$4\r\njob1\r\n")?.execute(&backend);
+
+        let ret = tokio::time::timeout(Duration::from_secs(1), waiter).await??;
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("queue").into(),
+            BulkString::new("job1").into(),
+        ])
+        .into();
+        assert_eq!(ret, expected);
+        assert!(backend.lpop("queue").is_none());
+        // nobody waits on the keys anymore
+        assert!(backend.list_notify.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_forgets_keys_nobody_waits_on() -> Result<()> {
+        let backend = Backend::new();
+        let blpop = |key: &str, timeout| BLPop {
+            keys: vec![key.to_string()],
+            timeout,
+        };
+        blpop("a", 0.01).wait(&backend).await;
+        assert!(backend.list_notify.is_empty());
+
+        // two waiters on one key, the notifier stays until both are gone
+        let first = tokio::spawn({
+            let backend = backend.clone();
+            async move { blpop("k", 0.0).wait(&backend).await }
+        });
+        blpop("k", 0.05).wait(&backend).await;
+        assert!(backend.list_notify.contains_key("k"));
+        // a client that went away while blocked
+        first.abort();
+        assert!(first.await.is_err_and(|e| e.is_cancelled()));
+        assert!(backend.list_notify.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_brpop_existing_element() -> Result<()> {
+        let backend = Backend::new();
+        backend.rpush("queue".to_string(), BulkString::new("a").into());
+        backend.rpush("queue".to_string(), BulkString::new("b").into());
+        let cmd = BRPop {
+            keys: vec!["queue".to_string()],
+            timeout: 1.0,
+        };
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("queue").into(),
+            BulkString::new("b").into(),
+        ])
+        .into();
+        assert_eq!(cmd.wait(&backend).await, expected);
+
+        Ok(())
+    }
+
+    fn lpos(args: &[&str], backend: &Backend) -> Result<RespFrame> {
+        let frames: Vec<RespFrame> = ["lpos"]
+            .iter()
+            .chain(args)
+            .map(|a| BulkString::new(*a).into())
+            .collect();
+        let cmd: Command = RespArray::new(frames).try_into()?;
+        Ok(cmd.execute(backend))
+    }
+
+    fn int_array(items: &[i64]) -> RespFrame {
+        RespArray::new(
+            items
+                .iter()
+                .map(|i| RespFrame::Integer(*i))
+                .collect::<Vec<_>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_lpos() -> Result<()> {
+        let backend = Backend::new();
+        for v in ["a", "b", "c", "b", "d", "b"] {
+            backend.rpush("l".to_string(), BulkString::new(v).into());
+        }
+
+        assert_eq!(lpos(&["l", "b"], &backend)?, RespFrame::Integer(1));
+        assert_eq!(lpos(&["l", "x"], &backend)?, RespFrame::Null(RespNull));
+        assert_eq!(
+            lpos(&["missing", "b"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            lpos(&["l", "b", "RANK", "2"], &backend)?,
+            RespFrame::Integer(3)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lpos_count_and_rank() -> Result<()> {
+        let backend = Backend::new();
+        for v in ["a", "b", "c", "b", "d", "b"] {
+            backend.rpush("l".to_string(), BulkString::new(v).into());
+        }
+
+        assert_eq!(
+            lpos(&["l", "b", "COUNT", "2"], &backend)?,
+            int_array(&[1, 3])
+        );
+        assert_eq!(
+            lpos(&["l", "b", "COUNT", "0"], &backend)?,
+            int_array(&[1, 3, 5])
+        );
+        assert_eq!(lpos(&["l", "x", "COUNT", "0"], &backend)?, int_array(&[]));
+        assert_eq!(
+            lpos(&["l", "b", "RANK", "-1"], &backend)?,
+            RespFrame::Integer(5)
+        );
+        assert_eq!(
+            lpos(&["l", "b", "rank", "-1", "count", "2"], &backend)?,
+            int_array(&[5, 3])
+        );
+        assert_eq!(
+            lpos(&["l", "b", "RANK", "2", "COUNT", "0"], &backend)?,
+            int_array(&[3, 5])
+        );
+
+        assert!(lpos(&["l", "b", "RANK", "0"], &backend).is_err());
+        assert!(lpos(&["l", "b", "COUNT", "-1"], &backend).is_err());
+        assert!(lpos(&["l", "b", "MAXLEN"], &backend).is_err());
+        assert!(lpos(&["l"], &backend).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_linsert() -> Result<()> {
+        let backend = Backend::new();
+        for v in ["a", "b", "c", "b"] {
+            backend.rpush("l".to_string(), BulkString::new(v).into());
+        }
+        let linsert = |args: &[&str]| -> Result<RespFrame> {
+            let frames: Vec<RespFrame> = ["linsert"]
+                .iter()
+                .chain(args)
+                .map(|a| BulkString::new(*a).into())
+                .collect();
+            let cmd: Command = RespArray::new(frames).try_into()?;
+            Ok(cmd.execute(&backend))
+        };
+
+        // only the first occurrence of the pivot counts
+        assert_eq!(linsert(&["l", "BEFORE", "b", "x"])?, RespFrame::Integer(5));
+        assert_eq!(linsert(&["l", "after", "b", "y"])?, RespFrame::Integer(6));
+        assert_eq!(linsert(&["l", "after", "z", "y"])?, RespFrame::Integer(-1));
+        assert_eq!(
+            linsert(&["missing", "before", "b", "x"])?,
+            RespFrame::Integer(0)
+        );
+        assert!(!backend.exists("missing"));
+
+        let items: Vec<RespFrame> = std::iter::from_fn(|| backend.lpop("l")).collect();
+        let expected: Vec<RespFrame> = ["a", "x", "b", "y", "c", "b"]
+            .iter()
+            .map(|v| BulkString::new(*v).into())
+            .collect();
+        assert_eq!(items, expected);
+
+        let parse_args =
+            |args: &[&str]| Command::from_args(args.iter().map(|a| a.to_string()).collect());
+        assert!(parse_args(&["linsert", "l", "beside", "b", "x"]).is_err());
+        assert!(parse_args(&["linsert", "l", "before", "b"]).is_err());
+
+        Ok(())
+    }
+}
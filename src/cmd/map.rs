@@ -1,25 +1,165 @@
-use super::{extract_args, validate_command, CommandExecutor, Set, RESP_OK};
+use super::{
+    extract_args, parse_int, validate_command, Append, BitPos, CommandExecutor, Decr, Incr, MSet,
+    Set, SetBit, SetRange, StrLen, RESP_OK,
+};
 use crate::{
+    backend::frame_bytes,
     cmd::{CommandError, Get},
     RespArray, RespFrame, RespNull,
 };
+use std::sync::Arc;
 
 impl CommandExecutor for Get {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        Arc::unwrap_or_clone(self.shared(backend))
+    }
+}
+
+impl Get {
+    // the reply without copying the stored value, see network::request_handler
+    pub fn shared(self, backend: &crate::Backend) -> Arc<RespFrame> {
         match backend.get(&self.key) {
-            Some(value) => value,
-            None => RespFrame::Null(RespNull),
+            Ok(Some(value)) => value,
+            Ok(None) => Arc::new(RespFrame::Null(RespNull)),
+            Err(e) => Arc::new(e.into()),
         }
     }
 }
 
 impl CommandExecutor for Set {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.set(self.key, self.value);
+        if !(self.nx || self.xx || self.get) {
+            backend.set(self.key, self.value);
+            return RESP_OK.clone();
+        }
+        match backend.set_with(self.key, self.value, self.nx, self.xx, self.get) {
+            // with GET the reply is the old value, whether or not the new one was written
+            Ok((_, old)) if self.get => match old {
+                Some(old) => Arc::unwrap_or_clone(old),
+                None => RespFrame::Null(RespNull),
+            },
+            Ok((true, _)) => RESP_OK.clone(),
+            Ok((false, _)) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        for (key, value) in self.pairs {
+            backend.set(key, value);
+        }
         RESP_OK.clone()
     }
 }
 
+impl CommandExecutor for BitPos {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.get(&self.key) {
+            Ok(Some(value)) => {
+                RespFrame::Integer(bitpos(&frame_bytes(&value), self.bit, self.range))
+            }
+            // a missing key is an empty string, it has no set bits but endless clear ones
+            Ok(None) => RespFrame::Integer(if self.bit { -1 } else { 0 }),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.append(&self.key, &self.value) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SetRange {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.setrange(&self.key, self.offset, &self.value) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SetBit {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.setbit(&self.key, self.offset, self.bit) {
+            Ok(old) => RespFrame::Integer(old as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Incr {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.incr_by(&self.key, 1) {
+            Ok(n) => RespFrame::Integer(n),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Decr {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.incr_by(&self.key, -1) {
+            Ok(n) => RespFrame::Integer(n),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for StrLen {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.get(&self.key) {
+            Ok(Some(value)) => RespFrame::Integer(frame_bytes(&value).len() as i64),
+            Ok(None) => RespFrame::Integer(0),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// position of the first `bit` within the byte range, -1 if there is none
+fn bitpos(bytes: &[u8], bit: bool, range: Option<(i64, Option<i64>)>) -> i64 {
+    let len = bytes.len() as i64;
+    let (start, end) = match range {
+        Some((start, end)) => (start, end),
+        None => (0, None),
+    };
+    // negative offsets count from the end, like GETRANGE
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let end = match end {
+        Some(end) if end < 0 => (end + len).max(0),
+        Some(end) => end.min(len - 1),
+        None => len - 1,
+    };
+    if start > end {
+        return -1;
+    }
+
+    let (first, last) = (start as usize, end as usize);
+    let found = bytes[first..=last]
+        .iter()
+        .enumerate()
+        .find_map(|(i, byte)| {
+            let byte = if bit { *byte } else { !*byte };
+            (byte != 0).then(|| (first + i) as i64 * 8 + byte.leading_zeros() as i64)
+        });
+    match found {
+        Some(pos) => pos,
+        // without an explicit end the string is treated as padded with zeros
+        None if !bit && range.is_none_or(|(_, e)| e.is_none()) => (end + 1) * 8,
+        None => -1,
+    }
+}
+
 impl TryFrom<RespArray> for Get {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
@@ -27,10 +167,10 @@ impl TryFrom<RespArray> for Get {
         //validate_command(&value, &["set"], 2)?;
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
+            Some(key) => Ok(Get {
+                key: key.into_string()?,
             }),
-            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
@@ -38,12 +178,117 @@ impl TryFrom<RespArray> for Get {
 impl TryFrom<RespArray> for Set {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        let n_args = value.len() - 1;
+        if !(2..=4).contains(&n_args) {
+            return Err(CommandError::InvalidArgument(
+                "set command must have 2 to 4 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["set"], n_args)?;
         let mut args = extract_args(value, 1)?.into_iter();
-        match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
+        let mut set = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(value)) => Set {
                 key: String::from_utf8(key.0)?,
                 value,
+                nx: false,
+                xx: false,
+                get: false,
+            },
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or value".to_string(),
+                ))
+            }
+        };
+        for flag in args {
+            match flag {
+                RespFrame::BulkString(flag) if flag.eq_ignore_ascii_case("nx") && !set.nx => {
+                    set.nx = true
+                }
+                RespFrame::BulkString(flag) if flag.eq_ignore_ascii_case("xx") && !set.xx => {
+                    set.xx = true
+                }
+                RespFrame::BulkString(flag) if flag.eq_ignore_ascii_case("get") && !set.get => {
+                    set.get = true
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        if set.nx && set.xx {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(set)
+    }
+}
+
+impl TryFrom<RespArray> for MSet {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if n_args == 0 || !n_args.is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'mset' command".to_string(),
+            ));
+        }
+        validate_command(&value, &["mset"], n_args)?;
+        let args = extract_args(value, 1)?;
+        let pairs = args
+            .chunks(2)
+            .map(|pair| match &pair[0] {
+                RespFrame::BulkString(key) => {
+                    Ok((String::from_utf8(key.0.clone())?, pair[1].clone()))
+                }
+                _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(MSet { pairs })
+    }
+}
+
+impl TryFrom<RespArray> for BitPos {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        if !(2..=4).contains(&n_args) {
+            return Err(CommandError::InvalidArgument(
+                "bitpos command must have 2 to 4 arguments".to_string(),
+            ));
+        }
+        validate_command(&value, &["bitpos"], n_args)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let bit = match parse_int(args.next())? {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "The bit argument must be 1 or 0.".to_string(),
+                ))
+            }
+        };
+        let range = match args.next() {
+            Some(start) => {
+                let end = args.next().map(|end| parse_int(Some(end))).transpose()?;
+                Some((parse_int(Some(start))?, end))
+            }
+            None => None,
+        };
+        Ok(BitPos { key, bit, range })
+    }
+}
+
+impl TryFrom<RespArray> for Append {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["append"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(key), Some(value)) => Ok(Append {
+                key: key.into_string()?,
+                value: frame_bytes(&value),
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or value".to_string(),
@@ -52,10 +297,93 @@ impl TryFrom<RespArray> for Set {
     }
 }
 
+impl TryFrom<RespArray> for SetRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setrange"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(key) => key.into_string()?,
+            None => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let offset = usize::try_from(parse_int(args.next())?)
+            .map_err(|_| CommandError::InvalidArgument("offset is out of range".to_string()))?;
+        let value = match args.next() {
+            Some(value) => frame_bytes(&value),
+            None => return Err(CommandError::InvalidArgument("Invalid value".to_string())),
+        };
+        Ok(SetRange { key, offset, value })
+    }
+}
+
+impl TryFrom<RespArray> for SetBit {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setbit"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = match args.next() {
+            Some(key) => key.into_string()?,
+            None => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        };
+        let offset = u64::try_from(parse_int(args.next())?).map_err(|_| {
+            CommandError::InvalidArgument(
+                "bit offset is not an integer or out of range".to_string(),
+            )
+        })?;
+        let bit = match parse_int(args.next()) {
+            Ok(0) => false,
+            Ok(1) => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "bit is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+        Ok(SetBit { key, offset, bit })
+    }
+}
+
+impl TryFrom<RespArray> for Incr {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Incr {
+            key: parse_key(value, "incr")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Decr {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Decr {
+            key: parse_key(value, "decr")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for StrLen {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(StrLen {
+            key: parse_key(value, "strlen")?,
+        })
+    }
+}
+
+fn parse_key(value: RespArray, name: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &[name], 1)?;
+    match extract_args(value, 1)?.into_iter().next() {
+        Some(key) => key.into_string(),
+        None => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::RespDecode;
+    use crate::{
+        Backend, BulkString, KeyType, RespDecode, SimpleError, DEFAULT_PROTO_MAX_BULK_LEN,
+    };
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -69,4 +397,409 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_get_wrong_type() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        assert!(matches!(backend.get("h"), Err(CommandError::WrongType)));
+        assert_eq!(backend.get("missing")?, None);
+
+        let ret = Get {
+            key: "h".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(ret, RespFrame::from(CommandError::WrongType));
+        let ret = Get {
+            key: "missing".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(ret, RespFrame::Null(RespNull));
+
+        assert!(backend.check_type("h", KeyType::String).is_err());
+        assert!(backend.check_type("h", KeyType::Hash).is_ok());
+        assert!(backend.check_type("missing", KeyType::String).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_shares_value() -> Result<()> {
+        let backend = Backend::new();
+        backend.set(
+            "big".to_string(),
+            BulkString::new(vec![b'x'; 1024 * 1024]).into(),
+        );
+
+        // every reply points at the stored value instead of a copy of it
+        let first = Get {
+            key: "big".to_string(),
+        }
+        .shared(&backend);
+        let second = Get {
+            key: "big".to_string(),
+        }
+        .shared(&backend);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(
+            &first,
+            &backend.get("big")?.expect("big exists")
+        ));
+
+        let missing = Get {
+            key: "missing".to_string(),
+        }
+        .shared(&backend);
+        assert_eq!(*missing, RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mset() -> Result<()> {
+        let backend = Backend::new();
+        let frames: Vec<RespFrame> = ["mset", "a", "1", "b", "2"]
+            .iter()
+            .map(|a| BulkString::new(*a).into())
+            .collect();
+        let cmd: crate::cmd::Command = RespArray::new(frames).try_into()?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert_eq!(
+            backend.get("a")?.as_deref(),
+            Some(&BulkString::new("1").into())
+        );
+        assert_eq!(
+            backend.get("b")?.as_deref(),
+            Some(&BulkString::new("2").into())
+        );
+
+        let frames: Vec<RespFrame> = ["mset", "a", "1", "b"]
+            .iter()
+            .map(|a| BulkString::new(*a).into())
+            .collect();
+        assert!(crate::cmd::Command::try_from(RespArray::new(frames)).is_err());
+
+        Ok(())
+    }
+
+    fn bitpos_cmd(args: &[&str], backend: &Backend) -> Result<RespFrame> {
+        let frames: Vec<RespFrame> = ["bitpos"]
+            .iter()
+            .chain(args)
+            .map(|a| BulkString::new(*a).into())
+            .collect();
+        let cmd: crate::cmd::Command = RespArray::new(frames).try_into()?;
+        Ok(cmd.execute(backend))
+    }
+
+    #[test]
+    fn test_bitpos_set_bit() -> Result<()> {
+        let backend = Backend::new();
+        backend.set(
+            "k".to_string(),
+            BulkString::new(b"\x00\x0f\xff".to_vec()).into(),
+        );
+
+        assert_eq!(bitpos_cmd(&["k", "1"], &backend)?, RespFrame::Integer(12));
+        assert_eq!(
+            bitpos_cmd(&["k", "1", "2"], &backend)?,
+            RespFrame::Integer(16)
+        );
+        assert_eq!(
+            bitpos_cmd(&["k", "1", "-1"], &backend)?,
+            RespFrame::Integer(16)
+        );
+        assert_eq!(
+            bitpos_cmd(&["k", "1", "0", "0"], &backend)?,
+            RespFrame::Integer(-1)
+        );
+        assert_eq!(
+            bitpos_cmd(&["missing", "1"], &backend)?,
+            RespFrame::Integer(-1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpos_clear_bit() -> Result<()> {
+        let backend = Backend::new();
+        backend.set(
+            "k".to_string(),
+            BulkString::new(b"\xff\xf0\x00".to_vec()).into(),
+        );
+
+        assert_eq!(bitpos_cmd(&["k", "0"], &backend)?, RespFrame::Integer(12));
+        assert_eq!(
+            bitpos_cmd(&["k", "0", "2"], &backend)?,
+            RespFrame::Integer(16)
+        );
+        assert_eq!(
+            bitpos_cmd(&["missing", "0"], &backend)?,
+            RespFrame::Integer(0)
+        );
+
+        assert!(bitpos_cmd(&["k", "2"], &backend).is_err());
+        assert!(bitpos_cmd(&["k"], &backend).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpos_all_ones() -> Result<()> {
+        let backend = Backend::new();
+        backend.set(
+            "k".to_string(),
+            BulkString::new(b"\xff\xff\xff".to_vec()).into(),
+        );
+
+        // without an end the search runs into the implicit zero padding
+        assert_eq!(bitpos_cmd(&["k", "0"], &backend)?, RespFrame::Integer(24));
+        assert_eq!(
+            bitpos_cmd(&["k", "0", "1"], &backend)?,
+            RespFrame::Integer(24)
+        );
+        // with an explicit end it doesn't
+        assert_eq!(
+            bitpos_cmd(&["k", "0", "0", "-1"], &backend)?,
+            RespFrame::Integer(-1)
+        );
+
+        Ok(())
+    }
+
+    fn run(args: &[&str], backend: &Backend) -> Result<RespFrame> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        let cmd: crate::cmd::Command = RespArray::new(frames).try_into()?;
+        Ok(cmd.execute(backend))
+    }
+
+    #[test]
+    fn test_append_setrange_setbit() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&["append", "k", "Hello"], &backend)?,
+            RespFrame::Integer(5)
+        );
+        assert_eq!(
+            run(&["append", "k", " World"], &backend)?,
+            RespFrame::Integer(11)
+        );
+        assert_eq!(
+            run(&["setrange", "k", "6", "Redis"], &backend)?,
+            RespFrame::Integer(11)
+        );
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("Hello Redis").into())
+        );
+
+        // the gap is zero filled, an empty value doesn't create the key
+        assert_eq!(
+            run(&["setrange", "r", "2", "x"], &backend)?,
+            RespFrame::Integer(3)
+        );
+        assert_eq!(
+            backend.get("r")?.as_deref(),
+            Some(&BulkString::new(b"\0\0x".to_vec()).into())
+        );
+        assert_eq!(
+            run(&["setrange", "none", "5", ""], &backend)?,
+            RespFrame::Integer(0)
+        );
+        assert!(!backend.exists("none"));
+
+        assert_eq!(
+            run(&["setbit", "b", "7", "1"], &backend)?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&["setbit", "b", "7", "0"], &backend)?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run(&["setbit", "b", "0", "1"], &backend)?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            backend.get("b")?.as_deref(),
+            Some(&BulkString::new(b"\x80".to_vec()).into())
+        );
+
+        assert!(run(&["setrange", "k", "-1", "x"], &backend).is_err());
+        assert!(run(&["setbit", "b", "0", "2"], &backend).is_err());
+        backend.hset("h".to_string(), "f", BulkString::new("v").into());
+        assert!(matches!(
+            run(&["append", "h", "x"], &backend)?,
+            RespFrame::Error(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_max_len() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_proto_max_bulk_len(8);
+        let too_long = RespFrame::from(crate::SimpleError::new(
+            "ERR string exceeds maximum allowed size",
+        ));
+
+        assert_eq!(
+            run(&["append", "k", "12345"], &backend)?,
+            RespFrame::Integer(5)
+        );
+        assert_eq!(run(&["append", "k", "6789"], &backend)?, too_long);
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("12345").into())
+        );
+        assert_eq!(
+            run(&["append", "k", "678"], &backend)?,
+            RespFrame::Integer(8)
+        );
+
+        assert_eq!(run(&["setrange", "k", "8", "x"], &backend)?, too_long);
+        assert_eq!(run(&["setbit", "k", "64", "1"], &backend)?, too_long);
+        assert_eq!(
+            run(&["setbit", "k", "63", "1"], &backend)?,
+            RespFrame::Integer(0)
+        );
+        // a refused write to a missing key doesn't create it, or count as an access
+        assert_eq!(run(&["setrange", "new", "100", "x"], &backend)?, too_long);
+        assert!(!backend.exists("new"));
+        assert!(!backend.last_access.contains_key("new"));
+
+        // a value already handed out isn't changed under its reader
+        backend.set_proto_max_bulk_len(DEFAULT_PROTO_MAX_BULK_LEN);
+        let held = backend.get("k")?;
+        run(&["append", "k", "!"], &backend)?;
+        // bit 63 turned the trailing 8 into a 9
+        assert_eq!(held.as_deref(), Some(&BulkString::new("12345679").into()));
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("12345679!").into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_get() -> Result<()> {
+        let backend = Backend::new();
+        let bulk = |v: &str| RespFrame::from(BulkString::new(v));
+
+        // a missing key is set, the reply is nil
+        assert_eq!(
+            run(&["set", "k", "v1", "GET"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+        // an existing one replies its old value
+        assert_eq!(run(&["set", "k", "v2", "get"], &backend)?, bulk("v1"));
+        assert_eq!(backend.get("k")?.as_deref(), Some(&bulk("v2")));
+
+        // NX+GET on an existing key: the old value, and nothing is written
+        assert_eq!(run(&["set", "k", "v3", "NX", "GET"], &backend)?, bulk("v2"));
+        assert_eq!(backend.get("k")?.as_deref(), Some(&bulk("v2")));
+        assert_eq!(
+            run(&["set", "new", "v", "NX", "GET"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(backend.get("new")?.as_deref(), Some(&bulk("v")));
+
+        // XX+GET on a missing key writes nothing
+        assert_eq!(
+            run(&["set", "none", "v", "XX", "GET"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+        assert!(!backend.exists("none"));
+
+        // without GET, NX and XX reply OK or nil
+        assert_eq!(
+            run(&["set", "k", "v4", "NX"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(run(&["set", "k", "v4", "XX"], &backend)?, RESP_OK.clone());
+        assert_eq!(backend.get("k")?.as_deref(), Some(&bulk("v4")));
+
+        backend.hset("h".to_string(), "f", bulk("v"));
+        assert!(matches!(
+            run(&["set", "h", "v", "GET"], &backend)?,
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            run(&["set", "h", "v", "NX"], &backend)?,
+            RespFrame::Null(RespNull)
+        );
+
+        assert!(run(&["set", "k", "v", "NX", "XX"], &backend).is_err());
+        assert!(run(&["set", "k", "v", "GET", "GET"], &backend).is_err());
+        assert!(run(&["set", "k", "v", "EX"], &backend).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_strings() -> Result<()> {
+        let backend = Backend::new();
+        let bulk = |v: &str| RespFrame::from(BulkString::new(v));
+
+        assert_eq!(run(&["set", "counter", "100"], &backend)?, RESP_OK.clone());
+        // kept as an integer, read back as the text that was set
+        assert_eq!(
+            backend
+                .map
+                .get("counter")
+                .map(|v| v.value().as_ref().clone()),
+            Some(RespFrame::Integer(100))
+        );
+        assert_eq!(run(&["get", "counter"], &backend)?, bulk("100"));
+        assert_eq!(
+            run(&["strlen", "counter"], &backend)?,
+            RespFrame::Integer(3)
+        );
+
+        assert_eq!(
+            run(&["incr", "counter"], &backend)?,
+            RespFrame::Integer(101)
+        );
+        assert_eq!(run(&["get", "counter"], &backend)?, bulk("101"));
+        assert_eq!(
+            run(&["decr", "counter"], &backend)?,
+            RespFrame::Integer(100)
+        );
+        assert_eq!(run(&["incr", "missing"], &backend)?, RespFrame::Integer(1));
+
+        // other spellings stay as they were sent, and INCR refuses them
+        for raw in ["0100", "+5", " 7", "1e3", "99999999999999999999"] {
+            run(&["set", "raw", raw], &backend)?;
+            assert_eq!(run(&["get", "raw"], &backend)?, bulk(raw));
+            assert_eq!(
+                run(&["incr", "raw"], &backend)?,
+                SimpleError::new("ERR Invalid argument: value is not an integer or out of range")
+                    .into()
+            );
+            assert_eq!(run(&["get", "raw"], &backend)?, bulk(raw));
+        }
+
+        run(&["set", "text", "abc"], &backend)?;
+        assert!(matches!(
+            run(&["incr", "text"], &backend)?,
+            RespFrame::Error(_)
+        ));
+        assert_eq!(run(&["get", "text"], &backend)?, bulk("abc"));
+        let max = i64::MAX.to_string();
+        run(&["set", "max", &max], &backend)?;
+        assert!(matches!(
+            run(&["incr", "max"], &backend)?,
+            RespFrame::Error(_)
+        ));
+        assert_eq!(run(&["get", "max"], &backend)?, bulk(&max));
+
+        Ok(())
+    }
 }
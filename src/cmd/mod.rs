@@ -1,9 +1,32 @@
+mod conn;
+mod dump;
+mod expire;
 mod hmap;
+mod keys;
+mod list;
 mod map;
+mod pubsub;
+mod server;
+mod set;
+mod sort;
+mod table;
+mod zset;
 
-use crate::{Backend, RespArray, RespError, RespFrame, RespNull, SimpleString};
+use crate::{
+    Aggregate, Backend, BulkString, KeyType, ProtocolVersion, RespArray, RespDecode, RespError,
+    RespFrame, RespNull, SimpleError, SimpleString, ZRangeBy,
+};
+pub use conn::ConnState;
+pub use table::{
+    lookup_command, lookup_docs, ArgDoc, CommandDoc, CommandSpec, COMMAND_DOCS, COMMAND_TABLE,
+};
+
+use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
+use std::io::Read;
+use std::ops::Bound;
+use std::time::Duration;
 use thiserror::Error;
 
 lazy_static! {
@@ -20,6 +43,44 @@ pub enum CommandError {
     RespError(#[from] RespError),
     #[error("Utf8 error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error("command timed out")]
+    Timeout,
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+    #[error("string exceeds maximum allowed size")]
+    StringTooLong,
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// the message after the prefix is what the client sees, e.g. `-ERR Invalid argument: ...`
+impl From<CommandError> for RespFrame {
+    fn from(err: CommandError) -> Self {
+        let msg = match err {
+            CommandError::WrongType
+            | CommandError::BusyKey
+            | CommandError::ReadOnly
+            | CommandError::NoAuth
+            | CommandError::WrongPass
+            | CommandError::NoProto => err.to_string(),
+            err => format!("ERR {}", err),
+        };
+        SimpleError::new(msg).into()
+    }
 }
 
 #[enum_dispatch]
@@ -27,21 +88,109 @@ pub trait CommandExecutor {
     fn execute(self, backend: &Backend) -> RespFrame;
 }
 
+// commands that read or change the connection they arrive on
+pub trait StatefulExecutor {
+    fn execute_with_state(self, backend: &Backend, state: &mut ConnState) -> Vec<RespFrame>;
+}
+
 #[enum_dispatch(CommandExecutor)]
 #[derive(Debug)]
 pub enum Command {
     Get(Get),
     Set(Set),
+    MSet(MSet),
+    BitPos(BitPos),
+    Append(Append),
+    SetRange(SetRange),
+    SetBit(SetBit),
+    Incr(Incr),
+    Decr(Decr),
+    StrLen(StrLen),
     HGet(HGet),
     HSet(HSet),
     HGetAll(HGetAll),
+    HKeys(HKeys),
+    HVals(HVals),
     HMGet(HMGet),
+    HRandField(HRandField),
+    HScan(HScan),
 
     Echo(Echo),
     Sadd(Sadd),
     Sismember(Sismember),
+    SMembers(SMembers),
+    SInter(SInter),
+    SUnion(SUnion),
+    SDiff(SDiff),
+    SInterStore(SInterStore),
+    SUnionStore(SUnionStore),
+    SDiffStore(SDiffStore),
+    SInterCard(SInterCard),
+    Sort(Sort),
+
+    LPush(LPush),
+    RPush(RPush),
+    LPop(LPop),
+    RPop(RPop),
+    BLPop(BLPop),
+    BRPop(BRPop),
+    LPos(LPos),
+    LInsert(LInsert),
 
-    Unrecognized(Unrecognized),
+    ZAdd(ZAdd),
+    ZMScore(ZMScore),
+    ZPopMin(ZPopMin),
+    ZPopMax(ZPopMax),
+    ZCount(ZCount),
+    ZRange(ZRange),
+    ZUnionStore(ZUnionStore),
+    ZInterStore(ZInterStore),
+    ZDiff(ZDiff),
+    ZDiffStore(ZDiffStore),
+
+    Expire(Expire),
+    PExpire(PExpire),
+    ExpireAt(ExpireAt),
+    PExpireAt(PExpireAt),
+    Ttl(Ttl),
+    PTtl(PTtl),
+    ExpireTime(ExpireTime),
+    PExpireTime(PExpireTime),
+
+    Del(Del),
+    Unlink(Unlink),
+    Move(Move),
+    Rename(Rename),
+    CopyCmd(CopyCmd),
+    Type(Type),
+    Dump(Dump),
+    Restore(Restore),
+    Scan(Scan),
+    RandomKey(RandomKey),
+    Object(Object),
+
+    Lolwut(Lolwut),
+    Time(Time),
+    Info(Info),
+    HealthCheck(HealthCheck),
+    Cluster(Cluster),
+    CommandCmd(CommandCmd),
+    DebugCmd(DebugCmd),
+    Reset(Reset),
+    Select(Select),
+    Auth(Auth),
+    Hello(Hello),
+    Client(Client),
+    SwapDb(SwapDb),
+
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    Publish(Publish),
+    PubSub(PubSub),
+
+    Empty(Empty),
 }
 
 #[derive(Debug)]
@@ -49,22 +198,78 @@ pub struct Get {
     key: String,
 }
 
+// SET key value [NX | XX] [GET]
 #[derive(Debug)]
 pub struct Set {
     key: String,
     value: RespFrame,
+    nx: bool,
+    xx: bool,
+    get: bool,
+}
+
+// MSET key value [key value ...]
+#[derive(Debug)]
+pub struct MSet {
+    pairs: Vec<(String, RespFrame)>,
+}
+
+// BITPOS key bit [start [end]], the range is in bytes
+#[derive(Debug)]
+pub struct BitPos {
+    key: String,
+    bit: bool,
+    range: Option<(i64, Option<i64>)>,
+}
+
+// APPEND key value
+#[derive(Debug)]
+pub struct Append {
+    key: String,
+    value: Vec<u8>,
+}
+
+// SETRANGE key offset value
+#[derive(Debug)]
+pub struct SetRange {
+    key: String,
+    offset: usize,
+    value: Vec<u8>,
+}
+
+// SETBIT key offset value, the offset is in bits
+#[derive(Debug)]
+pub struct SetBit {
+    key: String,
+    offset: u64,
+    bit: bool,
+}
+
+#[derive(Debug)]
+pub struct Incr {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct Decr {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct StrLen {
+    key: String,
 }
 
 #[derive(Debug)]
 pub struct HGet {
     key: String,
-    field: String,
+    field: Vec<u8>,
 }
 
 #[derive(Debug)]
 pub struct HSet {
     key: String,
-    field: String,
+    field: Vec<u8>,
     value: RespFrame,
 }
 
@@ -73,10 +278,39 @@ pub struct HGetAll {
     key: String,
 }
 
+// HKEYS and HVALS go by field name, the same order HGETALL's map has
+#[derive(Debug)]
+pub struct HKeys {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct HVals {
+    key: String,
+}
+
 #[derive(Debug)]
 pub struct HMGet {
     key: String,
-    fields: Vec<String>,
+    fields: Vec<Vec<u8>>,
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+#[derive(Debug)]
+pub struct HRandField {
+    key: String,
+    count: Option<i64>,
+    with_values: bool,
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+#[derive(Debug)]
+pub struct HScan {
+    key: String,
+    cursor: u64,
+    pattern: Option<String>,
+    count: usize,
+    novalues: bool,
 }
 
 #[derive(Debug)]
@@ -97,10 +331,439 @@ pub struct Sismember {
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
-impl CommandExecutor for Unrecognized {
+pub struct SMembers {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct SInter {
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SUnion {
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SDiff {
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SInterStore {
+    dest: String,
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SUnionStore {
+    dest: String,
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SDiffStore {
+    dest: String,
+    keys: Vec<String>,
+}
+
+// a limit of 0 is the same as no limit
+#[derive(Debug)]
+pub struct SInterCard {
+    keys: Vec<String>,
+    limit: Option<usize>,
+}
+
+// SORT key [LIMIT offset count] [ASC | DESC] [ALPHA]
+#[derive(Debug)]
+pub struct Sort {
+    key: String,
+    alpha: bool,
+    desc: bool,
+    limit: Option<(i64, i64)>,
+}
+
+#[derive(Debug)]
+pub struct LPush {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct RPush {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct LPop {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct RPop {
+    key: String,
+}
+
+// LPOS key element [RANK rank] [COUNT num-matches]
+#[derive(Debug)]
+pub struct LPos {
+    key: String,
+    element: RespFrame,
+    rank: Option<i64>,
+    count: Option<usize>,
+}
+
+// LINSERT key BEFORE|AFTER pivot element
+#[derive(Debug)]
+pub struct LInsert {
+    key: String,
+    before: bool,
+    pivot: RespFrame,
+    element: RespFrame,
+}
+
+// ZADD key score member [score member ...]
+#[derive(Debug)]
+pub struct ZAdd {
+    key: String,
+    members: Vec<(f64, String)>,
+}
+
+#[derive(Debug)]
+pub struct ZMScore {
+    key: String,
+    members: Vec<String>,
+}
+
+// ZPOPMIN key [count]
+#[derive(Debug)]
+pub struct ZPopMin {
+    key: String,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct ZPopMax {
+    key: String,
+    count: usize,
+}
+
+// ZCOUNT key min max
+#[derive(Debug)]
+pub struct ZCount {
+    key: String,
+    min: Bound<f64>,
+    max: Bound<f64>,
+}
+
+// ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+#[derive(Debug)]
+pub struct ZRange {
+    key: String,
+    by: ZRangeBy,
+    rev: bool,
+    limit: Option<(usize, usize)>,
+    with_scores: bool,
+}
+
+// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]]
+//   [AGGREGATE SUM|MIN|MAX]
+#[derive(Debug)]
+pub struct ZUnionStore {
+    dest: String,
+    keys: Vec<String>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+// ZINTERSTORE, with the same arguments as ZUNIONSTORE
+#[derive(Debug)]
+pub struct ZInterStore {
+    dest: String,
+    keys: Vec<String>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+}
+
+// ZDIFF numkeys key [key ...] [WITHSCORES]
+#[derive(Debug)]
+pub struct ZDiff {
+    keys: Vec<String>,
+    with_scores: bool,
+}
+
+// ZDIFFSTORE destination numkeys key [key ...]
+#[derive(Debug)]
+pub struct ZDiffStore {
+    dest: String,
+    keys: Vec<String>,
+}
+
+// timeout in seconds, 0 blocks forever
+#[derive(Debug)]
+pub struct BLPop {
+    keys: Vec<String>,
+    timeout: f64,
+}
+
+#[derive(Debug)]
+pub struct BRPop {
+    keys: Vec<String>,
+    timeout: f64,
+}
+
+#[derive(Debug)]
+pub struct Expire {
+    key: String,
+    seconds: i64,
+    options: ExpireOptions,
+}
+
+#[derive(Debug)]
+pub struct PExpire {
+    key: String,
+    ms: i64,
+    options: ExpireOptions,
+}
+
+// NX/XX/GT/LT flags of EXPIRE and PEXPIRE
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExpireOptions {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+}
+
+// absolute unix timestamps
+#[derive(Debug)]
+pub struct ExpireAt {
+    key: String,
+    ts_secs: i64,
+}
+
+#[derive(Debug)]
+pub struct PExpireAt {
+    key: String,
+    ts_ms: i64,
+}
+
+#[derive(Debug)]
+pub struct Ttl {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct PTtl {
+    key: String,
+}
+
+// EXPIRETIME key, the deadline as a unix timestamp
+#[derive(Debug)]
+pub struct ExpireTime {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct PExpireTime {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Unlink {
+    keys: Vec<String>,
+}
+
+// MOVE key db
+#[derive(Debug)]
+pub struct Move {
+    key: String,
+    db: usize,
+}
+
+// RENAME key newkey
+#[derive(Debug)]
+pub struct Rename {
+    key: String,
+    newkey: String,
+}
+
+// COPY source destination [REPLACE]
+#[derive(Debug)]
+pub struct CopyCmd {
+    source: String,
+    destination: String,
+    replace: bool,
+}
+
+#[derive(Debug)]
+pub struct Type {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct Dump {
+    key: String,
+}
+
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+// a ttl of 0 keeps the ttl stored in the payload
+#[derive(Debug)]
+pub struct Restore {
+    key: String,
+    ttl: i64,
+    payload: Vec<u8>,
+    replace: bool,
+    absttl: bool,
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+#[derive(Debug)]
+pub struct Scan {
+    cursor: u64,
+    pattern: Option<String>,
+    count: usize,
+    key_type: Option<KeyType>,
+}
+
+// OBJECT ENCODING key | OBJECT IDLETIME key
+#[derive(Debug)]
+pub enum Object {
+    Encoding(String),
+    IdleTime(String),
+}
+
+#[derive(Debug)]
+pub struct RandomKey;
+
+#[derive(Debug)]
+pub struct Lolwut;
+
+// TIME, the server's clock as unix seconds and microseconds
+#[derive(Debug)]
+pub struct Time;
+
+// HEALTHCHECK, for liveness probes
+#[derive(Debug)]
+pub struct HealthCheck;
+
+// CLUSTER INFO | CLUSTER MYID, enough for clients that probe for cluster mode
+#[derive(Debug)]
+pub enum Cluster {
+    Info,
+    MyId,
+}
+
+// INFO [section [section ...]], only the server and stats sections so far
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+// COMMAND | COMMAND LIST | COMMAND INFO name [name ...] | COMMAND GETKEYS command [arg ...]
+// | COMMAND DOCS [name ...]
+#[derive(Debug)]
+pub enum CommandCmd {
+    All,
+    List,
+    Info(Vec<String>),
+    GetKeys(Vec<String>),
+    Docs(Vec<String>),
+}
+
+// DEBUG OBJECT key | DEBUG SLEEP seconds | DEBUG SET-ACTIVE-EXPIRE 0|1
+#[derive(Debug)]
+pub enum DebugCmd {
+    Object(String),
+    Sleep(Duration),
+    SetActiveExpire(bool),
+}
+
+#[derive(Debug)]
+pub struct Reset;
+
+// SELECT index
+#[derive(Debug)]
+pub struct Select {
+    index: usize,
+}
+
+// SWAPDB index1 index2
+#[derive(Debug)]
+pub struct SwapDb {
+    a: usize,
+    b: usize,
+}
+
+// AUTH [username] password
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+// HELLO [protover [AUTH username password]]
+#[derive(Debug)]
+pub struct Hello {
+    protocol: Option<ProtocolVersion>,
+    auth: Option<(String, String)>,
+}
+
+// CLIENT SETNAME name | CLIENT GETNAME
+#[derive(Debug)]
+pub enum Client {
+    SetName(String),
+    GetName,
+}
+
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<String>,
+}
+
+// no channels means unsubscribe from all of them
+#[derive(Debug)]
+pub struct Unsubscribe {
+    channels: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PSubscribe {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PUnsubscribe {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Publish {
+    channel: String,
+    message: RespFrame,
+}
+
+// PUBSUB CHANNELS [pattern] | NUMSUB [channel ...] | NUMPAT
+#[derive(Debug)]
+pub enum PubSub {
+    Channels(Option<String>),
+    NumSub(Vec<String>),
+    NumPat,
+}
+
+// an empty `*0` request, redis ignores it without replying
+#[derive(Debug)]
+pub struct Empty;
+impl CommandExecutor for Empty {
     fn execute(self, _backend: &Backend) -> RespFrame {
-        RESP_OK.clone()
+        RespFrame::Null(RespNull)
     }
 }
 
@@ -146,19 +809,105 @@ impl TryFrom<RespFrame> for Command {
 impl TryFrom<RespArray> for Command {
     type Error = CommandError;
     fn try_from(v: RespArray) -> Result<Self, Self::Error> {
+        // RESP and inline requests both end up here, a wrong number of arguments
+        // is refused the same way for every command before its own parser runs
+        if let Some(spec) = command_name_of(&v).and_then(lookup_command) {
+            spec.check_arity(v.len())?;
+        }
         match v.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
+            // clients like redis-cli send command names in upper case
+            Some(RespFrame::BulkString(ref cmd)) => match cmd.to_ascii_lowercase().as_slice() {
                 b"get" => Ok(Get::try_from(v)?.into()),
                 b"set" => Ok(Set::try_from(v)?.into()),
+                b"mset" => Ok(MSet::try_from(v)?.into()),
+                b"bitpos" => Ok(BitPos::try_from(v)?.into()),
+                b"append" => Ok(Append::try_from(v)?.into()),
+                b"setrange" => Ok(SetRange::try_from(v)?.into()),
+                b"setbit" => Ok(SetBit::try_from(v)?.into()),
+                b"incr" => Ok(Incr::try_from(v)?.into()),
+                b"decr" => Ok(Decr::try_from(v)?.into()),
+                b"strlen" => Ok(StrLen::try_from(v)?.into()),
                 b"hget" => Ok(HGet::try_from(v)?.into()),
                 b"hset" => Ok(HSet::try_from(v)?.into()),
                 b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
+                b"hkeys" => Ok(HKeys::try_from(v)?.into()),
+                b"hvals" => Ok(HVals::try_from(v)?.into()),
                 b"hmget" => Ok(HMGet::try_from(v)?.into()),
+                b"hrandfield" => Ok(HRandField::try_from(v)?.into()),
+                b"hscan" => Ok(HScan::try_from(v)?.into()),
                 b"echo" => Ok(Echo::try_from(v)?.into()),
                 b"sadd" => Ok(Sadd::try_from(v)?.into()),
                 b"sismember" => Ok(Sismember::try_from(v)?.into()),
-                _ => Ok(Unrecognized.into()),
+                b"smembers" => Ok(SMembers::try_from(v)?.into()),
+                b"sinter" => Ok(SInter::try_from(v)?.into()),
+                b"sunion" => Ok(SUnion::try_from(v)?.into()),
+                b"sdiff" => Ok(SDiff::try_from(v)?.into()),
+                b"sinterstore" => Ok(SInterStore::try_from(v)?.into()),
+                b"sunionstore" => Ok(SUnionStore::try_from(v)?.into()),
+                b"sdiffstore" => Ok(SDiffStore::try_from(v)?.into()),
+                b"sintercard" => Ok(SInterCard::try_from(v)?.into()),
+                b"sort" => Ok(Sort::try_from(v)?.into()),
+                b"lpush" => Ok(LPush::try_from(v)?.into()),
+                b"rpush" => Ok(RPush::try_from(v)?.into()),
+                b"lpop" => Ok(LPop::try_from(v)?.into()),
+                b"rpop" => Ok(RPop::try_from(v)?.into()),
+                b"blpop" => Ok(BLPop::try_from(v)?.into()),
+                b"brpop" => Ok(BRPop::try_from(v)?.into()),
+                b"lpos" => Ok(LPos::try_from(v)?.into()),
+                b"linsert" => Ok(LInsert::try_from(v)?.into()),
+                b"zadd" => Ok(ZAdd::try_from(v)?.into()),
+                b"zmscore" => Ok(ZMScore::try_from(v)?.into()),
+                b"zpopmin" => Ok(ZPopMin::try_from(v)?.into()),
+                b"zpopmax" => Ok(ZPopMax::try_from(v)?.into()),
+                b"zcount" => Ok(ZCount::try_from(v)?.into()),
+                b"zrange" => Ok(ZRange::try_from(v)?.into()),
+                b"zunionstore" => Ok(ZUnionStore::try_from(v)?.into()),
+                b"zinterstore" => Ok(ZInterStore::try_from(v)?.into()),
+                b"zdiff" => Ok(ZDiff::try_from(v)?.into()),
+                b"zdiffstore" => Ok(ZDiffStore::try_from(v)?.into()),
+                b"expire" => Ok(Expire::try_from(v)?.into()),
+                b"pexpire" => Ok(PExpire::try_from(v)?.into()),
+                b"expireat" => Ok(ExpireAt::try_from(v)?.into()),
+                b"pexpireat" => Ok(PExpireAt::try_from(v)?.into()),
+                b"ttl" => Ok(Ttl::try_from(v)?.into()),
+                b"pttl" => Ok(PTtl::try_from(v)?.into()),
+                b"expiretime" => Ok(ExpireTime::try_from(v)?.into()),
+                b"pexpiretime" => Ok(PExpireTime::try_from(v)?.into()),
+                b"del" => Ok(Del::try_from(v)?.into()),
+                b"unlink" => Ok(Unlink::try_from(v)?.into()),
+                b"move" => Ok(Move::try_from(v)?.into()),
+                b"rename" => Ok(Rename::try_from(v)?.into()),
+                b"copy" => Ok(CopyCmd::try_from(v)?.into()),
+                b"type" => Ok(Type::try_from(v)?.into()),
+                b"dump" => Ok(Dump::try_from(v)?.into()),
+                b"restore" => Ok(Restore::try_from(v)?.into()),
+                b"scan" => Ok(Scan::try_from(v)?.into()),
+                b"randomkey" => Ok(RandomKey::try_from(v)?.into()),
+                b"object" => Ok(Object::try_from(v)?.into()),
+                b"lolwut" => Ok(Lolwut::try_from(v)?.into()),
+                b"time" => Ok(Time::try_from(v)?.into()),
+                b"info" => Ok(Info::try_from(v)?.into()),
+                b"healthcheck" => Ok(HealthCheck::try_from(v)?.into()),
+                b"cluster" => Ok(Cluster::try_from(v)?.into()),
+                b"command" => Ok(CommandCmd::try_from(v)?.into()),
+                b"debug" => Ok(DebugCmd::try_from(v)?.into()),
+                b"reset" => Ok(Reset::try_from(v)?.into()),
+                b"select" => Ok(Select::try_from(v)?.into()),
+                b"swapdb" => Ok(SwapDb::try_from(v)?.into()),
+                b"auth" => Ok(Auth::try_from(v)?.into()),
+                b"hello" => Ok(Hello::try_from(v)?.into()),
+                b"client" => Ok(Client::try_from(v)?.into()),
+                b"subscribe" => Ok(Subscribe::try_from(v)?.into()),
+                b"unsubscribe" => Ok(Unsubscribe::try_from(v)?.into()),
+                b"psubscribe" => Ok(PSubscribe::try_from(v)?.into()),
+                b"punsubscribe" => Ok(PUnsubscribe::try_from(v)?.into()),
+                b"publish" => Ok(Publish::try_from(v)?.into()),
+                b"pubsub" => Ok(PubSub::try_from(v)?.into()),
+                _ => Err(CommandError::UnknownCommand(
+                    String::from_utf8_lossy(cmd).into_owned(),
+                )),
             },
+            None => Ok(Empty.into()),
             _ => Err(CommandError::InvalidCommand(
                 "Command must have a BulkString as the first argument".to_string(),
             )),
@@ -166,6 +915,139 @@ impl TryFrom<RespArray> for Command {
     }
 }
 
+// the command name a request starts with, if it is text
+fn command_name_of(v: &RespArray) -> Option<&str> {
+    match v.first() {
+        Some(RespFrame::BulkString(name)) => std::str::from_utf8(name).ok(),
+        _ => None,
+    }
+}
+
+// build a command from plain arguments, e.g. ["get", "key"]
+impl TryFrom<Vec<String>> for Command {
+    type Error = CommandError;
+    fn try_from(args: Vec<String>) -> Result<Self, Self::Error> {
+        let frames: Vec<RespFrame> = args
+            .into_iter()
+            .map(|arg| BulkString::new(arg).into())
+            .collect();
+        RespArray::new(frames).try_into()
+    }
+}
+
+impl Command {
+    pub fn from_args(args: Vec<String>) -> Result<Command, CommandError> {
+        args.try_into()
+    }
+
+    // commands that may modify the keyspace, the ones flagged "write" in COMMAND_TABLE
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Command::Set(_)
+                | Command::MSet(_)
+                | Command::Append(_)
+                | Command::SetRange(_)
+                | Command::SetBit(_)
+                | Command::Incr(_)
+                | Command::Decr(_)
+                | Command::HSet(_)
+                | Command::Sadd(_)
+                | Command::SInterStore(_)
+                | Command::SUnionStore(_)
+                | Command::SDiffStore(_)
+                | Command::Sort(_)
+                | Command::LPush(_)
+                | Command::RPush(_)
+                | Command::LPop(_)
+                | Command::RPop(_)
+                | Command::LInsert(_)
+                | Command::BLPop(_)
+                | Command::BRPop(_)
+                | Command::ZAdd(_)
+                | Command::ZPopMin(_)
+                | Command::ZPopMax(_)
+                | Command::ZUnionStore(_)
+                | Command::ZInterStore(_)
+                | Command::ZDiffStore(_)
+                | Command::Expire(_)
+                | Command::PExpire(_)
+                | Command::ExpireAt(_)
+                | Command::PExpireAt(_)
+                | Command::Del(_)
+                | Command::Unlink(_)
+                | Command::Move(_)
+                | Command::Rename(_)
+                | Command::CopyCmd(_)
+                | Command::Restore(_)
+                | Command::SwapDb(_)
+        )
+    }
+}
+
+// the engine without the network: a frame in, its reply out. there is no connection
+// either, so SELECT, SUBSCRIBE and friends don't change anything here
+impl Backend {
+    pub fn execute_frame(&self, frame: RespFrame) -> RespFrame {
+        // the write hook gets the request as it came in
+        let request = self.has_write_hook().then(|| frame.clone());
+        let cmd = match Command::try_from(frame) {
+            Ok(cmd) => cmd,
+            Err(e) => return e.into(),
+        };
+        if cmd.is_write() && self.is_read_only() {
+            return CommandError::ReadOnly.into();
+        }
+        // a handle of its own counts only this command's changes
+        let db = self.index();
+        let backend = self.database(db).unwrap_or_else(|| self.clone());
+        let changes = backend.changes();
+        let reply = cmd.execute(&backend);
+        backend.notify_write(db, changes, request);
+        reply
+    }
+
+    // one reply per frame, in the same order
+    pub fn execute_many(&self, frames: Vec<RespFrame>) -> Vec<RespFrame> {
+        frames
+            .into_iter()
+            .map(|frame| self.execute_frame(frame))
+            .collect()
+    }
+
+    // rebuilds state from a stream of commands, like an append only file. the
+    // replies are dropped, returns how many commands ran. a frame cut off by the
+    // end of the stream is an error, the commands before it stay applied
+    pub fn replay(&self, mut reader: impl Read) -> Result<usize, CommandError> {
+        let mut buf = BytesMut::new();
+        let mut chunk = [0u8; 16 * 1024];
+        let mut applied = 0;
+        loop {
+            match RespFrame::decode(&mut buf) {
+                Ok(frame) => {
+                    self.execute_frame(frame);
+                    applied += 1;
+                    continue;
+                }
+                Err(RespError::NotComplete) => {}
+                Err(e) => return Err(e.into()),
+            }
+            let n = match reader.read(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if n == 0 {
+                if !buf.is_empty() {
+                    return Err(RespError::NotComplete.into());
+                }
+                return Ok(applied);
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
 fn validate_command(
     value: &RespArray,
     names: &[&'static str],
@@ -204,12 +1086,289 @@ fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, Comman
     Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 }
 
+fn extract_strings(args: Vec<RespFrame>) -> Result<Vec<String>, CommandError> {
+    args.into_iter().map(RespFrame::into_string).collect()
+}
+
+// key [key ...]
+fn parse_keys(value: RespArray, name: &'static str) -> Result<Vec<String>, CommandError> {
+    let n_args = value.len() - 1;
+    if n_args == 0 {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have at least 1 argument",
+            name
+        )));
+    }
+    validate_command(&value, &[name], n_args)?;
+    extract_strings(extract_args(value, 1)?)
+}
+
+// reply for a typed read: the value, a null for a missing key, or the error
+fn value_or_null(value: Result<Option<RespFrame>, CommandError>) -> RespFrame {
+    match value {
+        Ok(Some(value)) => value,
+        Ok(None) => RespFrame::Null(RespNull),
+        Err(e) => e.into(),
+    }
+}
+
+// integer arguments arrive as bulk strings
+fn parse_int(value: Option<RespFrame>) -> Result<i64, CommandError> {
+    match value {
+        Some(frame) => i64::try_from(&frame),
+        None => Err(not_an_integer()),
+    }
+}
+
+fn not_an_integer() -> CommandError {
+    CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+}
+
+// integers may be sent as RESP integers or as their decimal text
+impl TryFrom<&RespFrame> for i64 {
+    type Error = CommandError;
+    fn try_from(value: &RespFrame) -> Result<Self, Self::Error> {
+        let text = match value {
+            RespFrame::Integer(v) => return Ok(*v),
+            RespFrame::BulkString(v) => std::str::from_utf8(&v.0).ok(),
+            RespFrame::SimpleString(v) => Some(v.as_ref()),
+            _ => None,
+        };
+        text.and_then(|text| text.parse().ok())
+            .ok_or_else(not_an_integer)
+    }
+}
+
+// shortcuts for looking at an argument without taking it apart
+impl BulkString {
+    pub fn as_str(&self) -> Result<&str, CommandError> {
+        std::str::from_utf8(&self.0)
+            .map_err(|_| CommandError::InvalidArgument("invalid UTF-8 argument".to_string()))
+    }
+
+    // option names and subcommands are matched case-insensitively
+    pub fn eq_ignore_ascii_case(&self, other: &str) -> bool {
+        self.0.eq_ignore_ascii_case(other.as_bytes())
+    }
+}
+
+impl RespFrame {
+    pub fn as_bulk_bytes(&self) -> Option<&[u8]> {
+        match self {
+            RespFrame::BulkString(v) => Some(&v.0),
+            _ => None,
+        }
+    }
+
+    // keys and names, which have to be text
+    pub fn into_string(self) -> Result<String, CommandError> {
+        match self {
+            RespFrame::BulkString(v) => Ok(String::from_utf8(v.0)?),
+            RespFrame::SimpleString(v) => Ok(String::clone(&v)),
+            _ => Err(CommandError::InvalidArgument(
+                "expected a string argument".to_string(),
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{BulkString, RespDecode, RespEncode, RespNull};
+    use crate::{parse_inline, RespDecode, RespEncode};
     use anyhow::{Context, Ok, Result};
     use bytes::BytesMut;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_bulk_string_helpers() {
+        let arg = BulkString::new("WithValues");
+        assert_eq!(arg.as_str().unwrap(), "WithValues");
+        assert!(arg.eq_ignore_ascii_case("withvalues"));
+        assert!(arg.eq_ignore_ascii_case("WITHVALUES"));
+        assert!(!arg.eq_ignore_ascii_case("withvalue"));
+
+        let arg = BulkString::new(b"\xff\xfe".to_vec());
+        assert!(matches!(
+            arg.as_str(),
+            Err(CommandError::InvalidArgument(_))
+        ));
+        assert!(!arg.eq_ignore_ascii_case("nx"));
+    }
+
+    #[test]
+    fn test_frame_string_accessors() -> Result<()> {
+        let bulk: RespFrame = BulkString::new("key").into();
+        assert_eq!(bulk.as_bulk_bytes(), Some(&b"key"[..]));
+        assert_eq!(bulk.into_string()?, "key");
+
+        let simple: RespFrame = SimpleString::new("OK").into();
+        assert_eq!(simple.as_bulk_bytes(), None);
+        assert_eq!(simple.into_string()?, "OK");
+
+        let binary: RespFrame = BulkString::new(b"\xff".to_vec()).into();
+        assert_eq!(binary.as_bulk_bytes(), Some(&b"\xff"[..]));
+        assert!(matches!(
+            binary.into_string(),
+            Err(CommandError::Utf8Error(_))
+        ));
+
+        for frame in [RespFrame::Integer(1), RespFrame::Null(RespNull)] {
+            assert_eq!(frame.as_bulk_bytes(), None);
+            assert!(matches!(
+                frame.into_string(),
+                Err(CommandError::InvalidArgument(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_i64_from_frame() {
+        let int = |frame: RespFrame| i64::try_from(&frame);
+        assert_eq!(int(RespFrame::Integer(-7)).unwrap(), -7);
+        assert_eq!(int(BulkString::new("42").into()).unwrap(), 42);
+        assert_eq!(int(SimpleString::new("-3").into()).unwrap(), -3);
+        assert_eq!(
+            int(BulkString::new(i64::MIN.to_string()).into()).unwrap(),
+            i64::MIN
+        );
+
+        for frame in [
+            BulkString::new("4.5").into(),
+            BulkString::new("12a").into(),
+            BulkString::new("").into(),
+            BulkString::new("9223372036854775808").into(),
+            BulkString::new(b"\xff".to_vec()).into(),
+            SimpleString::new("ten").into(),
+            RespFrame::Boolean(true),
+            RespFrame::Null(RespNull),
+        ] {
+            assert!(matches!(int(frame), Err(CommandError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn test_write_hook() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let backend = Backend::new();
+        backend.set_write_hook(move |db, frame| sink.lock().unwrap().push((db, frame.clone())));
+        let frame = |args: &[&str]| -> RespFrame {
+            let args: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+            RespArray::new(args).into()
+        };
+
+        backend.execute_frame(frame(&["set", "k", "v"]));
+        backend.execute_frame(frame(&["get", "k"]));
+        backend.execute_frame(frame(&["hset", "h", "f", "v"]));
+        // a failed write isn't passed on
+        backend.execute_frame(frame(&["zadd", "k", "1", "m"]));
+        // neither are writes that didn't change anything
+        backend.execute_frame(frame(&["del", "nosuch"]));
+        backend.execute_frame(frame(&["set", "k", "v2", "nx"]));
+        backend.execute_frame(frame(&["lpop", "nosuch"]));
+        // the hook is server wide, and told which database was written
+        let db1 = backend.database(1).expect("database 1 exists");
+        db1.execute_frame(frame(&["set", "other", "v"]));
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (0, frame(&["set", "k", "v"])),
+                (0, frame(&["hset", "h", "f", "v"])),
+                (1, frame(&["set", "other", "v"])),
+            ]
+        );
+        assert!(!Backend::new().has_write_hook());
+    }
+
+    #[test]
+    fn test_replay() -> Result<()> {
+        let request = |args: &[&str]| -> Vec<u8> {
+            let args: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+            RespFrame::from(RespArray::new(args)).encode()
+        };
+        let mut stream = Vec::new();
+        for (key, value) in [("a", "1"), ("b", "2"), ("c", "3"), ("a", "4")] {
+            stream.extend(request(&["set", key, value]));
+        }
+        stream.extend(request(&["del", "b"]));
+
+        let backend = Backend::new();
+        assert_eq!(backend.replay(&stream[..])?, 5);
+        assert_eq!(
+            backend.get("a")?.as_deref(),
+            Some(&BulkString::new("4").into())
+        );
+        assert_eq!(backend.get("b")?, None);
+        assert_eq!(
+            backend.get("c")?.as_deref(),
+            Some(&BulkString::new("3").into())
+        );
+
+        // an empty stream applies nothing, a truncated one keeps what came before
+        assert_eq!(Backend::new().replay(&b""[..])?, 0);
+        let backend = Backend::new();
+        let cut = request(&["set", "a", "1"]).len() + 3;
+        assert!(matches!(
+            backend.replay(&stream[..cut]),
+            Err(CommandError::RespError(RespError::NotComplete))
+        ));
+        assert!(backend.exists("a"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute_many() {
+        let request = |args: &[&str]| -> RespFrame {
+            let args: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+            RespArray::new(args).into()
+        };
+        let backend = Backend::new();
+        let replies = backend.execute_many(vec![
+            request(&["set", "k", "v"]),
+            request(&["GET", "k"]),
+            request(&["hset", "h", "f", "hv"]),
+            request(&["hget", "h", "f"]),
+            request(&["get", "missing"]),
+        ]);
+        assert_eq!(
+            replies,
+            vec![
+                RESP_OK.clone(),
+                BulkString::new("v").into(),
+                RESP_OK.clone(),
+                BulkString::new("hv").into(),
+                RespFrame::Null(RespNull),
+            ]
+        );
+
+        // a frame that isn't a command gets an error in its place, the rest still runs
+        let replies = backend.execute_many(vec![
+            request(&["nosuch", "k"]),
+            BulkString::new("get").into(),
+            request(&["get", "h"]),
+            request(&["set", "k", "w"]),
+        ]);
+        assert_eq!(replies.len(), 4);
+        assert_eq!(
+            replies[0],
+            SimpleError::new("ERR unknown command 'nosuch'").into()
+        );
+        assert!(matches!(replies[1], RespFrame::Error(_)));
+        assert_eq!(replies[2], CommandError::WrongType.into());
+        assert_eq!(replies[3], RESP_OK.clone());
+
+        backend.set_read_only(true);
+        let reply = backend.execute_frame(request(&["set", "k", "x"]));
+        assert_eq!(reply, CommandError::ReadOnly.into());
+        assert_eq!(
+            backend.execute_frame(request(&["get", "k"])),
+            BulkString::new("w").into()
+        );
+    }
 
     #[test]
     fn test_sadd() -> Result<()> {
@@ -279,6 +1438,95 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_command_name_case_insensitive() -> Result<()> {
+        for name in ["GET", "Get", "get"] {
+            let frame = RespArray::new(vec![
+                BulkString::new(name).into(),
+                BulkString::new("hello").into(),
+            ]);
+            let cmd: Command = frame.try_into()?;
+            assert!(matches!(cmd, Command::Get(Get { ref key }) if key == "hello"));
+        }
+
+        let frame = RespArray::new(vec![
+            BulkString::new("HSET").into(),
+            BulkString::new("h").into(),
+            BulkString::new("f").into(),
+            BulkString::new("v").into(),
+        ]);
+        let cmd: Command = frame.try_into()?;
+        assert!(matches!(cmd, Command::HSet(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_is_write() -> Result<()> {
+        let is_write = |args: &[&str]| -> Result<bool> {
+            let args = args.iter().map(|a| a.to_string()).collect();
+            Ok(Command::from_args(args)?.is_write())
+        };
+        assert!(is_write(&["set", "k", "v"])?);
+        assert!(is_write(&["del", "k"])?);
+        assert!(is_write(&["lpop", "k"])?);
+        assert!(!is_write(&["get", "k"])?);
+        assert!(!is_write(&["type", "k"])?);
+        assert!(!is_write(&["ttl", "k"])?);
+        assert!(!is_write(&["publish", "c", "m"])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_from_args() -> Result<()> {
+        let cmd = Command::from_args(vec!["get".into(), "k".into()])?;
+        assert!(matches!(cmd, Command::Get(Get { ref key }) if key == "k"));
+
+        let backend = Backend::new();
+        Command::from_args(vec!["set".into(), "k".into(), "v".into()])?.execute(&backend);
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("v").into())
+        );
+
+        assert!(Command::from_args(vec!["get".into()]).is_err());
+        assert!(matches!(Command::from_args(vec![])?, Command::Empty(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_error_frame() -> Result<()> {
+        let err = Command::from_args(vec!["get".into()]).unwrap_err();
+        assert!(matches!(err, CommandError::WrongArity(_)));
+        let frame: RespFrame = err.into();
+        assert_eq!(
+            frame.encode(),
+            b"-ERR wrong number of arguments for 'get' command\r\n"
+        );
+
+        // inline requests are held to the same arity
+        let err = Command::try_from(parse_inline(b"GET")?).unwrap_err();
+        assert!(matches!(err, CommandError::WrongArity(ref name) if name == "get"));
+        assert!(matches!(
+            Command::try_from(parse_inline(b"set k")?),
+            Err(CommandError::WrongArity(_))
+        ));
+        assert!(matches!(
+            Command::try_from(parse_inline(b"set k v")?)?,
+            Command::Set(_)
+        ));
+
+        let frame: RespFrame = CommandError::WrongType.into();
+        assert_eq!(
+            frame.encode(),
+            b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_echo() -> Result<()> {
         let mut buf = BytesMut::new();
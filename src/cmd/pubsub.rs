@@ -0,0 +1,506 @@
+use super::{
+    extract_args, extract_strings, parse_keys, validate_command, CommandExecutor, ConnState,
+    PSubscribe, PUnsubscribe, PubSub, Publish, StatefulExecutor, Subscribe, Unsubscribe,
+};
+use crate::{
+    cmd::CommandError, Backend, BulkString, RespArray, RespArrayBuilder, RespFrame, RespNull,
+    SimpleError, Subscriber,
+};
+
+// subscriptions live on the connection, see ConnState
+impl CommandExecutor for Subscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR SUBSCRIBE is only supported on client connections").into()
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR UNSUBSCRIBE is only supported on client connections").into()
+    }
+}
+
+impl CommandExecutor for PSubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR PSUBSCRIBE is only supported on client connections").into()
+    }
+}
+
+impl CommandExecutor for PUnsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR PUNSUBSCRIBE is only supported on client connections").into()
+    }
+}
+
+impl StatefulExecutor for Subscribe {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        self.apply(&mut state.subscriber)
+    }
+}
+
+impl StatefulExecutor for Unsubscribe {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        self.apply(&mut state.subscriber)
+    }
+}
+
+impl StatefulExecutor for PSubscribe {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        self.apply(&mut state.subscriber)
+    }
+}
+
+impl StatefulExecutor for PUnsubscribe {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        self.apply(&mut state.subscriber)
+    }
+}
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.publish(&self.channel, self.message) as i64)
+    }
+}
+
+impl CommandExecutor for PubSub {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            PubSub::Channels(pattern) => {
+                let channels: Vec<RespFrame> = backend
+                    .pubsub_channels(pattern.as_deref())
+                    .into_iter()
+                    .map(|channel| BulkString::new(channel).into())
+                    .collect();
+                RespArray::new(channels).into()
+            }
+            PubSub::NumSub(channels) => {
+                let mut frames = Vec::with_capacity(channels.len() * 2);
+                for channel in channels {
+                    let count = backend.pubsub_numsub(&channel);
+                    frames.push(BulkString::new(channel).into());
+                    frames.push(RespFrame::Integer(count as i64));
+                }
+                RespArray::new(frames).into()
+            }
+            PubSub::NumPat => RespFrame::Integer(backend.pubsub_numpat() as i64),
+        }
+    }
+}
+
+impl Subscribe {
+    // one confirmation per channel, carrying the running subscription count.
+    // a channel repeated in the same call is acknowledged again but only counted once
+    pub fn apply(self, subscriber: &mut Subscriber) -> Vec<RespFrame> {
+        self.channels
+            .into_iter()
+            .map(|channel| {
+                subscriber.subscribe(&channel);
+                confirmation(
+                    "subscribe",
+                    BulkString::new(channel).into(),
+                    subscriber.subscription_count(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Unsubscribe {
+    pub fn apply(self, subscriber: &mut Subscriber) -> Vec<RespFrame> {
+        let channels = if self.channels.is_empty() {
+            subscriber.channels().to_vec()
+        } else {
+            self.channels
+        };
+        if channels.is_empty() {
+            return vec![confirmation(
+                "unsubscribe",
+                RespFrame::Null(RespNull),
+                subscriber.subscription_count(),
+            )];
+        }
+        channels
+            .into_iter()
+            .map(|channel| {
+                subscriber.unsubscribe(&channel);
+                confirmation(
+                    "unsubscribe",
+                    BulkString::new(channel).into(),
+                    subscriber.subscription_count(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl PSubscribe {
+    pub fn apply(self, subscriber: &mut Subscriber) -> Vec<RespFrame> {
+        self.patterns
+            .into_iter()
+            .map(|pattern| {
+                subscriber.psubscribe(&pattern);
+                confirmation(
+                    "psubscribe",
+                    BulkString::new(pattern).into(),
+                    subscriber.subscription_count(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl PUnsubscribe {
+    pub fn apply(self, subscriber: &mut Subscriber) -> Vec<RespFrame> {
+        let patterns = if self.patterns.is_empty() {
+            subscriber.patterns().to_vec()
+        } else {
+            self.patterns
+        };
+        if patterns.is_empty() {
+            return vec![confirmation(
+                "punsubscribe",
+                RespFrame::Null(RespNull),
+                subscriber.subscription_count(),
+            )];
+        }
+        patterns
+            .into_iter()
+            .map(|pattern| {
+                subscriber.punsubscribe(&pattern);
+                confirmation(
+                    "punsubscribe",
+                    BulkString::new(pattern).into(),
+                    subscriber.subscription_count(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn confirmation(kind: &str, channel: RespFrame, count: usize) -> RespFrame {
+    RespArrayBuilder::with_capacity(3)
+        .push_bulk(kind)
+        .push_frame(channel)
+        .push_int(count as i64)
+        .build()
+        .into()
+}
+
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Subscribe {
+            channels: parse_keys(value, "subscribe")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["unsubscribe"], n_args)?;
+        Ok(Unsubscribe {
+            channels: extract_strings(extract_args(value, 1)?)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PSubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PSubscribe {
+            patterns: parse_keys(value, "psubscribe")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PUnsubscribe {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["punsubscribe"], n_args)?;
+        Ok(PUnsubscribe {
+            patterns: extract_strings(extract_args(value, 1)?)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PubSub {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let subcommand = match value.get(1) {
+            Some(RespFrame::BulkString(sub)) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "pubsub command must have a subcommand".to_string(),
+                ))
+            }
+        };
+        let n_args = value.len() - 2;
+        match subcommand.as_slice() {
+            b"channels" => {
+                if n_args > 1 {
+                    return Err(CommandError::InvalidArgument(
+                        "pubsub channels takes at most 1 argument".to_string(),
+                    ));
+                }
+                validate_command(&value, &["pubsub", "channels"], n_args)?;
+                let pattern = extract_strings(extract_args(value, 2)?)?.pop();
+                Ok(PubSub::Channels(pattern))
+            }
+            b"numsub" => {
+                validate_command(&value, &["pubsub", "numsub"], n_args)?;
+                Ok(PubSub::NumSub(extract_strings(extract_args(value, 2)?)?))
+            }
+            b"numpat" => {
+                validate_command(&value, &["pubsub", "numpat"], 0)?;
+                Ok(PubSub::NumPat)
+            }
+            sub => Err(CommandError::InvalidCommand(format!(
+                "unknown pubsub subcommand {}",
+                String::from_utf8_lossy(sub)
+            ))),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Publish {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["publish"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(channel)), Some(message)) => Ok(Publish {
+                channel: String::from_utf8(channel.0)?,
+                message,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid channel or message".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use anyhow::Result;
+    use tokio::sync::mpsc;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn subscribe(args: &[&str]) -> Result<Subscribe> {
+        match command(args)? {
+            Command::Subscribe(cmd) => Ok(cmd),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    fn unsubscribe(args: &[&str]) -> Result<Unsubscribe> {
+        match command(args)? {
+            Command::Unsubscribe(cmd) => Ok(cmd),
+            cmd => panic!("unexpected command: {:?}", cmd),
+        }
+    }
+
+    fn expected(kind: &str, channel: Option<&str>, count: i64) -> RespFrame {
+        let channel = match channel {
+            Some(channel) => BulkString::new(channel).into(),
+            None => RespFrame::Null(RespNull),
+        };
+        RespArray::new(vec![
+            BulkString::new(kind).into(),
+            channel,
+            RespFrame::Integer(count),
+        ])
+        .into()
+    }
+
+    #[test]
+    fn test_subscribe_duplicate_channels() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(&backend, tx);
+
+        let ret = subscribe(&["subscribe", "a", "b", "a"])?.apply(&mut subscriber);
+        assert_eq!(
+            ret,
+            vec![
+                expected("subscribe", Some("a"), 1),
+                expected("subscribe", Some("b"), 2),
+                expected("subscribe", Some("a"), 2),
+            ]
+        );
+        assert_eq!(subscriber.subscription_count(), 2);
+        assert_eq!(backend.pubsub_numsub("a"), 1);
+
+        // the duplicate doesn't double deliver
+        assert_eq!(backend.publish("a", BulkString::new("hi").into()), 1);
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_confirmations() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(&backend, tx);
+
+        let ret = subscribe(&["subscribe", "news", "sports"])?.apply(&mut subscriber);
+        assert_eq!(
+            ret,
+            vec![
+                expected("subscribe", Some("news"), 1),
+                expected("subscribe", Some("sports"), 2),
+            ]
+        );
+
+        let ret = unsubscribe(&["unsubscribe", "news"])?.apply(&mut subscriber);
+        assert_eq!(ret, vec![expected("unsubscribe", Some("news"), 1)]);
+
+        let ret = unsubscribe(&["unsubscribe"])?.apply(&mut subscriber);
+        assert_eq!(ret, vec![expected("unsubscribe", Some("sports"), 0)]);
+
+        let ret = unsubscribe(&["unsubscribe"])?.apply(&mut subscriber);
+        assert_eq!(ret, vec![expected("unsubscribe", None, 0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_publish() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(&backend, tx);
+        subscribe(&["subscribe", "news"])?.apply(&mut subscriber);
+
+        let ret = command(&["publish", "news", "hello"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["publish", "other", "hello"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        let message: RespFrame = RespArray::new(vec![
+            BulkString::new("message").into(),
+            BulkString::new("news").into(),
+            BulkString::new("hello").into(),
+        ])
+        .into();
+        assert_eq!(rx.try_recv()?, message);
+        assert!(rx.try_recv().is_err());
+
+        // dropping the connection side removes its subscriptions
+        drop(subscriber);
+        let ret = command(&["publish", "news", "hello"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_psubscribe_receives_matching_messages() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriber = Subscriber::new(&backend, tx);
+        subscribe(&["subscribe", "news.sports"])?.apply(&mut subscriber);
+
+        let cmd = match command(&["psubscribe", "news.*"])? {
+            Command::PSubscribe(cmd) => cmd,
+            cmd => panic!("unexpected command: {:?}", cmd),
+        };
+        let ret = cmd.apply(&mut subscriber);
+        assert_eq!(ret, vec![expected("psubscribe", Some("news.*"), 2)]);
+
+        // one delivery for the channel and one for the pattern
+        let ret = command(&["publish", "news.sports", "goal"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        let ret = command(&["publish", "weather", "rain"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        let message: RespFrame = RespArray::new(vec![
+            BulkString::new("message").into(),
+            BulkString::new("news.sports").into(),
+            BulkString::new("goal").into(),
+        ])
+        .into();
+        let pmessage: RespFrame = RespArray::new(vec![
+            BulkString::new("pmessage").into(),
+            BulkString::new("news.*").into(),
+            BulkString::new("news.sports").into(),
+            BulkString::new("goal").into(),
+        ])
+        .into();
+        assert_eq!(rx.try_recv()?, message);
+        assert_eq!(rx.try_recv()?, pmessage);
+
+        let cmd = match command(&["punsubscribe"])? {
+            Command::PUnsubscribe(cmd) => cmd,
+            cmd => panic!("unexpected command: {:?}", cmd),
+        };
+        let ret = cmd.apply(&mut subscriber);
+        assert_eq!(ret, vec![expected("punsubscribe", Some("news.*"), 1)]);
+        let ret = command(&["publish", "news.weather", "sun"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pubsub_introspection() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx1) = mpsc::unbounded_channel();
+        let mut first = Subscriber::new(&backend, tx);
+        let (tx, _rx2) = mpsc::unbounded_channel();
+        let mut second = Subscriber::new(&backend, tx);
+        subscribe(&["subscribe", "news", "sports"])?.apply(&mut first);
+        subscribe(&["subscribe", "news"])?.apply(&mut second);
+        second.psubscribe("news.*");
+
+        let ret = command(&["pubsub", "channels"])?.execute(&backend);
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("news").into(),
+            BulkString::new("sports").into(),
+        ])
+        .into();
+        assert_eq!(ret, expected);
+
+        let ret = command(&["PUBSUB", "CHANNELS", "s*"])?.execute(&backend);
+        let expected: RespFrame = RespArray::new(vec![BulkString::new("sports").into()]).into();
+        assert_eq!(ret, expected);
+
+        let ret = command(&["pubsub", "numsub", "news", "sports", "none"])?.execute(&backend);
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("news").into(),
+            RespFrame::Integer(2),
+            BulkString::new("sports").into(),
+            RespFrame::Integer(1),
+            BulkString::new("none").into(),
+            RespFrame::Integer(0),
+        ])
+        .into();
+        assert_eq!(ret, expected);
+
+        let ret = command(&["pubsub", "numpat"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+
+        drop(first);
+        let ret = command(&["pubsub", "numsub", "sports"])?.execute(&backend);
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("sports").into(),
+            RespFrame::Integer(0),
+        ])
+        .into();
+        assert_eq!(ret, expected);
+
+        assert!(command(&["pubsub"]).is_err());
+        assert!(command(&["pubsub", "bogus"]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1141 @@
+use super::{
+    extract_args, extract_strings, lookup_command, lookup_docs, parse_int, validate_command, Auth,
+    Cluster, CommandCmd, CommandDoc, CommandExecutor, CommandSpec, ConnState, DebugCmd,
+    HealthCheck, Hello, Info, Lolwut, Reset, Select, StatefulExecutor, SwapDb, Time, COMMAND_DOCS,
+    COMMAND_TABLE, RESP_OK,
+};
+use crate::{
+    cmd::CommandError, Backend, BulkString, ProtocolVersion, RespArray, RespArrayBuilder,
+    RespFrame, RespMap, RespNull, RespSet, SimpleError, SimpleString,
+};
+use lazy_static::lazy_static;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    // what CLUSTER MYID reports, made up once per process
+    static ref NODE_ID: String = rand::random::<[u8; 20]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+}
+
+impl CommandExecutor for Lolwut {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        BulkString::new(format!("zredis {}", env!("CARGO_PKG_VERSION"))).into()
+    }
+}
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let all = self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|s| matches!(s.as_str(), "default" | "all" | "everything"));
+        let wants = |name: &str| all || self.sections.iter().any(|s| s == name);
+
+        let mut sections = Vec::new();
+        if wants("server") {
+            let uptime = backend.uptime_secs();
+            sections.push(format!(
+                "# Server\r\nredis_version:{}\r\nredis_mode:standalone\r\nuptime_in_seconds:{}\r\nuptime_in_days:{}\r\n",
+                env!("CARGO_PKG_VERSION"),
+                uptime,
+                uptime / (24 * 60 * 60)
+            ));
+        }
+        if wants("stats") {
+            sections.push(format!(
+                "# Stats\r\nexpired_keys:{}\r\nwrongtype_errors:{}\r\n",
+                backend.expired_keys(),
+                backend.wrongtype_errors()
+            ));
+        }
+        BulkString::new(sections.join("\r\n")).into()
+    }
+}
+
+// a standalone server, answered so cluster aware clients don't give up on it
+impl CommandExecutor for Cluster {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self {
+            Cluster::Info => BulkString::new("cluster_enabled:0\r\n").into(),
+            Cluster::MyId => BulkString::new(NODE_ID.as_str()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for Time {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        // a clock set before 1970 is reported as the epoch
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        RespArrayBuilder::with_capacity(2)
+            .push_bulk(now.as_secs().to_string())
+            .push_bulk(now.subsec_micros().to_string())
+            .build()
+            .into()
+    }
+}
+
+// status, whether writes are accepted and roughly how many keys the database holds
+impl CommandExecutor for HealthCheck {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut health = RespMap::new();
+        health.insert("status".to_string(), BulkString::new("ok").into());
+        health.insert(
+            "writable".to_string(),
+            RespFrame::Boolean(!backend.is_read_only()),
+        );
+        health.insert(
+            "keys".to_string(),
+            RespFrame::Integer(backend.approximate_len() as i64),
+        );
+        health.into()
+    }
+}
+
+impl CommandExecutor for CommandCmd {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        let specs: Vec<RespFrame> = match self {
+            CommandCmd::All => COMMAND_TABLE.iter().map(spec_frame).collect(),
+            CommandCmd::List => COMMAND_TABLE
+                .iter()
+                .map(|spec| BulkString::new(spec.name).into())
+                .collect(),
+            CommandCmd::Info(names) => names
+                .iter()
+                .map(|name| match lookup_command(name) {
+                    Some(spec) => spec_frame(spec),
+                    None => RespFrame::Null(RespNull),
+                })
+                .collect(),
+            CommandCmd::GetKeys(args) => {
+                let Some(spec) = lookup_command(&args[0]) else {
+                    return CommandError::InvalidArgument("Invalid command specified".to_string())
+                        .into();
+                };
+                match spec.keys(&args) {
+                    Ok(keys) => keys
+                        .into_iter()
+                        .map(|key| BulkString::new(key).into())
+                        .collect(),
+                    Err(e) => return e.into(),
+                }
+            }
+            CommandCmd::Docs(names) => return docs_frame(names),
+        };
+        RespArray::new(specs).into()
+    }
+}
+
+// a map from command name to its docs, names without docs are left out like redis does
+fn docs_frame(names: Vec<String>) -> RespFrame {
+    let docs: Vec<&CommandDoc> = if names.is_empty() {
+        COMMAND_DOCS.iter().collect()
+    } else {
+        names.iter().filter_map(|name| lookup_docs(name)).collect()
+    };
+    let mut map = RespMap::insertion_ordered();
+    for doc in docs {
+        map.insert(doc.name.to_string(), doc_frame(doc));
+    }
+    map.into()
+}
+
+fn doc_frame(doc: &CommandDoc) -> RespFrame {
+    let arguments: Vec<RespFrame> = doc
+        .arguments
+        .iter()
+        .map(|arg| {
+            let mut map = RespMap::insertion_ordered();
+            map.insert("name".to_string(), BulkString::new(arg.name).into());
+            map.insert("type".to_string(), BulkString::new(arg.kind).into());
+            if !arg.flags.is_empty() {
+                let flags: Vec<RespFrame> = arg
+                    .flags
+                    .iter()
+                    .map(|flag| SimpleString::new(*flag).into())
+                    .collect();
+                map.insert("flags".to_string(), RespArray::new(flags).into());
+            }
+            map.into()
+        })
+        .collect();
+    let arity = lookup_command(doc.name).map_or(0, |spec| spec.arity);
+    let mut map = RespMap::insertion_ordered();
+    map.insert("summary".to_string(), BulkString::new(doc.summary).into());
+    map.insert("since".to_string(), BulkString::new(doc.since).into());
+    map.insert("group".to_string(), BulkString::new(doc.group).into());
+    map.insert("arity".to_string(), RespFrame::Integer(arity));
+    map.insert("arguments".to_string(), RespArray::new(arguments).into());
+    map.into()
+}
+
+impl CommandExecutor for DebugCmd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            DebugCmd::Object(key) => {
+                let (Some(encoding), Some(payload)) =
+                    (backend.object_encoding(&key), backend.dump(&key))
+                else {
+                    return SimpleError::new("ERR no such key").into();
+                };
+                // values aren't shared, and the DUMP payload stands in for the rdb encoding
+                BulkString::new(format!(
+                    "refcount:1 encoding:{} serializedlength:{}",
+                    encoding,
+                    payload.len()
+                ))
+                .into()
+            }
+            // blocks the executing thread on purpose, like redis blocks the server
+            DebugCmd::Sleep(duration) => {
+                std::thread::sleep(duration);
+                RESP_OK.clone()
+            }
+            DebugCmd::SetActiveExpire(enabled) => {
+                backend.set_active_expire(enabled);
+                RESP_OK.clone()
+            }
+        }
+    }
+}
+
+// connection state lives on the connection, see ConnState
+impl CommandExecutor for Reset {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleString::new("RESET").into()
+    }
+}
+
+impl StatefulExecutor for Reset {
+    fn execute_with_state(self, _backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        state.reset();
+        vec![SimpleString::new("RESET").into()]
+    }
+}
+
+// only checks the index, the switching is done on the ConnState
+impl CommandExecutor for Select {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.index >= backend.num_databases() {
+            return SimpleError::new("ERR DB index is out of range").into();
+        }
+        SimpleString::new("OK").into()
+    }
+}
+
+impl StatefulExecutor for Select {
+    fn execute_with_state(self, backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        let index = self.index;
+        let reply = self.execute(backend);
+        if !matches!(reply, RespFrame::Error(_)) {
+            state.db = index;
+        }
+        vec![reply]
+    }
+}
+
+// only checks the credentials, the outcome is remembered on the ConnState
+impl CommandExecutor for Auth {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let Some(username) = self.username else {
+            if !backend.requires_auth() {
+                return SimpleError::new("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?").into();
+            }
+            return check_password(backend, "default", &self.password);
+        };
+        check_password(backend, &username, &self.password)
+    }
+}
+
+impl StatefulExecutor for Auth {
+    fn execute_with_state(self, backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        let reply = self.execute(backend);
+        if !matches!(reply, RespFrame::Error(_)) {
+            state.authenticated = true;
+        }
+        vec![reply]
+    }
+}
+
+fn check_password(backend: &Backend, username: &str, password: &str) -> RespFrame {
+    if backend.check_password(username, password) {
+        RESP_OK.clone()
+    } else {
+        CommandError::WrongPass.into()
+    }
+}
+
+// the protocol is switched on the ConnState, the reply already goes out in the new one
+impl CommandExecutor for Hello {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some((username, password)) = &self.auth {
+            if !backend.check_password(username, password) {
+                return CommandError::WrongPass.into();
+            }
+        }
+        let proto = match self.protocol() {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        };
+        let mut info = RespMap::new();
+        info.insert("server".to_string(), BulkString::new("zredis").into());
+        let version = BulkString::new(env!("CARGO_PKG_VERSION"));
+        info.insert("version".to_string(), version.into());
+        info.insert("proto".to_string(), RespFrame::Integer(proto));
+        info.insert("mode".to_string(), BulkString::new("standalone").into());
+        info.insert("role".to_string(), BulkString::new("master").into());
+        info.insert("modules".to_string(), RespArray::new(vec![]).into());
+        info.into()
+    }
+}
+
+impl StatefulExecutor for Hello {
+    fn execute_with_state(self, backend: &Backend, state: &mut ConnState) -> Vec<RespFrame> {
+        // HELLO is let through before AUTH, but only to authenticate
+        if !state.authenticated && backend.requires_auth() && !self.authenticates() {
+            return vec![CommandError::NoAuth.into()];
+        }
+        let cmd = self.or_protocol(state.protocol);
+        let (version, authenticates) = (cmd.protocol(), cmd.authenticates());
+        let reply = cmd.execute(backend);
+        if !matches!(reply, RespFrame::Error(_)) {
+            state.protocol = version;
+            state.authenticated |= authenticates;
+        }
+        vec![reply]
+    }
+}
+
+impl Hello {
+    // without a version HELLO keeps, and reports, the one the connection already speaks
+    pub fn or_protocol(mut self, current: ProtocolVersion) -> Self {
+        self.protocol.get_or_insert(current);
+        self
+    }
+
+    pub fn protocol(&self) -> ProtocolVersion {
+        self.protocol.unwrap_or_default()
+    }
+
+    pub fn authenticates(&self) -> bool {
+        self.auth.is_some()
+    }
+}
+
+impl CommandExecutor for SwapDb {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.swap_databases(self.a, self.b) {
+            return SimpleError::new("ERR DB index is out of range").into();
+        }
+        SimpleString::new("OK").into()
+    }
+}
+
+// [name, arity, flags, first_key, last_key, step]
+fn spec_frame(spec: &CommandSpec) -> RespFrame {
+    let flags: Vec<RespFrame> = spec
+        .flags
+        .iter()
+        .map(|flag| SimpleString::new(*flag).into())
+        .collect();
+    RespArrayBuilder::with_capacity(6)
+        .push_bulk(spec.name)
+        .push_int(spec.arity)
+        .push_frame(RespSet::new(flags))
+        .push_int(spec.first_key)
+        .push_int(spec.last_key)
+        .push_int(spec.step)
+        .build()
+        .into()
+}
+
+// LOLWUT [VERSION version], the arguments are accepted and ignored
+impl TryFrom<RespArray> for Lolwut {
+    type Error = CommandError;
+    fn try_from(_value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Lolwut)
+    }
+}
+
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["info"], n_args)?;
+        let sections = extract_strings(extract_args(value, 1)?)?
+            .into_iter()
+            .map(|s| s.to_ascii_lowercase())
+            .collect();
+        Ok(Info { sections })
+    }
+}
+
+impl TryFrom<RespArray> for Cluster {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster"], 1)?;
+        let sub = extract_strings(extract_args(value, 1)?)?
+            .pop()
+            .unwrap_or_default();
+        match sub.to_ascii_lowercase().as_str() {
+            "info" => Ok(Cluster::Info),
+            "myid" => Ok(Cluster::MyId),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'",
+                sub
+            ))),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Time {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["time"], 0)?;
+        Ok(Time)
+    }
+}
+
+impl TryFrom<RespArray> for HealthCheck {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["healthcheck"], 0)?;
+        Ok(HealthCheck)
+    }
+}
+
+impl TryFrom<RespArray> for CommandCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let subcommand = match value.get(1) {
+            None => return Ok(CommandCmd::All),
+            Some(RespFrame::BulkString(sub)) => sub.to_ascii_lowercase(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid subcommand".to_string(),
+                ))
+            }
+        };
+        match subcommand.as_slice() {
+            b"list" => {
+                validate_command(&value, &["command", "list"], 0)?;
+                Ok(CommandCmd::List)
+            }
+            b"info" => {
+                let n_args = value.len() - 2;
+                validate_command(&value, &["command", "info"], n_args)?;
+                Ok(CommandCmd::Info(extract_strings(extract_args(value, 2)?)?))
+            }
+            b"getkeys" => {
+                let n_args = value.len() - 2;
+                if n_args == 0 {
+                    return Err(CommandError::InvalidArgument(
+                        "command getkeys must have at least 1 argument".to_string(),
+                    ));
+                }
+                validate_command(&value, &["command", "getkeys"], n_args)?;
+                Ok(CommandCmd::GetKeys(extract_strings(extract_args(
+                    value, 2,
+                )?)?))
+            }
+            b"docs" => {
+                let n_args = value.len() - 2;
+                validate_command(&value, &["command", "docs"], n_args)?;
+                Ok(CommandCmd::Docs(extract_strings(extract_args(value, 2)?)?))
+            }
+            sub => Err(CommandError::InvalidCommand(format!(
+                "unknown command subcommand {}",
+                String::from_utf8_lossy(sub)
+            ))),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for DebugCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["debug"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(sub), Some(key), None) if sub.eq_ignore_ascii_case("object") => {
+                Ok(DebugCmd::Object(key))
+            }
+            (Some(sub), Some(secs), None) if sub.eq_ignore_ascii_case("sleep") => {
+                let secs = secs
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument("value is not a valid float".to_string())
+                    })?;
+                Ok(DebugCmd::Sleep(secs))
+            }
+            (Some(sub), Some(flag), None) if sub.eq_ignore_ascii_case("set-active-expire") => {
+                match flag.as_str() {
+                    "0" => Ok(DebugCmd::SetActiveExpire(false)),
+                    "1" => Ok(DebugCmd::SetActiveExpire(true)),
+                    _ => Err(CommandError::InvalidArgument(
+                        "value is not an integer or out of range".to_string(),
+                    )),
+                }
+            }
+            (Some(sub), _, _) => Err(CommandError::InvalidArgument(format!(
+                "unknown subcommand '{}'",
+                sub
+            ))),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Select {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["select"], 1)?;
+        let index = parse_db_index(extract_args(value, 1)?.into_iter().next())?;
+        Ok(Select { index })
+    }
+}
+
+impl TryFrom<RespArray> for SwapDb {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["swapdb"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let a = parse_db_index(args.next())?;
+        let b = parse_db_index(args.next())?;
+        Ok(SwapDb { a, b })
+    }
+}
+
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["auth"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (Some(password), None, None) => Ok(Auth {
+                username: None,
+                password,
+            }),
+            (Some(username), Some(password), None) => Ok(Auth {
+                username: Some(username),
+                password,
+            }),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let n_args = value.len() - 1;
+        validate_command(&value, &["hello"], n_args)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        let protocol = match args.next().map(|v| v.parse::<i64>()) {
+            None => None,
+            Some(Ok(2)) => Some(ProtocolVersion::Resp2),
+            Some(Ok(3)) => Some(ProtocolVersion::Resp3),
+            Some(Ok(_)) => return Err(CommandError::NoProto),
+            Some(Err(_)) => {
+                return Err(CommandError::InvalidArgument(
+                    "Protocol version is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+        let mut auth = None;
+        while let Some(option) = args.next() {
+            match (
+                option.to_ascii_lowercase().as_str(),
+                args.next(),
+                args.next(),
+            ) {
+                ("auth", Some(username), Some(password)) => auth = Some((username, password)),
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        option
+                    )))
+                }
+            }
+        }
+        Ok(Hello { protocol, auth })
+    }
+}
+
+// SELECT, SWAPDB and MOVE, a negative index is out of range too
+pub(crate) fn parse_db_index(value: Option<RespFrame>) -> Result<usize, CommandError> {
+    usize::try_from(parse_int(value)?)
+        .map_err(|_| CommandError::InvalidArgument("DB index is out of range".to_string()))
+}
+
+impl TryFrom<RespArray> for Reset {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["reset"], 0)?;
+        Ok(Reset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, Subscriber};
+    use anyhow::Result;
+    use std::collections::HashSet;
+    use std::time::Instant;
+    use tokio::sync::mpsc;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    #[test]
+    fn test_lolwut() -> Result<()> {
+        let ret = command(&["lolwut"])?.execute(&Backend::new());
+
+        let RespFrame::BulkString(banner) = ret else {
+            panic!("lolwut must reply with a bulk string");
+        };
+        let banner = String::from_utf8(banner.0)?;
+        assert!(banner.contains(env!("CARGO_PKG_VERSION")));
+        assert!(banner.starts_with("zredis "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_info() -> Result<()> {
+        let ret = command(&["command", "info", "get", "nosuch"])?.execute(&Backend::new());
+        let RespFrame::Array(specs) = ret else {
+            panic!("command info must reply with an array");
+        };
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[1], RespFrame::Null(RespNull));
+
+        let RespFrame::Array(ref get) = specs[0] else {
+            panic!("command spec must be an array");
+        };
+        assert_eq!(get[0], BulkString::new("get").into());
+        assert_eq!(get[1], RespFrame::Integer(2));
+        let RespFrame::Set(ref flags) = get[2] else {
+            panic!("command flags must be a set");
+        };
+        assert!(flags.contains(&SimpleString::new("readonly").into()));
+        assert_eq!(&get[3..], &[1.into(), 1.into(), 1.into()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_docs() -> Result<()> {
+        let ret = command(&["command", "docs", "GET", "nosuch"])?.execute(&Backend::new());
+        let RespFrame::Map(docs) = ret else {
+            panic!("command docs must reply with a map");
+        };
+        // names without docs are left out
+        assert_eq!(docs.len(), 1);
+        let Some(RespFrame::Map(get)) = docs.get("get") else {
+            panic!("get must have docs");
+        };
+        assert!(matches!(get.get("summary"), Some(RespFrame::BulkString(_))));
+        assert_eq!(get.get("arity"), Some(&RespFrame::Integer(2)));
+        let Some(RespFrame::Array(args)) = get.get("arguments") else {
+            panic!("arguments must be an array");
+        };
+        let RespFrame::Map(ref key) = args[0] else {
+            panic!("an argument must be a map");
+        };
+        assert_eq!(key.get("type"), Some(&BulkString::new("key").into()));
+
+        let RespFrame::Map(all) = command(&["command", "docs"])?.execute(&Backend::new()) else {
+            panic!("command docs must reply with a map");
+        };
+        for name in ["get", "set", "hset", "sadd"] {
+            assert!(all.get(name).is_some(), "{} must have docs", name);
+        }
+
+        Ok(())
+    }
+
+    fn getkeys(args: &[&str]) -> Result<RespFrame> {
+        let args: Vec<&str> = ["command", "getkeys"].iter().chain(args).copied().collect();
+        Ok(command(&args)?.execute(&Backend::new()))
+    }
+
+    fn bulks(values: &[&str]) -> RespFrame {
+        let frames: Vec<RespFrame> = values.iter().map(|v| BulkString::new(*v).into()).collect();
+        RespArray::new(frames).into()
+    }
+
+    #[test]
+    fn test_command_getkeys_single_key() -> Result<()> {
+        assert_eq!(getkeys(&["set", "foo", "bar"])?, bulks(&["foo"]));
+        assert_eq!(getkeys(&["GET", "foo"])?, bulks(&["foo"]));
+        assert_eq!(getkeys(&["object", "encoding", "foo"])?, bulks(&["foo"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys_multi_key() -> Result<()> {
+        assert_eq!(getkeys(&["del", "a", "b", "c"])?, bulks(&["a", "b", "c"]));
+        // the trailing timeout isn't a key
+        assert_eq!(getkeys(&["blpop", "a", "b", "0"])?, bulks(&["a", "b"]));
+        // numkeys says how many sources follow the destination
+        let args = ["zunionstore", "d", "2", "a", "b", "WEIGHTS", "1", "2"];
+        assert_eq!(getkeys(&args)?, bulks(&["d", "a", "b"]));
+        assert_eq!(
+            getkeys(&["zinterstore", "d", "1", "a"])?,
+            bulks(&["d", "a"])
+        );
+        assert!(matches!(
+            getkeys(&["zinterstore", "d", "3", "a"])?,
+            RespFrame::Error(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys_step() -> Result<()> {
+        assert_eq!(getkeys(&["mset", "a", "1", "b", "2"])?, bulks(&["a", "b"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys_errors() -> Result<()> {
+        assert!(matches!(getkeys(&["nosuch", "a"])?, RespFrame::Error(_)));
+        assert!(matches!(getkeys(&["get"])?, RespFrame::Error(_)));
+        assert!(matches!(getkeys(&["echo", "hi"])?, RespFrame::Error(_)));
+        assert!(command(&["command", "getkeys"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_all() -> Result<()> {
+        let ret = command(&["COMMAND"])?.execute(&Backend::new());
+        let RespFrame::Array(specs) = ret else {
+            panic!("command must reply with an array");
+        };
+        assert_eq!(specs.len(), COMMAND_TABLE.len());
+
+        let names: HashSet<_> = COMMAND_TABLE.iter().map(|spec| spec.name).collect();
+        assert_eq!(names.len(), COMMAND_TABLE.len());
+        assert!(command(&["command", "bogus"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_list() -> Result<()> {
+        let ret = command(&["command", "LIST"])?.execute(&Backend::new());
+        let RespFrame::Array(names) = ret else {
+            panic!("command list must reply with an array");
+        };
+        assert_eq!(names.len(), COMMAND_TABLE.len());
+        for name in ["get", "set", "hset", "sadd"] {
+            assert!(names.contains(&BulkString::new(name).into()), "{}", name);
+        }
+        // every listed command can be looked up again
+        for name in names.iter() {
+            let RespFrame::BulkString(name) = name else {
+                panic!("command names are bulk strings");
+            };
+            assert!(lookup_command(&String::from_utf8_lossy(name)).is_some());
+        }
+        assert!(command(&["command", "list", "filterby"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_object() -> Result<()> {
+        let backend = Backend::with_strings(&[("k", "hello")]);
+
+        let ret = command(&["debug", "object", "k"])?.execute(&backend);
+        let RespFrame::BulkString(info) = ret else {
+            panic!("debug object must reply with a bulk string");
+        };
+        let info = String::from_utf8(info.0)?;
+        assert!(info.contains("encoding:embstr"));
+        assert!(info.contains("serializedlength:"));
+        assert!(info.contains("refcount:1"));
+
+        let ret = command(&["debug", "object", "missing"])?.execute(&backend);
+        assert_eq!(ret, SimpleError::new("ERR no such key").into());
+        assert!(command(&["debug", "jmap"]).is_err());
+        assert!(command(&["debug"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_sleep() -> Result<()> {
+        let backend = Backend::new();
+        let start = std::time::Instant::now();
+        let ret = command(&["debug", "sleep", "0.05"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        assert!(command(&["debug", "sleep", "soon"]).is_err());
+        assert!(command(&["debug", "sleep", "-1"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth() -> Result<()> {
+        let backend = Backend::new();
+        let ret = command(&["auth", "secret"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        // the default user has no password, so anything goes
+        let ret = command(&["auth", "default", "whatever"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+
+        backend.set_password(Some("secret".to_string()));
+        let wrongpass =
+            SimpleError::new("WRONGPASS invalid username-password pair or user is disabled.");
+        let ret = command(&["auth", "secret"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        let ret = command(&["auth", "default", "secret"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        let ret = command(&["auth", "wrong"])?.execute(&backend);
+        assert_eq!(ret, wrongpass.clone().into());
+        let ret = command(&["auth", "admin", "secret"])?.execute(&backend);
+        assert_eq!(ret, wrongpass.into());
+        assert!(command(&["auth"]).is_err());
+        assert!(command(&["auth", "a", "b", "c"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_password(Some("secret".to_string()));
+
+        let ret = command(&["hello", "3", "auth", "default", "secret"])?.execute(&backend);
+        let RespFrame::Map(info) = ret else {
+            panic!("hello must reply with a map");
+        };
+        assert_eq!(info.get("proto"), Some(&RespFrame::Integer(3)));
+        assert_eq!(info.get("server"), Some(&BulkString::new("zredis").into()));
+
+        let ret = command(&["hello", "2", "AUTH", "default", "wrong"])?.execute(&backend);
+        let wrongpass =
+            SimpleError::new("WRONGPASS invalid username-password pair or user is disabled.");
+        assert_eq!(ret, wrongpass.into());
+
+        let Command::Hello(hello) = command(&["hello"])? else {
+            panic!("hello must parse to Hello");
+        };
+        assert!(!hello.authenticates());
+        assert_eq!(
+            hello.or_protocol(ProtocolVersion::Resp3).protocol(),
+            ProtocolVersion::Resp3
+        );
+
+        let err = command(&["hello", "4"]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::NoProto)
+        ));
+        assert!(command(&["hello", "three"]).is_err());
+        assert!(command(&["hello", "3", "auth", "default"]).is_err());
+
+        Ok(())
+    }
+
+    fn info_field(backend: &Backend, section: &str, field: &str) -> Result<Option<String>> {
+        let RespFrame::BulkString(info) = command(&["info", section])?.execute(backend) else {
+            panic!("info must reply with a bulk string");
+        };
+        let info = String::from_utf8(info.to_vec())?;
+        Ok(info
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{}:", field)))
+            .map(str::to_string))
+    }
+
+    #[test]
+    fn test_healthcheck() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("a".to_string(), BulkString::new("1").into());
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+
+        let RespFrame::Map(health) = command(&["healthcheck"])?.execute(&backend) else {
+            panic!("healthcheck must reply with a map");
+        };
+        assert_eq!(health.get("status"), Some(&BulkString::new("ok").into()));
+        assert_eq!(health.get("keys"), Some(&RespFrame::Integer(2)));
+        assert_eq!(health.get("writable"), Some(&RespFrame::Boolean(true)));
+
+        backend.set_read_only(true);
+        let RespFrame::Map(health) = command(&["healthcheck"])?.execute(&backend) else {
+            panic!("healthcheck must reply with a map");
+        };
+        assert_eq!(health.get("writable"), Some(&RespFrame::Boolean(false)));
+        assert!(command(&["healthcheck", "x"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time() -> Result<()> {
+        let backend = Backend::new();
+        let RespFrame::Array(ret) = command(&["time"])?.execute(&backend) else {
+            panic!("time must reply with an array");
+        };
+        let [RespFrame::BulkString(secs), RespFrame::BulkString(micros)] = &ret[..] else {
+            panic!("time must reply with two bulk strings: {:?}", ret);
+        };
+        let secs: u64 = std::str::from_utf8(secs)?.parse()?;
+        let micros: u64 = std::str::from_utf8(micros)?.parse()?;
+        // some time after 2024-01-01
+        assert!(secs > 1_704_067_200);
+        assert!(micros < 1_000_000);
+
+        assert!(command(&["time", "now"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster() -> Result<()> {
+        let backend = Backend::new();
+
+        let ret = command(&["cluster", "info"])?.execute(&backend);
+        let RespFrame::BulkString(info) = ret else {
+            panic!("cluster info must reply with a bulk string");
+        };
+        let info = String::from_utf8(info.to_vec())?;
+        assert!(info.lines().any(|line| line == "cluster_enabled:0"));
+
+        let RespFrame::BulkString(id) = command(&["CLUSTER", "MYID"])?.execute(&backend) else {
+            panic!("cluster myid must reply with a bulk string");
+        };
+        assert_eq!(id.len(), 40);
+        assert!(id.iter().all(|b| b.is_ascii_hexdigit()));
+        // the same id for as long as the process runs
+        let again = command(&["cluster", "myid"])?.execute(&backend);
+        assert_eq!(again, RespFrame::BulkString(id));
+
+        assert!(command(&["cluster"]).is_err());
+        assert!(command(&["cluster", "nodes"]).is_err());
+        assert!(command(&["cluster", "info", "x"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_uptime() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let before = backend.uptime();
+        assert!(before < Duration::from_secs(5));
+        assert_eq!(
+            info_field(&backend, "server", "uptime_in_seconds")?.as_deref(),
+            Some("0")
+        );
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(backend.uptime() >= before + Duration::from_millis(20));
+        // every database reports the server's uptime
+        let db1 = backend.database(1).expect("database 1 exists");
+        assert_eq!(db1.start_time, backend.start_time);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_stats() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let db1 = backend.database(1).expect("database 1 exists");
+        assert_eq!(
+            info_field(&backend, "stats", "wrongtype_errors")?.as_deref(),
+            Some("0")
+        );
+        assert_eq!(
+            info_field(&backend, "stats", "expired_keys")?.as_deref(),
+            Some("0")
+        );
+
+        backend.set("s".to_string(), BulkString::new("v").into());
+        let ret = command(&["hget", "s", "f"])?.execute(&backend);
+        assert_eq!(ret, CommandError::WrongType.into());
+
+        // one expiry noticed by a read, one reclaimed by the cycle on another database
+        let past = Instant::now() - Duration::from_millis(1);
+        for db in [&backend, &db1] {
+            db.set("k".to_string(), BulkString::new("v").into());
+            db.expires.insert("k".to_string(), past);
+        }
+        assert_eq!(backend.get("k")?, None);
+        assert_eq!(backend.expire_cycle(), 1);
+
+        // the counters are server wide
+        for db in [&backend, &db1] {
+            assert_eq!(
+                info_field(db, "stats", "wrongtype_errors")?.as_deref(),
+                Some("1")
+            );
+            assert_eq!(
+                info_field(db, "stats", "expired_keys")?.as_deref(),
+                Some("2")
+            );
+        }
+
+        assert!(info_field(&backend, "everything", "expired_keys")?.is_some());
+        assert!(info_field(&backend, "server", "expired_keys")?.is_none());
+        assert!(info_field(&backend, "server", "redis_version")?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_set_active_expire() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let db1 = backend.database(1).expect("database 1 exists");
+        let past = Instant::now() - Duration::from_millis(1);
+        for db in [&backend, &db1] {
+            db.set("k".to_string(), BulkString::new("v").into());
+            db.expires.insert("k".to_string(), past);
+        }
+
+        let ret = command(&["debug", "set-active-expire", "0"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        assert_eq!(backend.expire_cycle(), 0);
+        // expired, but still stored until something looks at it
+        assert!(backend.map.contains_key("k"));
+        assert!(db1.map.contains_key("k"));
+        assert_eq!(backend.get("k")?, None);
+        assert!(!backend.map.contains_key("k"));
+        assert!(!backend.expires.contains_key("k"));
+
+        // turned back on, a cycle reclaims the rest without any access
+        command(&["debug", "set-active-expire", "1"])?.execute(&backend);
+        assert_eq!(backend.expire_cycle(), 1);
+        assert!(!db1.map.contains_key("k"));
+        assert!(!db1.expires.contains_key("k"));
+
+        assert!(command(&["debug", "set-active-expire", "2"]).is_err());
+        assert!(command(&["debug", "set-active-expire"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_range() -> Result<()> {
+        let backend = Backend::with_databases(4);
+        assert_eq!(backend.num_databases(), 4);
+
+        let ret = command(&["select", "3"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+        let ret = command(&["select", "4"])?.execute(&backend);
+        assert_eq!(ret, SimpleError::new("ERR DB index is out of range").into());
+        assert!(command(&["select", "-1"]).is_err());
+        assert!(command(&["select", "one"]).is_err());
+
+        assert_eq!(Backend::new().num_databases(), crate::DEFAULT_DATABASES);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_databases_are_separate() -> Result<()> {
+        let backend = Backend::with_databases(2);
+        let other = backend.database(1).expect("database 1 exists");
+        backend.set("k".to_string(), BulkString::new("zero").into());
+        other.set("k".to_string(), BulkString::new("one").into());
+
+        assert_eq!(
+            backend.get("k")?.as_deref(),
+            Some(&BulkString::new("zero").into())
+        );
+        assert_eq!(
+            other.get("k")?.as_deref(),
+            Some(&BulkString::new("one").into())
+        );
+        assert!(backend.database(2).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swapdb() -> Result<()> {
+        let backend = Backend::with_databases(4);
+        let db1 = backend.database(1).expect("database 1 exists");
+        backend.set("zero".to_string(), BulkString::new("0").into());
+        db1.set("one".to_string(), BulkString::new("1").into());
+
+        let ret = command(&["swapdb", "0", "1"])?.execute(&backend);
+        assert_eq!(ret, SimpleString::new("OK").into());
+
+        // handles are looked up again to see the swap, like a connection does per request
+        let db0 = backend.database(0).expect("database 0 exists");
+        let db1 = backend.database(1).expect("database 1 exists");
+        assert_eq!(
+            db0.get("one")?.as_deref(),
+            Some(&BulkString::new("1").into())
+        );
+        assert!(!db0.exists("zero"));
+        assert_eq!(
+            db1.get("zero")?.as_deref(),
+            Some(&BulkString::new("0").into())
+        );
+        assert!(!db1.exists("one"));
+
+        let ret = command(&["swapdb", "0", "4"])?.execute(&backend);
+        assert_eq!(ret, SimpleError::new("ERR DB index is out of range").into());
+        assert!(command(&["swapdb", "0"]).is_err());
+        assert!(command(&["swapdb", "0", "-1"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut state = ConnState::new(Subscriber::new(&backend, tx));
+        state.subscriber.subscribe("news");
+        state.subscriber.psubscribe("news.*");
+        state.db = 2;
+        state.protocol = ProtocolVersion::Resp3;
+        state.authenticated = true;
+        state.name = Some("worker".to_string());
+
+        let Command::Reset(cmd) = command(&["RESET"])? else {
+            panic!("reset must parse to Command::Reset");
+        };
+        assert_eq!(
+            cmd.execute_with_state(&backend, &mut state),
+            vec![RespFrame::from(SimpleString::new("RESET"))]
+        );
+        assert!(!state.in_subscribe_mode());
+        assert_eq!(state.db, 0);
+        assert_eq!(state.protocol, ProtocolVersion::Resp2);
+        assert!(!state.authenticated);
+        assert_eq!(state.name, None);
+        assert_eq!(backend.pubsub_numsub("news"), 0);
+        assert_eq!(backend.pubsub_numpat(), 0);
+
+        assert!(command(&["reset", "now"]).is_err());
+
+        Ok(())
+    }
+}
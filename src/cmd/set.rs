@@ -0,0 +1,392 @@
+use super::{
+    extract_args, extract_strings, parse_keys, validate_command, CommandExecutor, SDiff,
+    SDiffStore, SInter, SInterCard, SInterStore, SMembers, SUnion, SUnionStore,
+};
+use crate::{cmd::CommandError, Backend, KeyType, RespArray, RespFrame, RespSet};
+
+impl CommandExecutor for SMembers {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::Set) {
+            return e.into();
+        }
+        RespSet::new(backend.smembers(&self.key)).into()
+    }
+}
+
+impl CommandExecutor for SInter {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespSet::new(backend.sinter(&self.keys)).into()
+    }
+}
+
+impl CommandExecutor for SUnion {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespSet::new(backend.sunion(&self.keys)).into()
+    }
+}
+
+impl CommandExecutor for SDiff {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.sdiff_iter(&self.keys, |diff| {
+            RespSet::new(diff.collect::<Vec<_>>()).into()
+        })
+    }
+}
+
+impl CommandExecutor for SInterStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let members = backend.sinter(&self.keys);
+        RespFrame::Integer(backend.sstore(self.dest, members) as i64)
+    }
+}
+
+impl CommandExecutor for SUnionStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let members = backend.sunion(&self.keys);
+        RespFrame::Integer(backend.sstore(self.dest, members) as i64)
+    }
+}
+
+impl CommandExecutor for SDiffStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // collected first, sstore writes to the keyspace
+        let members: Vec<_> = backend.sdiff_iter(&self.keys, |diff| diff.collect());
+        RespFrame::Integer(backend.sstore(self.dest, members) as i64)
+    }
+}
+
+impl CommandExecutor for SInterCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.sintercard(&self.keys, self.limit) as i64)
+    }
+}
+
+// destination key [key ...]
+fn parse_store_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<String>), CommandError> {
+    let mut keys = parse_keys(value, name)?;
+    if keys.len() < 2 {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have at least 2 arguments",
+            name
+        )));
+    }
+    let dest = keys.remove(0);
+    Ok((dest, keys))
+}
+
+impl TryFrom<RespArray> for SInter {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(SInter {
+            keys: parse_keys(value, "sinter")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SUnion {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(SUnion {
+            keys: parse_keys(value, "sunion")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SDiff {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(SDiff {
+            keys: parse_keys(value, "sdiff")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SInterStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = parse_store_args(value, "sinterstore")?;
+        Ok(SInterStore { dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SUnionStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = parse_store_args(value, "sunionstore")?;
+        Ok(SUnionStore { dest, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SDiffStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys) = parse_store_args(value, "sdiffstore")?;
+        Ok(SDiffStore { dest, keys })
+    }
+}
+
+// numkeys key [key ...] [LIMIT limit]
+impl TryFrom<RespArray> for SInterCard {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "sintercard")?.into_iter();
+        let numkeys = args
+            .next()
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                CommandError::InvalidArgument("numkeys should be greater than 0".to_string())
+            })?;
+        let keys: Vec<String> = args.by_ref().take(numkeys).collect();
+        if keys.len() != numkeys {
+            return Err(CommandError::InvalidArgument(
+                "Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+
+        let limit = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => None,
+            (Some(opt), Some(limit), None) if opt.eq_ignore_ascii_case("limit") => {
+                match limit.parse::<usize>() {
+                    Ok(limit) => (limit > 0).then_some(limit),
+                    Err(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            "LIMIT can't be negative".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+impl TryFrom<RespArray> for SMembers {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smembers"], 1)?;
+        let mut args = extract_strings(extract_args(value, 1)?)?.into_iter();
+        Ok(SMembers {
+            key: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString};
+    use anyhow::Result;
+    use std::collections::HashSet;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn members(items: &[&str]) -> RespSet {
+        RespSet::new(
+            items
+                .iter()
+                .map(|m| BulkString::new(*m).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    fn setup() -> Result<Backend> {
+        let backend = Backend::new();
+        for m in ["a", "b", "c", "d"] {
+            command(&["sadd", "s1", m])?.execute(&backend);
+        }
+        for m in ["c", "d", "e"] {
+            command(&["sadd", "s2", m])?.execute(&backend);
+        }
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_sadd_keeps_existing_members() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sadd", "s1", "a"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert_eq!(backend.smembers("s1").len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_commands_wrong_type() -> Result<()> {
+        let backend = setup()?;
+        command(&["set", "str", "v"])?.execute(&backend);
+        command(&["hset", "h", "f", "v"])?.execute(&backend);
+
+        for key in ["str", "h"] {
+            let ret = command(&["sadd", key, "m"])?.execute(&backend);
+            assert_eq!(ret, CommandError::WrongType.into());
+            let ret = command(&["sismember", key, "m"])?.execute(&backend);
+            assert_eq!(ret, CommandError::WrongType.into());
+            let ret = command(&["smembers", key])?.execute(&backend);
+            assert_eq!(ret, CommandError::WrongType.into());
+        }
+        // no set was created next to the string
+        assert!(backend.smembers("str").is_empty());
+        assert_eq!(backend.key_type("str"), Some(KeyType::String));
+
+        let ret = command(&["smembers", "s2"])?.execute(&backend);
+        assert!(ret.eq_unordered(&members(&["e", "d", "c"]).into()));
+        let ret = command(&["smembers", "missing"])?.execute(&backend);
+        assert_eq!(ret, members(&[]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_algebra() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sinter", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, members(&["c", "d"]).into());
+        let ret = command(&["sunion", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, members(&["a", "b", "c", "d", "e"]).into());
+        let ret = command(&["sdiff", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, members(&["a", "b"]).into());
+        let ret = command(&["sinter", "s1", "missing"])?.execute(&backend);
+        assert_eq!(ret, members(&[]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sinterstore() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sinterstore", "dest", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        let ret = command(&["sinter", "dest"])?.execute(&backend);
+        assert_eq!(ret, members(&["c", "d"]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sunionstore() -> Result<()> {
+        let backend = setup()?;
+        backend.set("dest".to_string(), BulkString::new("old").into());
+        let ret = command(&["sunionstore", "dest", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(5));
+        assert!(matches!(backend.get("dest"), Err(CommandError::WrongType)));
+        let ret = command(&["sunion", "dest"])?.execute(&backend);
+        assert_eq!(ret, members(&["a", "b", "c", "d", "e"]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sdiffstore() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sdiffstore", "dest", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        let ret = command(&["sdiff", "dest"])?.execute(&backend);
+        assert_eq!(ret, members(&["a", "b"]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sdiff_iter() -> Result<()> {
+        let backend = setup()?;
+        command(&["sadd", "s3", "a"])?.execute(&backend);
+        command(&["sadd", "s3", "z"])?.execute(&backend);
+        backend.set("str".to_string(), BulkString::new("v").into());
+
+        let sorted = |mut items: Vec<RespFrame>| {
+            items.sort();
+            items
+        };
+        // members of the first set that none of the others has
+        let expected = |keys: &[String]| -> Vec<RespFrame> {
+            let Some((first, rest)) = keys.split_first() else {
+                return Vec::new();
+            };
+            let others: HashSet<RespFrame> =
+                rest.iter().flat_map(|key| backend.smembers(key)).collect();
+            let diff = backend.smembers(first).into_iter();
+            sorted(diff.filter(|member| !others.contains(member)).collect())
+        };
+        for keys in [
+            &["s1"][..],
+            &["s1", "s2"],
+            &["s2", "s1"],
+            &["s1", "s2", "s3"],
+            &["s1", "s1"],
+            &["s1", "missing"],
+            &["missing", "s1"],
+            &["s1", "str"],
+            &[],
+        ] {
+            let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+            let lazy: Vec<RespFrame> = backend.sdiff_iter(&keys, |diff| diff.collect());
+            assert_eq!(sorted(lazy), expected(&keys), "{:?}", keys);
+            assert_eq!(
+                backend.sdiff_iter(&keys, |diff| diff.take(1).count()),
+                expected(&keys).len().min(1)
+            );
+        }
+
+        // storing into one of the source keys sees the sets as they were
+        let ret = command(&["sdiffstore", "s1", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        let ret = command(&["sdiff", "s1"])?.execute(&backend);
+        assert_eq!(ret, members(&["a", "b"]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_empty_result_deletes_dest() -> Result<()> {
+        let backend = setup()?;
+        command(&["sadd", "dest", "x"])?.execute(&backend);
+        let ret = command(&["sdiffstore", "dest", "s1", "s1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert!(!backend.exists("dest"));
+
+        assert!(command(&["sinterstore", "dest"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sintercard() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sintercard", "2", "s1", "s2"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        let ret = command(&["sintercard", "1", "s1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(4));
+        let ret = command(&["sintercard", "2", "s1", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sintercard_limit() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sintercard", "2", "s1", "s2", "LIMIT", "1"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = command(&["sintercard", "1", "s1", "limit", "3"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        // 0 means no limit
+        let ret = command(&["sintercard", "2", "s1", "s2", "LIMIT", "0"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+
+        assert!(command(&["sintercard", "0", "s1"]).is_err());
+        assert!(command(&["sintercard", "3", "s1", "s2"]).is_err());
+        assert!(command(&["sintercard", "1", "s1", "LIMIT", "-1"]).is_err());
+        assert!(command(&["sintercard", "1", "s1", "LIMIT"]).is_err());
+
+        Ok(())
+    }
+}
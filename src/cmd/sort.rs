@@ -0,0 +1,161 @@
+use super::{parse_keys, CommandExecutor, Sort};
+use crate::{cmd::CommandError, Backend, RespArray, RespFrame, SimpleError};
+
+impl CommandExecutor for Sort {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut elements = match backend.sort(&self.key, self.alpha, self.desc) {
+            Ok(Some(elements)) => elements,
+            Ok(None) => {
+                return SimpleError::new("ERR One or more scores can't be converted into double")
+                    .into()
+            }
+            Err(e) => return e.into(),
+        };
+        if let Some((offset, count)) = self.limit {
+            // like redis, a negative offset starts at 0 and a negative count takes the rest
+            let len = elements.len();
+            let start = (offset.max(0) as usize).min(len);
+            let end = match usize::try_from(count) {
+                Ok(count) => start.saturating_add(count).min(len),
+                Err(_) => len,
+            };
+            elements.truncate(end);
+            elements.drain(..start);
+        }
+        RespArray::new(elements).into()
+    }
+}
+
+// key [LIMIT offset count] [ASC | DESC] [ALPHA]
+impl TryFrom<RespArray> for Sort {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "sort")?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let mut sort = Sort {
+            key,
+            alpha: false,
+            desc: false,
+            limit: None,
+        };
+        while let Some(opt) = args.next() {
+            match opt.to_ascii_lowercase().as_str() {
+                "asc" => sort.desc = false,
+                "desc" => sort.desc = true,
+                "alpha" => sort.alpha = true,
+                "limit" => {
+                    let mut bound = || {
+                        args.next()
+                            .and_then(|v| v.parse::<i64>().ok())
+                            .ok_or_else(|| {
+                                CommandError::InvalidArgument(
+                                    "value is not an integer or out of range".to_string(),
+                                )
+                            })
+                    };
+                    sort.limit = Some((bound()?, bound()?));
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(sort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString};
+    use anyhow::Result;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    fn array(items: &[&str]) -> RespFrame {
+        RespArray::new(
+            items
+                .iter()
+                .map(|v| BulkString::new(*v).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into()
+    }
+
+    fn setup() -> Result<Backend> {
+        let backend = Backend::new();
+        for v in ["3", "10", "1", "2.5"] {
+            command(&["rpush", "nums", v])?.execute(&backend);
+        }
+        for v in ["pear", "apple", "fig"] {
+            command(&["sadd", "fruit", v])?.execute(&backend);
+        }
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_sort_numeric() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sort", "nums"])?.execute(&backend);
+        assert_eq!(ret, array(&["1", "2.5", "3", "10"]));
+        let ret = command(&["sort", "nums", "DESC"])?.execute(&backend);
+        assert_eq!(ret, array(&["10", "3", "2.5", "1"]));
+        let ret = command(&["sort", "missing"])?.execute(&backend);
+        assert_eq!(ret, array(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_alpha() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sort", "fruit", "alpha"])?.execute(&backend);
+        assert_eq!(ret, array(&["apple", "fig", "pear"]));
+        let ret = command(&["sort", "fruit", "ALPHA", "DESC"])?.execute(&backend);
+        assert_eq!(ret, array(&["pear", "fig", "apple"]));
+        // numbers compare as strings
+        let ret = command(&["sort", "nums", "alpha"])?.execute(&backend);
+        assert_eq!(ret, array(&["1", "10", "2.5", "3"]));
+
+        // without ALPHA the members have to be numbers
+        let ret = command(&["sort", "fruit"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_types() -> Result<()> {
+        let backend = Backend::populated();
+        command(&["zadd", "scores", "1", "b", "2", "a"])?.execute(&backend);
+        let ret = command(&["sort", "scores", "alpha"])?.execute(&backend);
+        assert_eq!(ret, array(&["a", "b"]));
+        for key in ["s", "h"] {
+            let ret = command(&["sort", key])?.execute(&backend);
+            assert_eq!(ret, CommandError::WrongType.into());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_limit() -> Result<()> {
+        let backend = setup()?;
+        let ret = command(&["sort", "nums", "LIMIT", "1", "2"])?.execute(&backend);
+        assert_eq!(ret, array(&["2.5", "3"]));
+        let ret = command(&["sort", "nums", "limit", "2", "10", "desc"])?.execute(&backend);
+        assert_eq!(ret, array(&["2.5", "1"]));
+        let ret = command(&["sort", "nums", "LIMIT", "1", "-1"])?.execute(&backend);
+        assert_eq!(ret, array(&["2.5", "3", "10"]));
+        let ret = command(&["sort", "nums", "LIMIT", "5", "1"])?.execute(&backend);
+        assert_eq!(ret, array(&[]));
+
+        assert!(command(&["sort", "nums", "LIMIT", "1"]).is_err());
+        assert!(command(&["sort", "nums", "LIMIT", "a", "1"]).is_err());
+        assert!(command(&["sort", "nums", "BY"]).is_err());
+        assert!(command(&["sort"]).is_err());
+
+        Ok(())
+    }
+}
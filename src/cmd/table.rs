@@ -0,0 +1,295 @@
+use super::CommandError;
+
+// arity follows redis: positive is exact (command name included), negative is a minimum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+}
+
+const fn spec(
+    name: &'static str,
+    arity: i64,
+    flags: &'static [&'static str],
+    first_key: i64,
+    last_key: i64,
+    step: i64,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        first_key,
+        last_key,
+        step,
+    }
+}
+
+const READ: &[&str] = &["readonly", "fast"];
+const READ_SLOW: &[&str] = &["readonly"];
+const WRITE: &[&str] = &["write", "denyoom"];
+const WRITE_FAST: &[&str] = &["write", "fast"];
+const WRITE_MOVABLE: &[&str] = &["write", "denyoom", "movablekeys"];
+const BLOCKING: &[&str] = &["write", "blocking"];
+const PUBSUB: &[&str] = &["pubsub", "noscript", "loading", "stale"];
+const PUBLISH: &[&str] = &["pubsub", "loading", "stale", "fast"];
+const SERVER: &[&str] = &["loading", "stale"];
+const CONNECTION: &[&str] = &["noscript", "loading", "stale", "fast"];
+
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    spec("get", 2, READ, 1, 1, 1),
+    spec("set", -3, WRITE, 1, 1, 1),
+    spec("mset", -3, WRITE, 1, -1, 2),
+    spec("bitpos", -3, READ_SLOW, 1, 1, 1),
+    spec("append", 3, WRITE, 1, 1, 1),
+    spec("setrange", 4, WRITE, 1, 1, 1),
+    spec("setbit", 4, WRITE, 1, 1, 1),
+    spec("incr", 2, &["write", "denyoom", "fast"], 1, 1, 1),
+    spec("decr", 2, &["write", "denyoom", "fast"], 1, 1, 1),
+    spec("strlen", 2, READ, 1, 1, 1),
+    spec("hget", 3, READ, 1, 1, 1),
+    spec("hset", 4, WRITE, 1, 1, 1),
+    spec("hgetall", 2, READ_SLOW, 1, 1, 1),
+    spec("hkeys", 2, READ_SLOW, 1, 1, 1),
+    spec("hvals", 2, READ_SLOW, 1, 1, 1),
+    spec("hmget", -3, READ, 1, 1, 1),
+    spec("hrandfield", -2, READ_SLOW, 1, 1, 1),
+    spec("hscan", -3, READ_SLOW, 1, 1, 1),
+    spec("echo", 2, &["fast"], 0, 0, 0),
+    spec("sadd", 3, WRITE, 1, 1, 1),
+    spec("sismember", 3, READ, 1, 1, 1),
+    spec("smembers", 2, READ_SLOW, 1, 1, 1),
+    spec("sinter", -2, READ_SLOW, 1, -1, 1),
+    spec("sunion", -2, READ_SLOW, 1, -1, 1),
+    spec("sdiff", -2, READ_SLOW, 1, -1, 1),
+    spec("sinterstore", -3, WRITE, 1, -1, 1),
+    spec("sunionstore", -3, WRITE, 1, -1, 1),
+    spec("sdiffstore", -3, WRITE, 1, -1, 1),
+    spec("sintercard", -3, &["readonly", "movablekeys"], 0, 0, 0),
+    spec("sort", -2, &["write", "denyoom", "movablekeys"], 1, 1, 1),
+    spec("lpush", -3, &["write", "denyoom", "fast"], 1, 1, 1),
+    spec("rpush", -3, &["write", "denyoom", "fast"], 1, 1, 1),
+    spec("lpop", 2, WRITE_FAST, 1, 1, 1),
+    spec("rpop", 2, WRITE_FAST, 1, 1, 1),
+    spec("blpop", -3, BLOCKING, 1, -2, 1),
+    spec("brpop", -3, BLOCKING, 1, -2, 1),
+    spec("lpos", -3, READ_SLOW, 1, 1, 1),
+    spec("linsert", 5, WRITE, 1, 1, 1),
+    spec("zadd", -4, &["write", "denyoom", "fast"], 1, 1, 1),
+    spec("zmscore", -3, READ, 1, 1, 1),
+    spec("zpopmin", -2, WRITE_FAST, 1, 1, 1),
+    spec("zpopmax", -2, WRITE_FAST, 1, 1, 1),
+    spec("zcount", 4, READ, 1, 1, 1),
+    spec("zrange", -4, READ_SLOW, 1, 1, 1),
+    spec("zunionstore", -4, WRITE_MOVABLE, 1, 1, 1),
+    spec("zinterstore", -4, WRITE_MOVABLE, 1, 1, 1),
+    spec("zdiff", -3, READ_SLOW, 0, 0, 0),
+    spec("zdiffstore", -4, WRITE, 1, 1, 1),
+    spec("expire", -3, WRITE_FAST, 1, 1, 1),
+    spec("pexpire", -3, WRITE_FAST, 1, 1, 1),
+    spec("expireat", 3, WRITE_FAST, 1, 1, 1),
+    spec("pexpireat", 3, WRITE_FAST, 1, 1, 1),
+    spec("ttl", 2, READ, 1, 1, 1),
+    spec("pttl", 2, READ, 1, 1, 1),
+    spec("expiretime", 2, READ, 1, 1, 1),
+    spec("pexpiretime", 2, READ, 1, 1, 1),
+    spec("del", -2, &["write"], 1, -1, 1),
+    spec("unlink", -2, WRITE_FAST, 1, -1, 1),
+    spec("move", 3, WRITE_FAST, 1, 1, 1),
+    spec("rename", 3, WRITE, 1, 2, 1),
+    spec("copy", -3, WRITE, 1, 2, 1),
+    spec("type", 2, READ, 1, 1, 1),
+    spec("dump", 2, READ_SLOW, 1, 1, 1),
+    spec("restore", -4, WRITE, 1, 1, 1),
+    spec("scan", -2, READ_SLOW, 0, 0, 0),
+    spec("randomkey", 1, READ, 0, 0, 0),
+    spec("object", -2, READ_SLOW, 2, 2, 1),
+    spec("lolwut", -1, READ, 0, 0, 0),
+    spec("time", 1, &["random", "loading", "stale", "fast"], 0, 0, 0),
+    spec("info", -1, SERVER, 0, 0, 0),
+    spec("healthcheck", 1, CONNECTION, 0, 0, 0),
+    spec("cluster", -2, SERVER, 0, 0, 0),
+    spec("subscribe", -2, PUBSUB, 0, 0, 0),
+    spec("unsubscribe", -1, PUBSUB, 0, 0, 0),
+    spec("psubscribe", -2, PUBSUB, 0, 0, 0),
+    spec("punsubscribe", -1, PUBSUB, 0, 0, 0),
+    spec("publish", 3, PUBLISH, 0, 0, 0),
+    spec("pubsub", -2, PUBSUB, 0, 0, 0),
+    spec("command", -1, SERVER, 0, 0, 0),
+    spec(
+        "debug",
+        -2,
+        &["admin", "noscript", "loading", "stale"],
+        0,
+        0,
+        0,
+    ),
+    spec("reset", 1, CONNECTION, 0, 0, 0),
+    spec("select", 2, CONNECTION, 0, 0, 0),
+    spec("swapdb", 3, WRITE_FAST, 0, 0, 0),
+    spec("auth", -2, CONNECTION, 0, 0, 0),
+    spec("hello", -1, CONNECTION, 0, 0, 0),
+    spec("client", -2, CONNECTION, 0, 0, 0),
+];
+
+// what COMMAND DOCS reports, for the commands that have docs so far. the
+// arguments are the ones this server accepts, which may be fewer than redis'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandDoc {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub since: &'static str,
+    pub group: &'static str,
+    pub arguments: &'static [ArgDoc],
+}
+
+// `kind` is one of redis' argument types: key, string, integer, double, pattern...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgDoc {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub flags: &'static [&'static str],
+}
+
+const fn arg(name: &'static str, kind: &'static str) -> ArgDoc {
+    ArgDoc {
+        name,
+        kind,
+        flags: &[],
+    }
+}
+
+pub static COMMAND_DOCS: &[CommandDoc] = &[
+    CommandDoc {
+        name: "get",
+        summary: "Returns the string value of a key.",
+        since: "1.0.0",
+        group: "string",
+        arguments: &[arg("key", "key")],
+    },
+    CommandDoc {
+        name: "set",
+        summary: "Sets the string value of a key, ignoring its type.",
+        since: "1.0.0",
+        group: "string",
+        arguments: &[
+            arg("key", "key"),
+            arg("value", "string"),
+            ArgDoc {
+                name: "condition",
+                kind: "oneof",
+                flags: &["optional"],
+            },
+            ArgDoc {
+                name: "get",
+                kind: "pure-token",
+                flags: &["optional"],
+            },
+        ],
+    },
+    CommandDoc {
+        name: "hset",
+        summary: "Creates or modifies the value of a field in a hash.",
+        since: "2.0.0",
+        group: "hash",
+        arguments: &[
+            arg("key", "key"),
+            arg("field", "string"),
+            arg("value", "string"),
+        ],
+    },
+    CommandDoc {
+        name: "sadd",
+        summary: "Adds a member to a set. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "set",
+        arguments: &[arg("key", "key"), arg("member", "string")],
+    },
+];
+
+impl CommandSpec {
+    // every request is checked against its command's arity before it's parsed,
+    // see TryFrom<RespArray> for Command
+    pub fn check_arity(&self, argc: usize) -> Result<(), CommandError> {
+        if !self.accepts(argc) {
+            return Err(CommandError::WrongArity(self.name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn accepts(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+        if self.arity >= 0 {
+            argc == self.arity
+        } else {
+            argc >= -self.arity
+        }
+    }
+
+    // the key arguments of a full command line (name included), as COMMAND GETKEYS reports them
+    pub fn keys<'a>(&self, args: &'a [String]) -> Result<Vec<&'a str>, CommandError> {
+        if !self.accepts(args.len()) {
+            return Err(CommandError::InvalidArgument(
+                "Invalid number of arguments specified for command".to_string(),
+            ));
+        }
+        if let Some(keys) = self.movable_keys(args) {
+            return keys;
+        }
+        if self.first_key <= 0 {
+            return Err(CommandError::InvalidArgument(
+                "The command has no key arguments".to_string(),
+            ));
+        }
+        // a negative last key counts back from the end of the arguments
+        let last = if self.last_key < 0 {
+            args.len() as i64 + self.last_key
+        } else {
+            self.last_key
+        };
+        let keys = (self.first_key..=last)
+            .step_by(self.step.max(1) as usize)
+            .filter_map(|i| args.get(i as usize).map(String::as_str))
+            .collect();
+        Ok(keys)
+    }
+
+    // keys that first_key, last_key and step can't describe, None for the commands
+    // they do describe
+    fn movable_keys<'a>(&self, args: &'a [String]) -> Option<Result<Vec<&'a str>, CommandError>> {
+        match self.name {
+            // destination numkeys key [key ...] ...
+            "zunionstore" | "zinterstore" => Some(numkeys_keys(args, 2)),
+            _ => None,
+        }
+    }
+}
+
+// the keys counted by the numkeys argument at `at`, after whatever keys come before it
+fn numkeys_keys(args: &[String], at: usize) -> Result<Vec<&str>, CommandError> {
+    let invalid =
+        || CommandError::InvalidArgument("Invalid arguments specified for command".to_string());
+    let numkeys: usize = args
+        .get(at)
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(invalid)?;
+    let end = numkeys.checked_add(at + 1).ok_or_else(invalid)?;
+    let keys = args.get(at + 1..end).ok_or_else(invalid)?;
+    Ok(args[1..at].iter().chain(keys).map(String::as_str).collect())
+}
+
+pub fn lookup_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+pub fn lookup_docs(name: &str) -> Option<&'static CommandDoc> {
+    COMMAND_DOCS
+        .iter()
+        .find(|doc| doc.name.eq_ignore_ascii_case(name))
+}
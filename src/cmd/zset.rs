@@ -0,0 +1,834 @@
+use super::{
+    parse_keys, CommandExecutor, ZAdd, ZCount, ZDiff, ZDiffStore, ZInterStore, ZMScore, ZPopMax,
+    ZPopMin, ZRange, ZUnionStore,
+};
+use crate::{
+    cmd::CommandError, Aggregate, Backend, KeyType, Nf64, RespArray, RespArrayBuilder, RespFrame,
+    RespNull, ZRangeBy,
+};
+use std::ops::Bound;
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        RespFrame::Integer(backend.zadd(self.key, self.members) as i64)
+    }
+}
+
+impl CommandExecutor for ZMScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        let scores: Vec<RespFrame> = backend
+            .zmscore(&self.key, &self.members)
+            .into_iter()
+            .map(|score| match score {
+                Some(score) => Nf64::new(score).into(),
+                None => RespFrame::Null(RespNull),
+            })
+            .collect();
+        RespArray::new(scores).into()
+    }
+}
+
+impl CommandExecutor for ZPopMin {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        popped_frame(backend.zpopmin(&self.key, self.count))
+    }
+}
+
+impl CommandExecutor for ZPopMax {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        popped_frame(backend.zpopmax(&self.key, self.count))
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        RespFrame::Integer(backend.zcount(&self.key, self.min, self.max) as i64)
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = backend.check_type(&self.key, KeyType::ZSet) {
+            return e.into();
+        }
+        let members = backend.zrange(&self.key, &self.by, self.rev, self.limit);
+        members_frame(members, self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZDiff {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_zsets(backend, &self.keys) {
+            return e.into();
+        }
+        members_frame(backend.zdiff(&self.keys), self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZDiffStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_zsets(backend, &self.keys) {
+            return e.into();
+        }
+        RespFrame::Integer(backend.zdiffstore(self.dest, &self.keys) as i64)
+    }
+}
+
+// just the members, or [member, score, ...] with scores
+fn members_frame(members: Vec<(String, f64)>, with_scores: bool) -> RespFrame {
+    if with_scores {
+        return popped_frame(members);
+    }
+    let mut builder = RespArrayBuilder::with_capacity(members.len());
+    for (member, _) in members {
+        builder.push_bulk(member);
+    }
+    builder.build().into()
+}
+
+// [member, score, member, score, ...]
+fn popped_frame(popped: Vec<(String, f64)>) -> RespFrame {
+    let mut builder = RespArrayBuilder::with_capacity(popped.len() * 2);
+    for (member, score) in popped {
+        builder.push_bulk(member).push_frame(Nf64::new(score));
+    }
+    builder.build().into()
+}
+
+impl CommandExecutor for ZUnionStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_zsets(backend, &self.keys) {
+            return e.into();
+        }
+        let len = backend.zunionstore(self.dest, &self.keys, &self.weights, self.aggregate);
+        RespFrame::Integer(len as i64)
+    }
+}
+
+impl CommandExecutor for ZInterStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_zsets(backend, &self.keys) {
+            return e.into();
+        }
+        let len = backend.zinterstore(self.dest, &self.keys, &self.weights, self.aggregate);
+        RespFrame::Integer(len as i64)
+    }
+}
+
+fn check_zsets(backend: &Backend, keys: &[String]) -> Result<(), CommandError> {
+    keys.iter()
+        .try_for_each(|key| backend.check_type(key, KeyType::ZSet))
+}
+
+// key score member [score member ...]
+impl TryFrom<RespArray> for ZAdd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "zadd")?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let args: Vec<String> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let members = args
+            .chunks(2)
+            .map(|pair| match pair[0].parse::<f64>() {
+                Ok(score) if !score.is_nan() => Ok((score, pair[1].clone())),
+                _ => Err(CommandError::InvalidArgument(
+                    "value is not a valid float".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ZAdd { key, members })
+    }
+}
+
+// key member [member ...]
+impl TryFrom<RespArray> for ZMScore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "zmscore")?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let members: Vec<String> = args.collect();
+        if members.is_empty() {
+            return Err(CommandError::InvalidArgument(
+                "zmscore command must have at least 2 arguments".to_string(),
+            ));
+        }
+        Ok(ZMScore { key, members })
+    }
+}
+
+// key [count]
+fn parse_pop(value: RespArray, name: &'static str) -> Result<(String, usize), CommandError> {
+    if value.len() > 3 {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+    let mut args = parse_keys(value, name)?.into_iter();
+    let key = args.next().unwrap_or_default();
+    let count = match args.next() {
+        Some(count) => {
+            let count: i64 = count.parse().map_err(|_| {
+                CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+            })?;
+            usize::try_from(count).map_err(|_| {
+                CommandError::InvalidArgument("value is out of range, must be positive".to_string())
+            })?
+        }
+        None => 1,
+    };
+    Ok((key, count))
+}
+
+impl TryFrom<RespArray> for ZPopMin {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = parse_pop(value, "zpopmin")?;
+        Ok(ZPopMin { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for ZPopMax {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = parse_pop(value, "zpopmax")?;
+        Ok(ZPopMax { key, count })
+    }
+}
+
+// key min max
+impl TryFrom<RespArray> for ZCount {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 4 {
+            return Err(CommandError::InvalidArgument(
+                "zcount command must have exactly 3 arguments".to_string(),
+            ));
+        }
+        let mut args = parse_keys(value, "zcount")?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let min = parse_score_bound(&args.next().unwrap_or_default())?;
+        let max = parse_score_bound(&args.next().unwrap_or_default())?;
+        Ok(ZCount { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZRange {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let syntax_error = |msg: &str| CommandError::InvalidArgument(msg.to_string());
+        if value.len() < 4 {
+            return Err(syntax_error(
+                "zrange command must have at least 3 arguments",
+            ));
+        }
+        let mut args = parse_keys(value, "zrange")?.into_iter();
+        let key = args.next().unwrap_or_default();
+        let start = args.next().unwrap_or_default();
+        let stop = args.next().unwrap_or_default();
+
+        let (mut by_score, mut by_lex, mut rev, mut with_scores) = (false, false, false, false);
+        let mut limit = None;
+        while let Some(opt) = args.next() {
+            match opt.to_ascii_lowercase().as_str() {
+                "byscore" => by_score = true,
+                "bylex" => by_lex = true,
+                "rev" => rev = true,
+                "withscores" => with_scores = true,
+                "limit" => {
+                    let (Some(offset), Some(count)) = (args.next(), args.next()) else {
+                        return Err(syntax_error("syntax error"));
+                    };
+                    let parse = |n: &str| {
+                        n.parse::<i64>()
+                            .map_err(|_| syntax_error("value is not an integer or out of range"))
+                    };
+                    let (offset, count) = (parse(&offset)?, parse(&count)?);
+                    // a negative offset selects nothing, a negative count everything
+                    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+                    let count = usize::try_from(count).unwrap_or(usize::MAX);
+                    limit = Some((offset, count));
+                }
+                _ => return Err(syntax_error("syntax error")),
+            }
+        }
+        if by_score && by_lex {
+            return Err(syntax_error("syntax error"));
+        }
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(syntax_error(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+            ));
+        }
+        if with_scores && by_lex {
+            return Err(syntax_error(
+                "syntax error, WITHSCORES not supported in combination with BYLEX",
+            ));
+        }
+
+        // with REV the score and lex ranges are given from high to low
+        let (low, high) = if rev && (by_score || by_lex) {
+            (stop, start)
+        } else {
+            (start, stop)
+        };
+        let by = if by_score {
+            ZRangeBy::Score(parse_score_bound(&low)?, parse_score_bound(&high)?)
+        } else if by_lex {
+            parse_lex_range(&low, &high)?
+        } else {
+            let parse = |n: &str| {
+                n.parse::<i64>()
+                    .map_err(|_| syntax_error("value is not an integer or out of range"))
+            };
+            ZRangeBy::Index(parse(&low)?, parse(&high)?)
+        };
+        Ok(ZRange {
+            key,
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+}
+
+// `[a` is inclusive, `(a` exclusive, `-` and `+` are the lowest and highest possible
+fn parse_lex_range(min: &str, max: &str) -> Result<ZRangeBy, CommandError> {
+    let parse = |arg: &str| match arg.split_at_checked(1) {
+        Some(("[", member)) => Ok(Bound::Included(member.to_string())),
+        Some(("(", member)) => Ok(Bound::Excluded(member.to_string())),
+        _ if arg == "-" || arg == "+" => Ok(Bound::Unbounded),
+        _ => Err(CommandError::InvalidArgument(
+            "min or max not valid string range item".to_string(),
+        )),
+    };
+    let (low, high) = (parse(min)?, parse(max)?);
+    // `+` as the low end or `-` as the high end leave nothing in between
+    if min == "+" || max == "-" {
+        let nothing = Bound::Excluded(String::new());
+        return Ok(ZRangeBy::Lex(nothing.clone(), nothing));
+    }
+    Ok(ZRangeBy::Lex(low, high))
+}
+
+impl TryFrom<RespArray> for ZUnionStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys, weights, aggregate) = parse_zstore(value, "zunionstore")?;
+        Ok(ZUnionStore {
+            dest,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZInterStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (dest, keys, weights, aggregate) = parse_zstore(value, "zinterstore")?;
+        Ok(ZInterStore {
+            dest,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+// numkeys key [key ...]
+fn take_numkeys(
+    args: &mut impl Iterator<Item = String>,
+    name: &'static str,
+) -> Result<Vec<String>, CommandError> {
+    let numkeys = args
+        .next()
+        .and_then(|n| n.parse::<i64>().ok())
+        .ok_or_else(|| {
+            CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+        })?;
+    if numkeys <= 0 {
+        return Err(CommandError::InvalidArgument(format!(
+            "at least 1 input key is needed for '{}' command",
+            name
+        )));
+    }
+    let keys: Vec<String> = args.take(numkeys as usize).collect();
+    if keys.len() < numkeys as usize {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+    Ok(keys)
+}
+
+impl TryFrom<RespArray> for ZDiff {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "zdiff")?.into_iter();
+        let keys = take_numkeys(&mut args, "zdiff")?;
+        let with_scores = match args.next() {
+            None => false,
+            Some(opt) if opt.eq_ignore_ascii_case("withscores") => true,
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(ZDiff { keys, with_scores })
+    }
+}
+
+impl TryFrom<RespArray> for ZDiffStore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = parse_keys(value, "zdiffstore")?.into_iter();
+        let dest = args.next().unwrap_or_default();
+        let keys = take_numkeys(&mut args, "zdiffstore")?;
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(ZDiffStore { dest, keys })
+    }
+}
+
+// destination numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX]
+fn parse_zstore(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<String>, Vec<f64>, Aggregate), CommandError> {
+    let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+    let mut args = parse_keys(value, name)?.into_iter();
+    let dest = args.next().unwrap_or_default();
+    let keys = take_numkeys(&mut args, name)?;
+
+    let mut weights = Vec::new();
+    let mut aggregate = Aggregate::default();
+    while let Some(opt) = args.next() {
+        match opt.to_ascii_lowercase().as_str() {
+            "weights" => {
+                weights = args
+                    .by_ref()
+                    .take(keys.len())
+                    .map(|w| match w.parse::<f64>() {
+                        Ok(w) if !w.is_nan() => Ok(w),
+                        _ => Err(CommandError::InvalidArgument(
+                            "weight value is not a float".to_string(),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if weights.len() < keys.len() {
+                    return Err(syntax_error());
+                }
+            }
+            "aggregate" => {
+                aggregate = match args.next().map(|a| a.to_ascii_lowercase()).as_deref() {
+                    Some("sum") => Aggregate::Sum,
+                    Some("min") => Aggregate::Min,
+                    Some("max") => Aggregate::Max,
+                    _ => return Err(syntax_error()),
+                }
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((dest, keys, weights, aggregate))
+}
+
+// `1.5` is inclusive, `(1.5` exclusive, and `-inf`/`+inf` cover everything
+fn parse_score_bound(arg: &str) -> Result<Bound<f64>, CommandError> {
+    let (score, exclusive) = match arg.strip_prefix('(') {
+        Some(score) => (score, true),
+        None => (arg, false),
+    };
+    match score.parse::<f64>() {
+        Ok(score) if !score.is_nan() && exclusive => Ok(Bound::Excluded(score)),
+        Ok(score) if !score.is_nan() => Ok(Bound::Included(score)),
+        _ => Err(CommandError::InvalidArgument(
+            "min or max is not a float".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString};
+    use anyhow::Result;
+
+    fn command(args: &[&str]) -> Result<Command> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        Ok(RespArray::new(frames).try_into()?)
+    }
+
+    #[test]
+    fn test_zadd() -> Result<()> {
+        let backend = Backend::new();
+        let ret = command(&["zadd", "z", "1", "a", "2.5", "b"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        // updating a score doesn't count as an addition
+        let ret = command(&["zadd", "z", "3", "a", "4", "c"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert_eq!(backend.zscore("z", "a"), Some(3.0));
+
+        assert!(command(&["zadd", "z", "1"]).is_err());
+        assert!(command(&["zadd", "z", "x", "a"]).is_err());
+        assert!(command(&["zadd", "z", "nan", "a"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zmscore() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "z", "1", "a", "2.5", "b"])?.execute(&backend);
+
+        let ret = command(&["zmscore", "z", "b", "missing", "a"])?.execute(&backend);
+        let expected = RespArray::new(vec![
+            Nf64::new(2.5).into(),
+            RespFrame::Null(RespNull),
+            Nf64::new(1.0).into(),
+        ]);
+        assert_eq!(ret, expected.into());
+
+        let ret = command(&["zmscore", "missing", "a", "b"])?.execute(&backend);
+        let expected = RespArray::new(vec![RespFrame::Null(RespNull), RespFrame::Null(RespNull)]);
+        assert_eq!(ret, expected.into());
+
+        assert!(command(&["zmscore", "z"]).is_err());
+
+        backend.set("s".to_string(), BulkString::new("v").into());
+        let ret = command(&["zmscore", "s", "a"])?.execute(&backend);
+        assert_eq!(ret, CommandError::WrongType.into());
+
+        Ok(())
+    }
+
+    fn popped(pairs: &[(&str, f64)]) -> RespFrame {
+        let mut builder = RespArrayBuilder::new();
+        for (member, score) in pairs {
+            builder.push_bulk(*member).push_frame(Nf64::new(*score));
+        }
+        builder.build().into()
+    }
+
+    #[test]
+    fn test_zpopmin() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "z", "3", "c", "1", "a", "2", "b"])?.execute(&backend);
+
+        let ret = command(&["zpopmin", "z"])?.execute(&backend);
+        assert_eq!(ret, popped(&[("a", 1.0)]));
+        let ret = command(&["zpopmin", "z", "5"])?.execute(&backend);
+        assert_eq!(ret, popped(&[("b", 2.0), ("c", 3.0)]));
+        // the emptied set is gone
+        assert!(!backend.exists("z"));
+        let ret = command(&["zpopmin", "z"])?.execute(&backend);
+        assert_eq!(ret, popped(&[]));
+
+        assert!(command(&["zpopmin", "z", "-1"]).is_err());
+        assert!(command(&["zpopmin", "z", "1", "2"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zpopmax() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "z", "3", "c", "1", "a", "2", "b"])?.execute(&backend);
+
+        let ret = command(&["zpopmax", "z", "2"])?.execute(&backend);
+        assert_eq!(ret, popped(&[("c", 3.0), ("b", 2.0)]));
+        assert_eq!(
+            backend.zmscore("z", &["a".into(), "c".into()]),
+            vec![Some(1.0), None]
+        );
+        let ret = command(&["zpopmax", "missing"])?.execute(&backend);
+        assert_eq!(ret, popped(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zcount() -> Result<()> {
+        let backend = Backend::new();
+        command(&[
+            "zadd", "z", "1", "a", "2", "b", "2", "c", "3", "d", "5", "e",
+        ])?
+        .execute(&backend);
+
+        let zcount = |min: &str, max: &str| -> Result<RespFrame> {
+            Ok(command(&["zcount", "z", min, max])?.execute(&backend))
+        };
+        assert_eq!(zcount("2", "3")?, RespFrame::Integer(3));
+        assert_eq!(zcount("-inf", "+inf")?, RespFrame::Integer(5));
+        assert_eq!(zcount("1", "1")?, RespFrame::Integer(1));
+        // both ends can be exclusive, ties on the bound are left out
+        assert_eq!(zcount("(2", "5")?, RespFrame::Integer(2));
+        assert_eq!(zcount("2", "(5")?, RespFrame::Integer(3));
+        assert_eq!(zcount("(1", "(5")?, RespFrame::Integer(3));
+        assert_eq!(zcount("-inf", "(2")?, RespFrame::Integer(1));
+
+        // empty ranges
+        assert_eq!(zcount("4", "4.5")?, RespFrame::Integer(0));
+        assert_eq!(zcount("3", "2")?, RespFrame::Integer(0));
+        assert_eq!(zcount("(2", "(2")?, RespFrame::Integer(0));
+        assert_eq!(zcount("(5", "+inf")?, RespFrame::Integer(0));
+        let ret = command(&["zcount", "missing", "-inf", "+inf"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+
+        assert!(command(&["zcount", "z", "x", "1"]).is_err());
+        assert!(command(&["zcount", "z", "1"]).is_err());
+        backend.set("s".to_string(), BulkString::new("v").into());
+        let ret = command(&["zcount", "s", "0", "1"])?.execute(&backend);
+        assert_eq!(ret, CommandError::WrongType.into());
+
+        Ok(())
+    }
+
+    fn zrange(backend: &Backend, args: &[&str]) -> Result<Vec<String>> {
+        let args: Vec<&str> = ["zrange", "z"].iter().chain(args).copied().collect();
+        let RespFrame::Array(ret) = command(&args)?.execute(backend) else {
+            panic!("zrange must reply with an array");
+        };
+        Ok(ret
+            .iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(member) => String::from_utf8_lossy(member).into_owned(),
+                RespFrame::Double(score) => (**score).to_string(),
+                frame => panic!("unexpected frame {:?}", frame),
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_zrange_by_index() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "z", "1", "a", "2", "b", "3", "c", "4", "d"])?.execute(&backend);
+
+        assert_eq!(zrange(&backend, &["0", "-1"])?, ["a", "b", "c", "d"]);
+        assert_eq!(zrange(&backend, &["1", "2"])?, ["b", "c"]);
+        assert_eq!(zrange(&backend, &["0", "1", "REV"])?, ["d", "c"]);
+        assert_eq!(
+            zrange(&backend, &["-2", "-1", "WITHSCORES"])?,
+            ["c", "3", "d", "4"]
+        );
+        assert!(zrange(&backend, &["3", "1"])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_byscore_rev_limit() -> Result<()> {
+        let backend = Backend::new();
+        command(&[
+            "zadd", "z", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+        ])?
+        .execute(&backend);
+
+        assert_eq!(zrange(&backend, &["2", "(4", "BYSCORE"])?, ["b", "c"]);
+        // with REV the range is given from max to min
+        assert_eq!(
+            zrange(&backend, &["+inf", "2", "BYSCORE", "REV"])?,
+            ["e", "d", "c", "b"]
+        );
+        assert_eq!(
+            zrange(
+                &backend,
+                &[
+                    "+inf",
+                    "2",
+                    "BYSCORE",
+                    "REV",
+                    "LIMIT",
+                    "1",
+                    "2",
+                    "WITHSCORES"
+                ]
+            )?,
+            ["d", "4", "c", "3"]
+        );
+        assert_eq!(
+            zrange(&backend, &["-inf", "+inf", "BYSCORE", "LIMIT", "3", "-1"])?,
+            ["d", "e"]
+        );
+        assert!(zrange(&backend, &["2", "+inf", "BYSCORE", "REV"])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_bylex() -> Result<()> {
+        let backend = Backend::new();
+        command(&[
+            "zadd", "z", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+        ])?
+        .execute(&backend);
+
+        assert_eq!(
+            zrange(&backend, &["-", "+", "BYLEX"])?,
+            ["a", "b", "c", "d", "e"]
+        );
+        assert_eq!(zrange(&backend, &["[b", "(d", "BYLEX"])?, ["b", "c"]);
+        assert_eq!(
+            zrange(&backend, &["(b", "+", "BYLEX", "LIMIT", "0", "2"])?,
+            ["c", "d"]
+        );
+        assert_eq!(
+            zrange(&backend, &["[c", "-", "BYLEX", "REV"])?,
+            ["c", "b", "a"]
+        );
+        assert!(zrange(&backend, &["+", "-", "BYLEX"])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_invalid() {
+        assert!(command(&["zrange", "z", "0", "1", "LIMIT", "0", "1"]).is_err());
+        assert!(command(&["zrange", "z", "0", "1", "BYSCORE", "BYLEX"]).is_err());
+        assert!(command(&["zrange", "z", "[a", "[b", "BYLEX", "WITHSCORES"]).is_err());
+        assert!(command(&["zrange", "z", "a", "b", "BYLEX"]).is_err());
+        assert!(command(&["zrange", "z", "x", "1", "BYSCORE"]).is_err());
+        assert!(command(&["zrange", "z", "0", "1", "BYSCORE", "LIMIT", "0"]).is_err());
+        assert!(command(&["zrange", "z", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_zunionstore_weights() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "a", "1", "x", "2", "y"])?.execute(&backend);
+        command(&["zadd", "b", "10", "y", "20", "z"])?.execute(&backend);
+        command(&["zadd", "dest", "5", "old"])?.execute(&backend);
+
+        let ret = command(&[
+            "zunionstore",
+            "dest",
+            "3",
+            "a",
+            "b",
+            "missing",
+            "WEIGHTS",
+            "2",
+            "0.5",
+            "7",
+        ])?
+        .execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        assert_eq!(backend.zscore("dest", "x"), Some(2.0));
+        assert_eq!(backend.zscore("dest", "y"), Some(9.0));
+        assert_eq!(backend.zscore("dest", "z"), Some(10.0));
+        assert_eq!(backend.zscore("dest", "old"), None);
+
+        let ret =
+            command(&["zunionstore", "dest", "2", "a", "b", "aggregate", "max"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(3));
+        assert_eq!(backend.zscore("dest", "y"), Some(10.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zinterstore_aggregate_min() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "a", "1", "x", "2", "y", "3", "z"])?.execute(&backend);
+        command(&["zadd", "b", "10", "y", "0.5", "z"])?.execute(&backend);
+
+        let ret =
+            command(&["zinterstore", "dest", "2", "a", "b", "AGGREGATE", "MIN"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        assert_eq!(backend.zscore("dest", "x"), None);
+        assert_eq!(backend.zscore("dest", "y"), Some(2.0));
+        assert_eq!(backend.zscore("dest", "z"), Some(0.5));
+
+        // the sum is the default, and a missing key empties the result
+        command(&["zinterstore", "dest", "2", "a", "b"])?.execute(&backend);
+        assert_eq!(backend.zscore("dest", "y"), Some(12.0));
+        let ret = command(&["zinterstore", "dest", "2", "a", "missing"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert!(!backend.exists("dest"));
+
+        command(&["set", "s", "v"])?.execute(&backend);
+        let ret = command(&["zinterstore", "dest", "2", "a", "s"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::from(CommandError::WrongType));
+
+        assert!(command(&["zinterstore", "dest", "0", "a"]).is_err());
+        assert!(command(&["zinterstore", "dest", "3", "a", "b"]).is_err());
+        assert!(command(&["zinterstore", "dest", "2", "a", "b", "weights", "1"]).is_err());
+        assert!(command(&["zinterstore", "dest", "1", "a", "weights", "x"]).is_err());
+        assert!(command(&["zinterstore", "dest", "1", "a", "aggregate", "avg"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zdiff_withscores() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "a", "1", "x", "2", "y", "3", "z"])?.execute(&backend);
+        command(&["zadd", "b", "10", "y"])?.execute(&backend);
+
+        let ret = command(&["zdiff", "3", "a", "b", "missing", "WITHSCORES"])?.execute(&backend);
+        let expected = RespArray::new(vec![
+            BulkString::new("x").into(),
+            Nf64::new(1.0).into(),
+            BulkString::new("z").into(),
+            Nf64::new(3.0).into(),
+        ]);
+        assert_eq!(ret, expected.into());
+
+        let ret = command(&["zdiff", "1", "a"])?.execute(&backend);
+        let expected = RespArray::new(vec![
+            BulkString::new("x").into(),
+            BulkString::new("y").into(),
+            BulkString::new("z").into(),
+        ]);
+        assert_eq!(ret, expected.into());
+        let ret = command(&["zdiff", "2", "missing", "a"])?.execute(&backend);
+        assert_eq!(ret, RespArray::new(vec![]).into());
+
+        assert!(command(&["zdiff", "2", "a"]).is_err());
+        assert!(command(&["zdiff", "1", "a", "scores"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zdiffstore() -> Result<()> {
+        let backend = Backend::new();
+        command(&["zadd", "a", "1", "x", "2", "y", "3", "z"])?.execute(&backend);
+        command(&["zadd", "b", "10", "y"])?.execute(&backend);
+        command(&["zadd", "c", "0", "z"])?.execute(&backend);
+
+        let ret = command(&["zdiffstore", "dest", "2", "a", "b"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(2));
+        // the members keep the first set's scores
+        assert_eq!(backend.zscore("dest", "x"), Some(1.0));
+        assert_eq!(backend.zscore("dest", "y"), None);
+        assert_eq!(backend.zscore("dest", "z"), Some(3.0));
+
+        let ret = command(&["zdiffstore", "dest", "3", "a", "b", "c"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        assert_eq!(backend.zscore("dest", "z"), None);
+
+        let ret = command(&["zdiffstore", "dest", "2", "a", "a"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(0));
+        assert!(!backend.exists("dest"));
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ mod backend;
 mod resp;
 
 pub mod cmd;
+pub mod logging;
 pub mod network;
 
 pub use backend::*;
@@ -0,0 +1,48 @@
+use tracing_subscriber::EnvFilter;
+
+// how the server writes its logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // human readable lines, the default
+    Pretty,
+    // one json object per line, for log ingestion
+    Json,
+}
+
+impl LogFormat {
+    // REDIS_LOG_JSON=1 switches to json, anything else keeps the readable lines
+    pub fn from_env() -> Self {
+        Self::from_env_value(std::env::var("REDIS_LOG_JSON").ok().as_deref())
+    }
+
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("1" | "true" | "yes") => LogFormat::Json,
+            _ => LogFormat::Pretty,
+        }
+    }
+}
+
+// installs the global subscriber, the level comes from RUST_LOG like before
+pub fn init_logging(format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_format_from_env_value() {
+        assert_eq!(LogFormat::from_env_value(None), LogFormat::Pretty);
+        assert_eq!(LogFormat::from_env_value(Some("1")), LogFormat::Json);
+        assert_eq!(LogFormat::from_env_value(Some("true")), LogFormat::Json);
+        assert_eq!(LogFormat::from_env_value(Some("yes ")), LogFormat::Json);
+        assert_eq!(LogFormat::from_env_value(Some("0")), LogFormat::Pretty);
+        assert_eq!(LogFormat::from_env_value(Some("")), LogFormat::Pretty);
+    }
+}
@@ -1,23 +1,79 @@
 use anyhow::Result;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tracing::{info, warn};
-use zredis::{network, Backend};
+use zredis::{
+    logging::{self, LogFormat},
+    network,
+    network::{ConnectionOptions, SocketOptions},
+    Backend, DEFAULT_DATABASES,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    // RUST_LOG picks the level, REDIS_LOG_JSON=1 writes json lines
+    logging::init_logging(LogFormat::from_env());
 
     let addr = "0.0.0.0:6379";
     info!("zredis-server listening on {}", addr);
     let listener = TcpListener::bind(addr).await?;
 
-    let backend = Backend::new();
+    // REDIS_DATABASES=<n>, like the databases setting of redis.conf
+    let databases = std::env::var("REDIS_DATABASES")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_DATABASES);
+    let backend = Backend::with_databases(databases);
+    // REDIS_READONLY=1 refuses writes, handy for testing replica behaviour
+    if matches!(
+        std::env::var("REDIS_READONLY").as_deref(),
+        Ok("1" | "true" | "yes")
+    ) {
+        backend.set_read_only(true);
+    }
+    // REDIS_HASH_INSERTION_ORDER=1 makes HGETALL, HKEYS and HVALS list fields as they
+    // were added. by default they are sorted by name
+    if matches!(
+        std::env::var("REDIS_HASH_INSERTION_ORDER").as_deref(),
+        Ok("1" | "true" | "yes")
+    ) {
+        backend.set_hash_insertion_order(true);
+    }
+    // REDIS_PROTO_MAX_BULK_LEN=<bytes>, the longest APPEND, SETRANGE and SETBIT may make a string
+    if let Some(len) = std::env::var("REDIS_PROTO_MAX_BULK_LEN")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+    {
+        backend.set_proto_max_bulk_len(len);
+    }
+    // REDIS_PASSWORD=<password>, like requirepass, connections must AUTH before anything else
+    if let Some(password) = std::env::var("REDIS_PASSWORD")
+        .ok()
+        .filter(|p| !p.is_empty())
+    {
+        backend.set_password(Some(password));
+    }
+    // reclaims expired keys nobody reads, like redis' active expire cycle
+    let sweeper = backend.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        loop {
+            interval.tick().await;
+            sweeper.expire_cycle();
+        }
+    });
+    let socket_opts = SocketOptions::from_env();
+    let conn_opts = ConnectionOptions::from_env();
     loop {
         let (stream, raddr) = listener.accept().await?;
         info!("Accepted connection from: {}", raddr);
+        if let Err(e) = socket_opts.apply(&stream) {
+            warn!("failed to set socket options for {}: {:?}", raddr, e);
+        }
         let cloned_backend = backend.clone();
         tokio::spawn(async move {
-            match network::stream_handler(stream, cloned_backend).await {
+            match network::connection_handler(stream, cloned_backend, conn_opts).await {
                 Ok(_) => {
                     info!("Connection from {} exited", raddr);
                 }
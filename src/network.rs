@@ -1,61 +1,356 @@
 use crate::{
-    cmd::{Command, CommandExecutor},
-    Backend, RespDecode, RespEncode, RespError, RespFrame,
+    cmd::{Command, CommandError, CommandExecutor, ConnState, StatefulExecutor},
+    parse_inline, Backend, DecodeConfig, ProtocolVersion, RespDecode, RespError, RespFrame,
+    SimpleError, Subscriber,
 };
 use anyhow::Result;
 use futures::SinkExt;
+use socket2::{SockRef, TcpKeepalive};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use tokio_util::codec::{Decoder, Encoder, Framed};
 use tracing::info;
 
-#[derive(Debug)]
-struct RespFrameCodec;
+// options applied to every accepted socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketOptions {
+    pub nodelay: bool,
+    // idle time before keepalive probes are sent, None turns keepalive off
+    pub keepalive: Option<Duration>,
+}
+
+// per connection limits, independent of the socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    // unsent reply bytes past which no new command is read until the client catches up
+    pub output_buffer_limit: usize,
+    // how long a command may run before its client gets an error instead, None waits forever
+    pub command_timeout: Option<Duration>,
+    // accept requests whose lines end with a bare \n, see DecodeConfig::lenient_newlines
+    pub lenient_newlines: bool,
+}
+
+#[derive(Debug, Default)]
+struct RespFrameCodec {
+    // negotiated per connection, decides how replies are encoded
+    protocol: ProtocolVersion,
+    // bytes the frame being received is known to need, it isn't parsed again before
+    needed: Option<usize>,
+    decode: DecodeConfig,
+}
+
+// the most a declared length may grow the read buffer by in advance, so a client
+// can't make us allocate a huge buffer with nothing but a header
+const MAX_READ_RESERVE: usize = 8 * 1024 * 1024;
+
+// the longest inline command line, like redis' PROTO_INLINE_MAX_SIZE
+const MAX_INLINE_LEN: usize = 64 * 1024;
 
 #[derive(Debug)]
 struct RedisRequest {
     frame: RespFrame,
     backend: Backend,
+    timeout: Option<Duration>,
 }
 
-#[derive(Debug)]
+// what a connection may send before it has authenticated
+const NOAUTH_COMMANDS: &[&str] = &["auth", "hello", "quit"];
+
+// the only commands a connection may send while it has subscriptions
+const SUBSCRIBE_MODE_COMMANDS: &[&str] = &[
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
+// most commands reply with one frame, (un)subscribe replies once per channel
+// and an empty request gets no reply at all
+#[derive(Debug, Default)]
 struct RedisResponse {
-    frame: RespFrame,
+    // shared, so a stored value can be sent without copying it
+    frames: Vec<Arc<RespFrame>>,
+}
+
+impl Default for SocketOptions {
+    // same as redis' default tcp-keepalive of 300 seconds
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+impl SocketOptions {
+    // REDIS_TCP_NODELAY=0|1 and REDIS_TCP_KEEPALIVE=<seconds>, 0 disables keepalive
+    pub fn from_env() -> Self {
+        let mut opts = Self::default();
+        if let Ok(v) = std::env::var("REDIS_TCP_NODELAY") {
+            opts.nodelay = !matches!(v.trim(), "0" | "false" | "no");
+        }
+        if let Some(secs) = std::env::var("REDIS_TCP_KEEPALIVE")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            opts.keepalive = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        opts
+    }
+
+    pub fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        let socket = SockRef::from(stream);
+        match self.keepalive {
+            Some(time) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time)),
+            None => socket.set_keepalive(false),
+        }
+    }
+}
+
+impl RedisResponse {
+    fn reply(frame: RespFrame) -> Self {
+        Self {
+            frames: vec![Arc::new(frame)],
+        }
+    }
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            output_buffer_limit: 64 * 1024,
+            command_timeout: None,
+            lenient_newlines: false,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    // REDIS_OUTPUT_BUFFER_LIMIT=<bytes>, REDIS_COMMAND_TIMEOUT=<milliseconds>, 0 disables it,
+    // and REDIS_LENIENT_NEWLINES=1
+    pub fn from_env() -> Self {
+        let mut opts = Self::default();
+        if let Some(limit) = std::env::var("REDIS_OUTPUT_BUFFER_LIMIT")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            opts.output_buffer_limit = limit.max(1);
+        }
+        if let Some(ms) = std::env::var("REDIS_COMMAND_TIMEOUT")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            opts.command_timeout = (ms > 0).then(|| Duration::from_millis(ms));
+        }
+        if let Ok(v) = std::env::var("REDIS_LENIENT_NEWLINES") {
+            opts.lenient_newlines = matches!(v.trim(), "1" | "yes" | "true");
+        }
+        opts
+    }
 }
 
 // request handler
 pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
-    let mut framed = Framed::new(stream, RespFrameCodec);
+    connection_handler(stream, backend, ConnectionOptions::default()).await
+}
+
+pub async fn connection_handler<S>(
+    stream: S,
+    backend: Backend,
+    opts: ConnectionOptions,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let codec = RespFrameCodec {
+        decode: DecodeConfig {
+            lenient_newlines: opts.lenient_newlines,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut framed = Framed::new(stream, codec);
+    // published messages are queued here and written between replies
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut state = ConnState::new(Subscriber::new(&backend, tx));
     loop {
-        match framed.next().await {
-            Some(Ok(frame)) => {
-                info!("Received frame: {:?}", frame);
-                let request = RedisRequest {
-                    frame,
-                    backend: backend.clone(),
-                };
-                let response = request_handler(request).await?;
-                info!("Sending response: {:?}", response.frame);
-                framed.send(response.frame).await?;
+        // replies to pipelined commands are batched, but once the batch is over the
+        // limit, or there is nothing left to do, it's flushed before reading on.
+        // a client that doesn't read its replies thus stops being served
+        if framed.write_buffer().len() >= opts.output_buffer_limit
+            || !has_complete_frame(framed.read_buffer())
+        {
+            SinkExt::<RespFrame>::flush(&mut framed).await?;
+        }
+        tokio::select! {
+            frame = framed.next() => match frame {
+                Some(Ok(frame)) => {
+                    info!("Received frame: {}", frame);
+                    // the database picked with SELECT is looked up again for every request
+                    let request = RedisRequest {
+                        frame,
+                        backend: backend.database(state.db).unwrap_or_else(|| backend.clone()),
+                        timeout: opts.command_timeout,
+                    };
+                    let response = request_handler(request, &mut state).await?;
+                    // HELLO and RESET switch the protocol before their own reply is encoded
+                    framed.codec_mut().protocol = state.protocol;
+                    info!("Sending response: {:?}", response.frames);
+                    for frame in response.frames {
+                        framed.feed(frame).await?;
+                    }
+                }
+                Some(Err(e)) => {
+                    // malformed input can't be resynchronised, report it and hang up
+                    if let Some(err) = e.downcast_ref::<RespError>() {
+                        let reply = SimpleError::new(format!("ERR Protocol error: {}", err));
+                        framed.send(RespFrame::from(reply)).await?;
+                    }
+                    return Err(e);
+                }
+                None => return Ok(()),
+            },
+            Some(message) = rx.recv() => {
+                framed.feed(message).await?;
+                // whatever else was published meanwhile goes out in the same batch
+                while framed.write_buffer().len() < opts.output_buffer_limit {
+                    match rx.try_recv() {
+                        Ok(message) => framed.feed(message).await?,
+                        Err(_) => break,
+                    }
+                }
             }
-            Some(Err(e)) => return Err(e),
-            None => return Ok(()),
         }
     }
 }
 
-async fn request_handler(request: RedisRequest) -> Result<RedisResponse> {
-    let (frame, backend) = (request.frame, request.backend);
-    let cmd = Command::try_from(frame)?;
+async fn request_handler(request: RedisRequest, state: &mut ConnState) -> Result<RedisResponse> {
+    let (frame, backend, timeout) = (request.frame, request.backend, request.timeout);
+    // SELECT doesn't write, so the hook is told which database a write went to
+    let (db, changes) = (state.db, backend.changes());
+    if !state.authenticated
+        && backend.requires_auth()
+        && command_name(&frame).is_some_and(|n| !NOAUTH_COMMANDS.contains(&n.as_str()))
+    {
+        return Ok(RedisResponse::reply(CommandError::NoAuth.into()));
+    }
+    if state.in_subscribe_mode() {
+        if let Some(name) =
+            command_name(&frame).filter(|n| !SUBSCRIBE_MODE_COMMANDS.contains(&n.as_str()))
+        {
+            let reply = SimpleError::new(format!(
+                "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT / RESET are allowed in subscribe mode",
+                name
+            ));
+            return Ok(RedisResponse::reply(reply.into()));
+        }
+    }
+    // the write hook gets the request as it came in
+    let request = backend.has_write_hook().then(|| frame.clone());
+    // a bad command only fails the request, the connection stays usable
+    let cmd = match Command::try_from(frame) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            info!("Rejected command: {}", e);
+            return Ok(RedisResponse::reply(e.into()));
+        }
+    };
+    if cmd.is_write() && backend.is_read_only() {
+        return Ok(RedisResponse::reply(CommandError::ReadOnly.into()));
+    }
     info!("Executing command: {:?}", cmd);
-    let frame = cmd.execute(&backend);
-    Ok(RedisResponse { frame })
+    let cmd = match cmd {
+        // the stored value goes out as is, GET doesn't copy it into the reply
+        Command::Get(cmd) => {
+            return Ok(RedisResponse {
+                frames: vec![cmd.shared(&backend)],
+            })
+        }
+        cmd => cmd,
+    };
+    // blocking commands wait on the connection task instead of the executor
+    let frames = match cmd {
+        Command::BLPop(cmd) => vec![cmd.wait(&backend).await],
+        Command::BRPop(cmd) => vec![cmd.wait(&backend).await],
+        // the ones that change the connection run against its state
+        Command::Subscribe(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Unsubscribe(cmd) => cmd.execute_with_state(&backend, state),
+        Command::PSubscribe(cmd) => cmd.execute_with_state(&backend, state),
+        Command::PUnsubscribe(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Reset(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Select(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Auth(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Hello(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Client(cmd) => cmd.execute_with_state(&backend, state),
+        Command::Empty(_) => vec![],
+        cmd => vec![execute(cmd, backend.clone(), timeout).await?],
+    };
+    backend.notify_write(db, changes, request);
+    Ok(RedisResponse {
+        frames: frames.into_iter().map(Arc::new).collect(),
+    })
+}
+
+// with a timeout the command runs on the blocking pool so the connection can stop waiting
+// for it. the command itself can't be interrupted, it still finishes in the background
+async fn execute(cmd: Command, backend: Backend, timeout: Option<Duration>) -> Result<RespFrame> {
+    let Some(timeout) = timeout else {
+        return Ok(cmd.execute(&backend));
+    };
+    let task = tokio::task::spawn_blocking(move || cmd.execute(&backend));
+    match tokio::time::timeout(timeout, task).await {
+        Ok(reply) => Ok(reply?),
+        Err(_) => {
+            info!("Command timed out after {:?}", timeout);
+            Ok(CommandError::Timeout.into())
+        }
+    }
+}
+
+// whether the next request is already buffered and can be handled without waiting
+fn has_complete_frame(buf: &[u8]) -> bool {
+    if is_inline(buf) {
+        return buf.contains(&b'\n');
+    }
+    RespFrame::expect_length(buf).is_ok_and(|len| len <= buf.len())
+}
+
+// a request that doesn't start like an array is an inline command, e.g. from telnet
+fn is_inline(buf: &[u8]) -> bool {
+    buf.first().is_some_and(|b| *b != b'*')
+}
+
+fn command_name(frame: &RespFrame) -> Option<String> {
+    match frame {
+        RespFrame::Array(array) => match array.first() {
+            Some(RespFrame::BulkString(name)) => {
+                Some(String::from_utf8_lossy(name).to_ascii_lowercase())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
 impl Encoder<RespFrame> for RespFrameCodec {
     type Error = anyhow::Error;
     fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
+        Encoder::<Arc<RespFrame>>::encode(self, Arc::new(item), dst)
+    }
+}
+
+impl Encoder<Arc<RespFrame>> for RespFrameCodec {
+    type Error = anyhow::Error;
+    fn encode(&mut self, item: Arc<RespFrame>, dst: &mut bytes::BytesMut) -> Result<()> {
+        let encoded = item.encode_for(self.protocol);
         dst.extend_from_slice(&encoded);
         Ok(())
     }
@@ -65,10 +360,573 @@ impl Decoder for RespFrameCodec {
     type Item = RespFrame;
     type Error = anyhow::Error;
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
+        // a large value arrives over many reads, only try again once all of it is here
+        if self.needed.is_some_and(|needed| src.len() < needed) {
+            return Ok(None);
+        }
+        while is_inline(src) {
+            let Some(end) = src.iter().position(|b| *b == b'\n') else {
+                if src.len() > MAX_INLINE_LEN {
+                    return Err(
+                        RespError::InvalidFrame("too big inline request".to_string()).into(),
+                    );
+                }
+                return Ok(None);
+            };
+            let line = src.split_to(end + 1);
+            let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+            // the same array a client would have sent, so it's checked the same way.
+            // blank lines are skipped like redis does
+            let args = parse_inline(line)?;
+            if !args.is_empty() {
+                return Ok(Some(args.into()));
+            }
+        }
+        match RespFrame::decode_with(src, &self.decode) {
+            Ok(frame) => {
+                self.needed = None;
+                Ok(Some(frame))
+            }
+            Err(RespError::NotComplete) => {
+                self.needed = RespFrame::needed_length_with(src, &self.decode);
+                if let Some(needed) = self.needed {
+                    src.reserve(needed.saturating_sub(src.len()).min(MAX_READ_RESERVE));
+                }
+                Ok(None)
+            }
             Err(e) => Err(e.into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, RespEncode, RespNull, SimpleString};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use tokio::net::TcpListener;
+
+    async fn request(
+        args: &[&str],
+        backend: &Backend,
+        state: &mut ConnState,
+    ) -> Result<Vec<RespFrame>> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        let request = RedisRequest {
+            frame: RespArray::new(frames).into(),
+            backend: backend.clone(),
+            timeout: None,
+        };
+        let frames = request_handler(request, state).await?.frames;
+        Ok(frames.into_iter().map(Arc::unwrap_or_clone).collect())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_mode_rejects_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut state = ConnState::new(Subscriber::new(&backend, tx));
+
+        let ret = request(&["get", "k"], &backend, &mut state).await?;
+        assert_eq!(ret, vec![RespFrame::Null(RespNull)]);
+
+        request(&["subscribe", "news"], &backend, &mut state).await?;
+        let ret = request(&["GET", "k"], &backend, &mut state).await?;
+        let expected = SimpleError::new(
+            "ERR Can't execute 'get': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT / RESET are allowed in subscribe mode",
+        );
+        assert_eq!(ret, vec![RespFrame::from(expected)]);
+        let ret = request(&["psubscribe", "n*"], &backend, &mut state).await?;
+        assert_eq!(ret.len(), 1);
+        assert!(!matches!(ret[0], RespFrame::Error(_)));
+
+        // leaving subscribe mode allows everything again
+        request(&["reset"], &backend, &mut state).await?;
+        let ret = request(&["get", "k"], &backend, &mut state).await?;
+        assert_eq!(ret, vec![RespFrame::Null(RespNull)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_request_is_ignored() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut state = ConnState::new(Subscriber::new(&backend, tx));
+
+        let mut buf = bytes::BytesMut::from(&b"*0\r\n"[..]);
+        let frame = RespFrameCodec::default().decode(&mut buf)?;
+        let empty = RedisRequest {
+            frame: frame.expect("*0 is a complete frame"),
+            backend: backend.clone(),
+            timeout: None,
+        };
+        let response = request_handler(empty, &mut state).await?;
+        assert!(response.frames.is_empty());
+
+        // the connection carries on as usual
+        let ret = request(&["get", "k"], &backend, &mut state).await?;
+        assert_eq!(ret, vec![RespFrame::Null(RespNull)]);
+
+        Ok(())
+    }
+
+    async fn roundtrip(client: &mut DuplexStream, args: &[&str], expected: &[u8]) -> Result<()> {
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        client.write_all(&RespArray::new(frames).encode()).await?;
+        let mut reply = vec![0; expected.len()];
+        client.read_exact(&mut reply).await?;
+        assert_eq!(reply, expected, "{:?}", args);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_select_switches_database() -> Result<()> {
+        let backend = Backend::with_databases(4);
+        let db3 = backend.database(3).expect("database 3 exists");
+        db3.set("k".to_string(), BulkString::new("three").into());
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+
+        roundtrip(&mut client, &["get", "k"], b"$-1\r\n").await?;
+        roundtrip(&mut client, &["select", "3"], b"+OK\r\n").await?;
+        roundtrip(&mut client, &["get", "k"], b"$5\r\nthree\r\n").await?;
+        roundtrip(&mut client, &["set", "n", "v"], b"+OK\r\n").await?;
+        assert!(db3.exists("n"));
+        assert!(!backend.exists("n"));
+
+        // a rejected SELECT leaves the connection where it was
+        let err = b"-ERR DB index is out of range\r\n";
+        roundtrip(&mut client, &["select", "4"], err).await?;
+        roundtrip(&mut client, &["get", "k"], b"$5\r\nthree\r\n").await?;
+
+        // and RESET goes back to database 0
+        roundtrip(&mut client, &["reset"], b"+RESET\r\n").await?;
+        roundtrip(&mut client, &["get", "k"], b"$-1\r\n").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_hook_gets_selected_database() -> Result<()> {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let backend = Backend::with_databases(4);
+        backend.set_write_hook(move |db, frame| sink.lock().unwrap().push((db, frame.clone())));
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+
+        roundtrip(&mut client, &["select", "3"], b"+OK\r\n").await?;
+        roundtrip(&mut client, &["set", "k", "v"], b"+OK\r\n").await?;
+        // a pop that timed out changed nothing
+        roundtrip(&mut client, &["blpop", "l", "0.01"], b"*-1\r\n").await?;
+        roundtrip(&mut client, &["rpush", "l", "a"], b":+1\r\n").await?;
+        roundtrip(
+            &mut client,
+            &["blpop", "l", "0"],
+            b"*2\r\n$1\r\nl\r\n$1\r\na\r\n",
+        )
+        .await?;
+
+        let frame = |args: &[&str]| -> RespFrame {
+            let args: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+            RespArray::new(args).into()
+        };
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (3, frame(&["set", "k", "v"])),
+                (3, frame(&["rpush", "l", "a"])),
+                (3, frame(&["blpop", "l", "0"])),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout() -> Result<()> {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions {
+            command_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        tokio::spawn(connection_handler(server, Backend::new(), opts));
+
+        let err = b"-ERR command timed out\r\n";
+        roundtrip(&mut client, &["debug", "sleep", "0.5"], err).await?;
+        // the connection is still served while the slow command finishes elsewhere
+        roundtrip(&mut client, &["set", "k", "v"], b"+OK\r\n").await?;
+        roundtrip(&mut client, &["get", "k"], b"$1\r\nv\r\n").await?;
+        roundtrip(&mut client, &["debug", "sleep", "0"], b"+OK\r\n").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_required() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_password(Some("secret".to_string()));
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+
+        let noauth = b"-NOAUTH Authentication required.\r\n";
+        roundtrip(&mut client, &["set", "k", "v"], noauth).await?;
+        roundtrip(&mut client, &["get", "k"], noauth).await?;
+        roundtrip(&mut client, &["hello", "3"], noauth).await?;
+        assert!(!backend.exists("k"));
+
+        let wrongpass = b"-WRONGPASS invalid username-password pair or user is disabled.\r\n";
+        roundtrip(&mut client, &["auth", "wrong"], wrongpass).await?;
+        roundtrip(&mut client, &["get", "k"], noauth).await?;
+
+        roundtrip(&mut client, &["auth", "secret"], b"+OK\r\n").await?;
+        roundtrip(&mut client, &["set", "k", "v"], b"+OK\r\n").await?;
+        roundtrip(&mut client, &["get", "k"], b"$1\r\nv\r\n").await?;
+
+        // RESET forgets the authentication as well
+        roundtrip(&mut client, &["reset"], b"+RESET\r\n").await?;
+        roundtrip(&mut client, &["get", "k"], noauth).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hello_auth() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_password(Some("secret".to_string()));
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+
+        let noauth = b"-NOAUTH Authentication required.\r\n";
+        let wrongpass = b"-WRONGPASS invalid username-password pair or user is disabled.\r\n";
+        let args = ["hello", "3", "auth", "default", "wrong"];
+        roundtrip(&mut client, &args, wrongpass).await?;
+        roundtrip(&mut client, &["get", "k"], noauth).await?;
+
+        // the reply is a map already, HELLO switches the protocol before it's sent
+        let args = ["hello", "3", "AUTH", "default", "secret"];
+        let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+        let info = Command::try_from(RespArray::new(frames))?.execute(&backend);
+        assert!(matches!(info, RespFrame::Map(_)));
+        roundtrip(&mut client, &args, &info.encode()).await?;
+        // and it sticks, a missing key is a RESP3 null
+        roundtrip(&mut client, &["get", "k"], b"_\r\n").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hello_switches_protocol() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset(
+            "h".to_string(),
+            "f".to_string(),
+            BulkString::new("v").into(),
+        );
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+
+        let resp2 = b"*2\r\n$1\r\nf\r\n$1\r\nv\r\n";
+        let resp3 = b"%1\r\n+f\r\n$1\r\nv\r\n";
+        roundtrip(&mut client, &["hgetall", "h"], resp2).await?;
+        for (version, hgetall, null) in [
+            ("3", &resp3[..], &b"_\r\n"[..]),
+            ("2", &resp2[..], &b"$-1\r\n"[..]),
+            ("3", &resp3[..], &b"_\r\n"[..]),
+        ] {
+            let args = ["hello", version];
+            let frames: Vec<RespFrame> = args.iter().map(|a| BulkString::new(*a).into()).collect();
+            let info = Command::try_from(RespArray::new(frames))?.execute(&backend);
+            let protocol = match version {
+                "2" => ProtocolVersion::Resp2,
+                _ => ProtocolVersion::Resp3,
+            };
+            roundtrip(&mut client, &args, &info.encode_for(protocol)).await?;
+            roundtrip(&mut client, &["hgetall", "h"], hgetall).await?;
+            roundtrip(&mut client, &["get", "missing"], null).await?;
+        }
+
+        // an unknown version is refused and the protocol stays as it was
+        let noproto = b"-NOPROTO unsupported protocol version\r\n";
+        roundtrip(&mut client, &["hello", "4"], noproto).await?;
+        roundtrip(&mut client, &["hgetall", "h"], resp3).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_timeout_resp2() -> Result<()> {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, Backend::new(), opts));
+
+        // the null array, where a missing value is the null bulk string
+        roundtrip(&mut client, &["blpop", "empty", "0.01"], b"*-1\r\n").await?;
+        roundtrip(&mut client, &["brpop", "empty", "0.01"], b"*-1\r\n").await?;
+        roundtrip(&mut client, &["get", "empty"], b"$-1\r\n").await?;
+
+        Ok(())
+    }
+
+    async fn inline(client: &mut DuplexStream, line: &[u8], expected: &[u8]) -> Result<()> {
+        client.write_all(line).await?;
+        let mut reply = vec![0; expected.len()];
+        client.read_exact(&mut reply).await?;
+        assert_eq!(reply, expected, "{:?}", String::from_utf8_lossy(line));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (mut client, server) = tokio::io::duplex(4096);
+        tokio::spawn(connection_handler(
+            server,
+            backend.clone(),
+            ConnectionOptions::default(),
+        ));
+
+        // the arity is checked like for any other request
+        let arity = b"-ERR wrong number of arguments for 'get' command\r\n";
+        inline(&mut client, b"get\r\n", arity).await?;
+        roundtrip(&mut client, &["get"], arity).await?;
+
+        inline(&mut client, b"SET k \"a b\"\r\n", b"+OK\r\n").await?;
+        // blank lines are skipped, a bare newline ends a line too
+        inline(&mut client, b"\r\n\nget k\n", b"$3\r\na b\r\n").await?;
+        // and RESP requests still work on the same connection
+        roundtrip(&mut client, &["get", "k"], b"$3\r\na b\r\n").await?;
+
+        let unbalanced = b"-ERR Protocol error: ";
+        inline(&mut client, b"get \"k\r\n", unbalanced).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_newlines_codec() -> Result<()> {
+        let request = b"*2\n$3\nget\n$1\nk\n";
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("get").into(),
+            BulkString::new("k").into(),
+        ])
+        .into();
+
+        let mut codec = RespFrameCodec {
+            decode: DecodeConfig {
+                lenient_newlines: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = bytes::BytesMut::from(&request[..10]);
+        assert!(codec.decode(&mut buf)?.is_none());
+        buf.extend_from_slice(&request[10..]);
+        assert_eq!(codec.decode(&mut buf)?, Some(expected));
+        assert!(buf.is_empty());
+
+        let mut buf = bytes::BytesMut::from(&request[..]);
+        assert!(RespFrameCodec::default().decode(&mut buf).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_rejects_writes() -> Result<()> {
+        let backend = Backend::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let mut state = ConnState::new(Subscriber::new(&backend, tx));
+        request(&["set", "k", "v"], &backend, &mut state).await?;
+
+        backend.set_read_only(true);
+        let ret = request(&["set", "k", "w"], &backend, &mut state).await?;
+        let expected = SimpleError::new("READONLY You can't write against a read only replica.");
+        assert_eq!(ret, vec![RespFrame::from(expected)]);
+        let ret = request(&["del", "k"], &backend, &mut state).await?;
+        assert!(matches!(ret[0], RespFrame::Error(_)));
+        // reads still go through and see the untouched value
+        let ret = request(&["get", "k"], &backend, &mut state).await?;
+        assert_eq!(ret, vec![BulkString::new("v").into()]);
+
+        backend.set_read_only(false);
+        let ret = request(&["set", "k", "w"], &backend, &mut state).await?;
+        assert_eq!(ret, vec![SimpleString::new("OK").into()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_reader_pauses_reads() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("k".to_string(), BulkString::new(vec![b'x'; 1024]).into());
+        let (client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions {
+            output_buffer_limit: 2048,
+            ..Default::default()
+        };
+        tokio::spawn(connection_handler(server, backend, opts));
+
+        let get = b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n";
+        let reply_len = BulkString::new(vec![b'x'; 1024]).encode().len();
+        let n = 1000;
+        let (mut rd, mut wr) = tokio::io::split(client);
+        let writer = tokio::spawn(async move { wr.write_all(&get.repeat(n)).await });
+
+        // nobody takes the replies, so the server stops taking requests
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!writer.is_finished());
+
+        // once the replies are read everything goes through
+        let mut replies = vec![0; reply_len * n];
+        rd.read_exact(&mut replies).await?;
+        writer.await??;
+        assert!(replies.starts_with(b"$1024\r\nxxx"));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_publishes_arrive_intact() -> Result<()> {
+        let backend = Backend::new();
+        let (mut client, server) = tokio::io::duplex(4096);
+        let opts = ConnectionOptions::default();
+        tokio::spawn(connection_handler(server, backend.clone(), opts));
+        let subscribed = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:+1\r\n";
+        roundtrip(&mut client, &["subscribe", "news"], subscribed).await?;
+
+        // messages bigger than the pipe, so every one of them is written in pieces
+        let (publishers, messages) = (8, 500);
+        let padding = "x".repeat(6000);
+        let tasks: Vec<_> = (0..publishers)
+            .map(|p| {
+                let (backend, padding) = (backend.clone(), padding.clone());
+                tokio::spawn(async move {
+                    for n in 0..messages {
+                        let message = format!("{}:{}:{}", p, n, padding);
+                        assert_eq!(backend.publish("news", BulkString::new(message).into()), 1);
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        let mut codec = RespFrameCodec::default();
+        let mut buf = bytes::BytesMut::new();
+        let mut next = vec![0; publishers];
+        let mut received = 0;
+        while received < publishers * messages {
+            let frame = match codec.decode(&mut buf)? {
+                Some(frame) => frame,
+                None => {
+                    let read = client.read_buf(&mut buf);
+                    assert!(tokio::time::timeout(Duration::from_secs(10), read).await?? > 0);
+                    continue;
+                }
+            };
+            let RespFrame::Array(frame) = frame else {
+                panic!("messages are arrays");
+            };
+            assert_eq!(
+                frame[..2],
+                [
+                    BulkString::new("message").into(),
+                    BulkString::new("news").into()
+                ]
+            );
+            let RespFrame::BulkString(message) = &frame[2] else {
+                panic!("the message is a bulk string");
+            };
+            let message = std::str::from_utf8(message)?;
+            let mut parts = message.splitn(3, ':');
+            let p: usize = parts.next().unwrap_or_default().parse()?;
+            let n: usize = parts.next().unwrap_or_default().parse()?;
+            assert_eq!(parts.next(), Some(padding.as_str()));
+            // each publisher's messages come in the order it sent them
+            assert_eq!(n, next[p]);
+            next[p] += 1;
+            received += 1;
+        }
+        for task in tasks {
+            task.await?;
+        }
+        assert!(next.iter().all(|n| *n == messages));
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_large_bulk_string() -> Result<()> {
+        let value = vec![b'x'; 1024 * 1024];
+        let frames: Vec<RespFrame> = vec![
+            BulkString::new("set").into(),
+            BulkString::new("k").into(),
+            BulkString::new(value.clone()).into(),
+        ];
+        let request = RespArray::new(frames).encode();
+
+        let mut codec = RespFrameCodec::default();
+        let mut buf = bytes::BytesMut::new();
+        let mut chunks = request.chunks(4096);
+        buf.extend_from_slice(chunks.next().expect("request isn't empty"));
+        assert!(codec.decode(&mut buf)?.is_none());
+        // the declared length is known after the first chunk and the buffer sized for it,
+        // so the rest is appended in place and nothing is parsed until it's all there
+        assert_eq!(codec.needed, Some(request.len()));
+        assert!(buf.capacity() >= request.len());
+        let start = buf.as_ptr();
+        let mut frame = None;
+        for chunk in chunks {
+            buf.extend_from_slice(chunk);
+            assert_eq!(buf.as_ptr(), start);
+            frame = codec.decode(&mut buf)?;
+        }
+
+        let RespFrame::Array(args) = frame.expect("the last chunk completes the frame") else {
+            panic!("request must decode to an array");
+        };
+        assert_eq!(args[2], BulkString::new(value).into());
+        assert!(buf.is_empty());
+        assert_eq!(codec.needed, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_complete_frame() {
+        assert!(has_complete_frame(b"+OK\r\n"));
+        assert!(has_complete_frame(b"*1\r\n$4\r\nping\r\n*1"));
+        assert!(!has_complete_frame(b"*1\r\n$4\r\npi"));
+        assert!(!has_complete_frame(b""));
+    }
+
+    async fn connected_pair() -> Result<(TcpStream, TcpStream)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let client = TcpStream::connect(listener.local_addr()?).await?;
+        let (server, _) = listener.accept().await?;
+        Ok((server, client))
+    }
+
+    #[tokio::test]
+    async fn test_socket_options_applied() -> Result<()> {
+        let (server, _client) = connected_pair().await?;
+        SocketOptions::default().apply(&server)?;
+        assert!(server.nodelay()?);
+        assert!(SockRef::from(&server).keepalive()?);
+
+        let opts = SocketOptions {
+            nodelay: false,
+            keepalive: None,
+        };
+        opts.apply(&server)?;
+        assert!(!server.nodelay()?);
+        assert!(!SockRef::from(&server).keepalive()?);
+
+        Ok(())
+    }
+}
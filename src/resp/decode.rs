@@ -3,13 +3,52 @@ use crate::{
     SimpleError, SimpleString,
 };
 use bytes::{Buf, BytesMut};
+use tracing::debug;
 
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
 
+// how deep arrays, maps and sets may nest by default. anything deeper is rejected
+// rather than recursed into, so a hostile frame can't exhaust the stack
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+// how RespFrame::decode_with reads a frame, the defaults are RespFrame::decode's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeConfig {
+    // how many levels deep aggregates may nest
+    pub max_depth: usize,
+    // take a bare \n as a line ending too, for hand written clients and fixtures.
+    // strict decoding rejects it
+    pub lenient_newlines: bool,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_NESTING_DEPTH,
+            lenient_newlines: false,
+        }
+    }
+}
+
+// RESP2's null bulk string and null array, both decode to a null
+const RESP2_NULLS: [&[u8]; 2] = [b"$-1\r\n", b"*-1\r\n"];
+const RESP2_NULL_LEN: usize = 5;
+
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_frame(buf, MAX_NESTING_DEPTH)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, MAX_NESTING_DEPTH)
+    }
+}
+
+// `depth` is how many more levels of aggregates may be opened below this frame
+fn decode_frame(buf: &mut BytesMut, depth: usize) -> Result<RespFrame, RespError> {
+    {
         let mut iter = buf.iter().peekable();
         match iter.peek() {
             Some(b'+') => {
@@ -24,14 +63,18 @@ impl RespDecode for RespFrame {
                 let frame = i64::decode(buf)?;
                 Ok(frame.into())
             }
-            Some(b'$') => match BulkString::decode(buf) {
-                Ok(frame) => Ok(frame.into()),
-                Err(_) => Err(RespError::NotComplete),
-            },
-            Some(b'*') => match RespArray::decode(buf) {
-                Ok(frame) => Ok(frame.into()),
-                Err(_) => Err(RespError::NotComplete),
-            },
+            Some(b'$') | Some(b'*') if is_resp2_null(buf) => {
+                buf.advance(RESP2_NULL_LEN);
+                Ok(RespFrame::Null(RespNull))
+            }
+            Some(b'$') => {
+                let frame = BulkString::decode(buf)?;
+                Ok(frame.into())
+            }
+            Some(b'*') => {
+                let frame = decode_array(buf, depth)?;
+                Ok(frame.into())
+            }
             Some(b'_') => {
                 let frame = RespNull::decode(buf)?;
                 Ok(frame.into())
@@ -46,13 +89,19 @@ impl RespDecode for RespFrame {
                 Ok(frame.into())
             }
             Some(b'%') => {
-                let frame = RespMap::decode(buf)?;
+                let frame = decode_map(buf, depth)?;
                 Ok(frame.into())
             }
             Some(b'~') => {
-                let frame = RespSet::decode(buf)?;
+                let frame = decode_set(buf, depth)?;
                 Ok(frame.into())
             }
+            Some(b'|') => {
+                // attributes are out-of-band metadata, nothing uses them yet
+                let attributes = decode_attribute(buf, depth)?;
+                debug!("discarding attributes: {:?}", attributes);
+                decode_frame(buf, depth)
+            }
             None => Err(RespError::NotComplete),
             _ => Err(RespError::InvalidFrameType(format!(
                 "[decode.rs] expect length: unknown frame type: {:?}",
@@ -60,13 +109,16 @@ impl RespDecode for RespFrame {
             ))),
         }
     }
+}
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+fn frame_length(buf: &[u8], depth: usize) -> Result<usize, RespError> {
+    {
         let mut iter = buf.iter().peekable();
         match iter.peek() {
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
+            Some(b'$') | Some(b'*') if is_resp2_null(buf) => Ok(RESP2_NULL_LEN),
+            Some(b'*') => aggregate_length(buf, RespArray::PREFIX, depth),
+            Some(b'~') => aggregate_length(buf, RespSet::PREFIX, depth),
+            Some(b'%') => aggregate_length(buf, RespMap::PREFIX, depth),
             Some(b'$') => BulkString::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
             Some(b'+') => SimpleString::expect_length(buf),
@@ -75,8 +127,170 @@ impl RespDecode for RespFrame {
             Some(b',') => f64::expect_length(buf),
             //Some(b',') => Nf64::expect_length(buf),
             Some(b'_') => RespNull::expect_length(buf),
-            _ => Err(RespError::NotComplete),
+            // an attribute is only complete together with the frame it precedes
+            Some(b'|') => {
+                let attr_len = aggregate_length(buf, "|", depth)?;
+                let rest = buf.get(attr_len..).ok_or(RespError::NotComplete)?;
+                Ok(attr_len + frame_length(rest, depth)?)
+            }
+            None => Err(RespError::NotComplete),
+            // waiting for more data wouldn't help
+            Some(_) => Err(RespError::InvalidFrameType(format!(
+                "unknown frame type: {:?}",
+                buf
+            ))),
+        }
+    }
+}
+
+// one level deeper, or an error once the limit is reached
+fn nested(depth: usize) -> Result<usize, RespError> {
+    depth
+        .checked_sub(1)
+        .ok_or_else(|| RespError::InvalidFrame("aggregates are nested too deep".to_string()))
+}
+
+impl RespFrame {
+    // like decode, but aggregates may only nest `max_depth` levels deep
+    pub fn decode_with_max_depth(buf: &mut BytesMut, max_depth: usize) -> Result<Self, RespError> {
+        decode_frame(buf, max_depth)
+    }
+
+    pub fn decode_with(buf: &mut BytesMut, config: &DecodeConfig) -> Result<Self, RespError> {
+        if !config.lenient_newlines {
+            return decode_frame(buf, config.max_depth);
+        }
+        // a copy of the frame with \r\n line endings is decoded the strict way
+        let mut strict = BytesMut::new();
+        let consumed = with_crlf(buf, 0, Some(&mut strict), &mut 0, config.max_depth)?;
+        let frame = decode_frame(&mut strict, config.max_depth)?;
+        buf.advance(consumed);
+        Ok(frame)
+    }
+
+    // how many bytes the incomplete frame at the start of `buf` needs at least, as
+    // far as its headers tell. a bulk string declares its full length up front, an
+    // array only up to its first incomplete element. None if nothing is known yet
+    pub fn needed_length(buf: &[u8]) -> Option<usize> {
+        needed_length(buf, MAX_NESTING_DEPTH)
+    }
+
+    // needed_length for a frame read with decode_with
+    pub fn needed_length_with(buf: &[u8], config: &DecodeConfig) -> Option<usize> {
+        if !config.lenient_newlines {
+            return needed_length(buf, config.max_depth);
+        }
+        let mut needed = 0;
+        match with_crlf(buf, 0, None, &mut needed, config.max_depth) {
+            Err(RespError::NotComplete) if needed > buf.len() => Some(needed),
+            _ => None,
+        }
+    }
+
+    // decode a frame from a plain slice, returning the frame and the bytes consumed
+    pub fn from_bytes(buf: &[u8]) -> Result<(RespFrame, usize), RespError> {
+        let mut data = BytesMut::from(buf);
+        let frame = RespFrame::decode(&mut data)?;
+        Ok((frame, buf.len() - data.len()))
+    }
+}
+
+// reads the frame starting at `at` in `buf`, taking a bare \n as a line ending too,
+// and returns where it ends. the frame is copied to `out` with every line ending in
+// \r\n, if given. when it's incomplete, `needed` is raised to the bytes it's known
+// to need. anything else wrong with the frame is left for the strict decoder to find
+fn with_crlf(
+    buf: &[u8],
+    at: usize,
+    mut out: Option<&mut BytesMut>,
+    needed: &mut usize,
+    depth: usize,
+) -> Result<usize, RespError> {
+    let end = at
+        + buf[at..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or(RespError::NotComplete)?;
+    let line = buf[at..end].strip_suffix(b"\r").unwrap_or(&buf[at..end]);
+    copy_line(&mut out, line);
+    let mut pos = end + 1;
+    // bounded like parse_length. a length that doesn't parse, or a null, has
+    // nothing following it
+    let len = match std::str::from_utf8(&line[1..])
+        .ok()
+        .and_then(|s| s.parse::<isize>().ok())
+    {
+        Some(len) if len >= 0 => len as usize,
+        _ => return Ok(pos),
+    };
+    match line.first() {
+        Some(b'$') => {
+            let data_end = pos
+                .checked_add(len)
+                .ok_or(RespError::InvalidFrameLength(len as isize))?;
+            let Some(data) = buf.get(pos..data_end) else {
+                *needed = (*needed).max(data_end.saturating_add(1));
+                return Err(RespError::NotComplete);
+            };
+            copy_line(&mut out, data);
+            pos = data_end;
+            let rest = &buf[pos..];
+            if rest.starts_with(b"\n") {
+                pos += 1;
+            } else if rest.starts_with(CRLF) {
+                pos += CRLF_LEN;
+            } else if rest.is_empty() || rest == b"\r" {
+                *needed = (*needed).max(data_end + 1);
+                return Err(RespError::NotComplete);
+            } else {
+                return Err(RespError::InvalidFrame(
+                    "bulk string is longer than its declared length".to_string(),
+                ));
+            }
         }
+        Some(prefix @ (b'*' | b'~' | b'%' | b'|')) => {
+            let depth = nested(depth)?;
+            // maps and attributes have a key and a value per entry
+            let frames = if matches!(prefix, b'%' | b'|') {
+                len.saturating_mul(2)
+            } else {
+                len
+            };
+            for _ in 0..frames {
+                pos = with_crlf(buf, pos, out.as_deref_mut(), needed, depth)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(pos)
+}
+
+fn copy_line(out: &mut Option<&mut BytesMut>, data: &[u8]) {
+    if let Some(out) = out {
+        out.extend_from_slice(data);
+        out.extend_from_slice(CRLF);
+    }
+}
+
+fn needed_length(buf: &[u8], depth: usize) -> Option<usize> {
+    match buf.first() {
+        Some(b'*') | Some(b'~') => {
+            let depth = nested(depth).ok()?;
+            let end = find_crlf(buf, 1)?;
+            let len: usize = std::str::from_utf8(&buf[1..end]).ok()?.parse().ok()?;
+            let mut total = end + CRLF_LEN;
+            for _ in 0..len {
+                let data = buf.get(total..)?;
+                match frame_length(data, depth) {
+                    Ok(len) if len <= data.len() => total += len,
+                    Ok(len) => return Some(total + len),
+                    Err(_) => return Some(total + needed_length(data, depth).unwrap_or(0)),
+                }
+            }
+            Some(total)
+        }
+        Some(_) => frame_length(buf, depth).ok(),
+        None => None,
     }
 }
 
@@ -157,6 +371,7 @@ impl RespDecode for i64 {
     }
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
         let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        String::from_utf8_lossy(&buf[Self::PREFIX.len()..end]).parse::<i64>()?;
         Ok(end + CRLF_LEN)
     }
 }
@@ -202,25 +417,29 @@ impl RespDecode for BulkString {
 impl RespDecode for RespArray {
     const PREFIX: &'static str = "*";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
+        decode_array(buf, MAX_NESTING_DEPTH)
+    }
 
-        buf.advance(end + CRLF_LEN);
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, Self::PREFIX, MAX_NESTING_DEPTH)
+    }
+}
 
-        let mut frames = Vec::with_capacity(len);
-        for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
-        }
-        Ok(RespArray::new(frames))
+fn decode_array(buf: &mut BytesMut, depth: usize) -> Result<RespArray, RespError> {
+    let (end, len) = parse_length(buf, RespArray::PREFIX)?;
+    let total_len = aggregate_length(buf, RespArray::PREFIX, depth)?;
+    if buf.len() < total_len {
+        return Err(RespError::NotComplete);
     }
 
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+    buf.advance(end + CRLF_LEN);
+
+    let depth = nested(depth)?;
+    let mut frames = Vec::with_capacity(len);
+    for _ in 0..len {
+        frames.push(decode_frame(buf, depth)?);
     }
+    Ok(RespArray::new(frames))
 }
 
 impl RespDecode for f64 {
@@ -255,52 +474,94 @@ impl RespDecode for Nf64 {
 impl RespDecode for RespMap {
     const PREFIX: &'static str = "%";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
+        decode_map(buf, MAX_NESTING_DEPTH)
+    }
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, Self::PREFIX, MAX_NESTING_DEPTH)
+    }
+}
 
-        buf.advance(end + CRLF_LEN);
-        let mut frames = RespMap::new();
-        for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
-            let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
-        }
+fn decode_map(buf: &mut BytesMut, depth: usize) -> Result<RespMap, RespError> {
+    let (end, len) = parse_length(buf, RespMap::PREFIX)?;
+    let total_len = aggregate_length(buf, RespMap::PREFIX, depth)?;
 
-        Ok(frames)
+    if buf.len() < total_len {
+        return Err(RespError::NotComplete);
     }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+
+    buf.advance(end + CRLF_LEN);
+    let depth = nested(depth)?;
+    let mut frames = RespMap::new();
+    for _ in 0..len {
+        let key = decode_map_key(buf)?;
+        let value = decode_frame(buf, depth)?;
+        frames.insert(key, value);
     }
+
+    Ok(frames)
 }
 
 impl RespDecode for RespSet {
     const PREFIX: &'static str = "~";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
+        decode_set(buf, MAX_NESTING_DEPTH)
+    }
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, Self::PREFIX, MAX_NESTING_DEPTH)
+    }
+}
 
-        buf.advance(end + CRLF_LEN);
-        let mut frames = Vec::new();
-        for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
-        }
+fn decode_set(buf: &mut BytesMut, depth: usize) -> Result<RespSet, RespError> {
+    let (end, len) = parse_length(buf, RespSet::PREFIX)?;
+    let total_len = aggregate_length(buf, RespSet::PREFIX, depth)?;
+    if buf.len() < total_len {
+        return Err(RespError::NotComplete);
+    }
 
-        Ok(RespSet::new(frames))
+    buf.advance(end + CRLF_LEN);
+    let depth = nested(depth)?;
+    let mut frames = Vec::new();
+    for _ in 0..len {
+        frames.push(decode_frame(buf, depth)?);
     }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        calc_total_length(buf, end, len, Self::PREFIX)
+
+    Ok(RespSet::new(frames))
+}
+
+// map keys are simple strings, or bulk strings when they aren't text
+fn decode_map_key(buf: &mut BytesMut) -> Result<Vec<u8>, RespError> {
+    if buf.starts_with(BulkString::PREFIX.as_bytes()) {
+        Ok(BulkString::decode(buf)?.0)
+    } else {
+        Ok(SimpleString::decode(buf)?.0.into_bytes())
+    }
+}
+
+fn map_key_length(buf: &[u8]) -> Result<usize, RespError> {
+    if buf.starts_with(BulkString::PREFIX.as_bytes()) {
+        BulkString::expect_length(buf)
+    } else {
+        SimpleString::expect_length(buf)
     }
 }
 
+// consume a `|` attribute map, only once the frame following it is complete too
+fn decode_attribute(buf: &mut BytesMut, depth: usize) -> Result<RespMap, RespError> {
+    if buf.len() < frame_length(buf, depth)? {
+        return Err(RespError::NotComplete);
+    }
+    let (end, len) = parse_length(buf, "|")?;
+    buf.advance(end + CRLF_LEN);
+    let depth = nested(depth)?;
+    let mut attributes = RespMap::new();
+    for _ in 0..len {
+        let key = decode_map_key(buf)?;
+        let value = decode_frame(buf, depth)?;
+        attributes.insert(key, value);
+    }
+    Ok(attributes)
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     expect: &str,
@@ -323,6 +584,14 @@ fn extract_fixed_data(
 }
 
 fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
+    // more data can't make up for a missing \r, see DecodeConfig::lenient_newlines
+    if let Some(i) = buf.iter().position(|b| *b == b'\n') {
+        if i == 0 || buf[i - 1] != b'\r' {
+            return Err(RespError::InvalidFrame(
+                "line ends with \\n instead of \\r\\n".to_string(),
+            ));
+        }
+    }
     if buf.len() < 3 {
         return Err(RespError::NotComplete);
     }
@@ -339,9 +608,13 @@ fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespErro
     Ok(end)
 }
 
+fn is_resp2_null(buf: &[u8]) -> bool {
+    RESP2_NULLS.iter().any(|null| buf.starts_with(null))
+}
+
 fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
     let mut count = 0;
-    for i in 1..buf.len() - 1 {
+    for i in 1..buf.len().saturating_sub(1) {
         if buf[i] == b'\r' && buf[i + 1] == b'\n' {
             count += 1;
             if count == nth {
@@ -352,31 +625,48 @@ fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
     None
 }
 
+// a length that isn't a number, or is negative, is an error right away instead of
+// waiting for data that would never make the frame valid. the RESP2 nulls, the
+// only negative lengths allowed, are looked for before this
 fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
     let end = extract_simple_frame_data(buf, prefix)?;
     let s = String::from_utf8_lossy(&buf[prefix.len()..end]);
-    Ok((end, s.parse()?))
+    let len: isize = s.parse()?;
+    let len = usize::try_from(len).map_err(|_| RespError::InvalidFrameLength(len))?;
+    Ok((end, len))
+}
+
+// full length of an array, set, map or attribute, its elements one level deeper
+fn aggregate_length(buf: &[u8], prefix: &str, depth: usize) -> Result<usize, RespError> {
+    let (end, len) = parse_length(buf, prefix)?;
+    calc_total_length(buf, end, len, prefix, nested(depth)?)
 }
 
-fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
+fn calc_total_length(
+    buf: &[u8],
+    end: usize,
+    len: usize,
+    prefix: &str,
+    depth: usize,
+) -> Result<usize, RespError> {
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
         "*" | "~" => {
             for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
+                let len = frame_length(data, depth)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
             }
             Ok(total)
         }
-        "%" => {
+        "%" | "|" => {
             for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
-                data = &data[len..];
+                let len = map_key_length(data)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
+                let len = frame_length(data, depth)?;
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
             }
             Ok(total)
@@ -391,6 +681,23 @@ mod tests {
     use anyhow::Result;
     use bytes::BufMut;
 
+    #[test]
+    fn test_needed_length() {
+        // a bulk string knows its full length from the header alone
+        assert_eq!(RespFrame::needed_length(b"$5\r\nhel"), Some(11));
+        // an array as far as its first incomplete element
+        let buf = b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1048576\r\nxx";
+        assert_eq!(
+            RespFrame::needed_length(buf),
+            Some(4 + 9 + 7 + 1048576 + 12)
+        );
+        assert_eq!(RespFrame::needed_length(b"*2\r\n$3\r\nget\r\n"), Some(13));
+        assert_eq!(RespFrame::needed_length(b"*2\r\n$3\r\nget\r\n$"), Some(13));
+        // nothing to go on yet
+        assert_eq!(RespFrame::needed_length(b"$10"), None);
+        assert_eq!(RespFrame::needed_length(b""), None);
+    }
+
     #[test]
     fn test_simple_string_decode() -> Result<()> {
         let mut buf = BytesMut::new();
@@ -409,4 +716,270 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_from_bytes_simple_string() -> Result<()> {
+        let (frame, consumed) = RespFrame::from_bytes(b"+OK\r\n+next\r\n")?;
+        assert_eq!(frame, SimpleString::new("OK").into());
+        assert_eq!(consumed, 5);
+
+        let ret = RespFrame::from_bytes(b"+OK\r");
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_bytes_array() -> Result<()> {
+        let raw = b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n";
+        let (frame, consumed) = RespFrame::from_bytes(raw)?;
+        assert_eq!(
+            frame,
+            RespArray::new(vec![
+                BulkString::new("get").into(),
+                BulkString::new("hello").into()
+            ])
+            .into()
+        );
+        assert_eq!(consumed, raw.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_array_is_not_complete() {
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nget\r\n$5\r\nhel"[..]);
+        assert_eq!(
+            RespFrame::decode(&mut buf).unwrap_err(),
+            RespError::NotComplete
+        );
+        assert_eq!(buf.len(), 20);
+    }
+
+    #[test]
+    fn test_bad_headers_are_errors() {
+        let decode = |raw: &[u8]| RespFrame::decode(&mut BytesMut::from(raw));
+        for raw in [
+            &b"$abc\r\n"[..],
+            b"*xyz\r\n",
+            b":notanumber\r\n",
+            b"$1.5\r\n",
+        ] {
+            assert!(
+                matches!(decode(raw), Err(RespError::ParseIntError(_))),
+                "{:?}",
+                raw
+            );
+            // no amount of further input makes them valid
+            assert!(!matches!(
+                RespFrame::expect_length(raw),
+                Ok(_) | Err(RespError::NotComplete)
+            ));
+        }
+        for raw in [&b"*-5\r\n"[..], b"$-2\r\n", b"~-1\r\n"] {
+            assert!(
+                matches!(decode(raw), Err(RespError::InvalidFrameLength(_))),
+                "{:?}",
+                raw
+            );
+        }
+        // an unknown type inside an array
+        let raw = b"*2\r\n$3\r\nget\r\n!x\r\n";
+        assert!(matches!(decode(raw), Err(RespError::InvalidFrameType(_))));
+        assert!(matches!(
+            RespFrame::expect_length(raw),
+            Err(RespError::InvalidFrameType(_))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_newlines() -> Result<()> {
+        let lenient = DecodeConfig {
+            lenient_newlines: true,
+            ..Default::default()
+        };
+
+        let mut buf = BytesMut::from(&b"+OK\n"[..]);
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(matches!(
+            RespFrame::decode_with(&mut buf, &DecodeConfig::default()),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert_eq!(
+            RespFrame::decode_with(&mut buf, &lenient)?,
+            SimpleString::new("OK").into()
+        );
+        assert!(buf.is_empty());
+
+        // \r is optional line by line, a bulk string's data may hold a \n
+        let mut buf = BytesMut::from(&b"*3\n$3\r\nset\n$1\nk\r\n$3\na\nb\n:1\n"[..]);
+        let expected = RespArray::new(vec![
+            BulkString::new("set").into(),
+            BulkString::new("k").into(),
+            BulkString::new("a\nb").into(),
+        ]);
+        assert_eq!(RespFrame::decode_with(&mut buf, &lenient)?, expected.into());
+        assert_eq!(
+            RespFrame::decode_with(&mut buf, &lenient)?,
+            RespFrame::Integer(1)
+        );
+
+        let mut map = RespMap::new();
+        map.insert("k".to_string(), RespFrame::Null(RespNull));
+        let mut buf = BytesMut::from(&b"%1\n+k\n$-1\n"[..]);
+        assert_eq!(RespFrame::decode_with(&mut buf, &lenient)?, map.into());
+
+        // an incomplete frame is left alone until the rest arrives
+        let mut buf = BytesMut::from(&b"*2\n$3\nget\n$1\nk"[..]);
+        assert_eq!(
+            RespFrame::decode_with(&mut buf, &lenient),
+            Err(RespError::NotComplete)
+        );
+        assert_eq!(buf.len(), 14);
+        // the bulk string's data and a \n at least
+        assert_eq!(RespFrame::needed_length_with(&buf, &lenient), Some(15));
+        let mut buf = BytesMut::from(&b"$1\nkey\n"[..]);
+        assert!(matches!(
+            RespFrame::decode_with(&mut buf, &lenient),
+            Err(RespError::InvalidFrame(_))
+        ));
+        // lengths are bounded like in strict mode
+        let mut buf = BytesMut::from(&b"$18446744073709551615\n"[..]);
+        assert!(RespFrame::decode_with(&mut buf, &lenient).is_err());
+        let mut buf = BytesMut::from(&b"$9223372036854775807\nab"[..]);
+        assert_eq!(
+            RespFrame::decode_with(&mut buf, &lenient),
+            Err(RespError::NotComplete)
+        );
+
+        // crlf frames decode the same either way
+        let mut buf = BytesMut::from(&b"*1\r\n$4\r\nping\r\n"[..]);
+        let ping = RespArray::new(vec![BulkString::new("ping").into()]);
+        assert_eq!(RespFrame::decode_with(&mut buf, &lenient)?, ping.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resp2_nulls() -> Result<()> {
+        for raw in [&b"$-1\r\n"[..], b"*-1\r\n"] {
+            let mut buf = BytesMut::from(raw);
+            assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::Null(RespNull));
+            assert!(buf.is_empty());
+            assert_eq!(RespFrame::expect_length(raw)?, 5);
+        }
+        let mut buf = BytesMut::from(&b"*2\r\n$-1\r\n:1\r\n"[..]);
+        let expected = RespArray::new(vec![RespFrame::Null(RespNull), RespFrame::Integer(1)]);
+        assert_eq!(RespFrame::decode(&mut buf)?, expected.into());
+        assert_eq!(
+            RespFrame::decode(&mut BytesMut::from(&b"$-1\r"[..])),
+            Err(RespError::NotComplete)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_length_is_protocol_error() {
+        let mut buf = BytesMut::from(&b"$abc\r\nhello\r\n"[..]);
+        let err = RespFrame::decode(&mut buf).unwrap_err();
+        assert!(matches!(err, RespError::ParseIntError(_)));
+
+        let mut buf = BytesMut::from(&b"hello\r\n"[..]);
+        let err = RespFrame::decode(&mut buf).unwrap_err();
+        assert!(matches!(err, RespError::InvalidFrameType(_)));
+    }
+
+    #[test]
+    fn test_attribute_frame_is_skipped() -> Result<()> {
+        let mut buf = BytesMut::from(&b"|1\r\n+key-popularity\r\n:1\r\n+OK\r\n"[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, SimpleString::new("OK").into());
+        assert!(buf.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribute_waits_for_following_frame() -> Result<()> {
+        let input = b"|1\r\n+ttl\r\n:3600\r\n$5\r\nhel";
+        let mut buf = BytesMut::from(&input[..]);
+        assert_eq!(
+            RespFrame::decode(&mut buf).unwrap_err(),
+            RespError::NotComplete
+        );
+        // nothing is consumed until the whole reply has arrived
+        assert_eq!(buf.len(), input.len());
+        buf.extend_from_slice(b"lo\r\n");
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, BulkString::new("hello").into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribute_inside_array() -> Result<()> {
+        let input = b"*2\r\n|1\r\n+a\r\n#t\r\n:1\r\n:2\r\n";
+        let (frame, consumed) = RespFrame::from_bytes(input)?;
+        assert_eq!(frame, RespArray::new(vec![1.into(), 2.into()]).into());
+        assert_eq!(consumed, input.len());
+        Ok(())
+    }
+
+    fn nested_arrays(depth: usize) -> BytesMut {
+        let mut buf = BytesMut::new();
+        for _ in 0..depth {
+            buf.extend_from_slice(b"*1\r\n");
+        }
+        buf.extend_from_slice(b":1\r\n");
+        buf
+    }
+
+    #[test]
+    fn test_deeply_nested_array_is_rejected() {
+        let mut buf = nested_arrays(1_000_000);
+        let len = buf.len();
+        assert!(matches!(
+            RespFrame::expect_length(&buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert_eq!(buf.len(), len);
+        // only has to come back without overflowing the stack
+        RespFrame::needed_length(&buf);
+
+        // maps and sets count towards the same limit
+        let mut buf = BytesMut::new();
+        for _ in 0..MAX_NESTING_DEPTH {
+            buf.extend_from_slice(b"%1\r\n+k\r\n~1\r\n");
+        }
+        buf.extend_from_slice(b":1\r\n");
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+    }
+
+    #[test]
+    fn test_nesting_up_to_the_limit() -> Result<()> {
+        let mut buf = nested_arrays(MAX_NESTING_DEPTH);
+        RespFrame::decode(&mut buf)?;
+        assert!(buf.is_empty());
+
+        let mut buf = nested_arrays(MAX_NESTING_DEPTH + 1);
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+
+        // the limit can be lowered or raised per call
+        let mut buf = nested_arrays(3);
+        assert!(RespFrame::decode_with_max_depth(&mut buf, 2).is_err());
+        let mut buf = nested_arrays(MAX_NESTING_DEPTH + 1);
+        RespFrame::decode_with_max_depth(&mut buf, MAX_NESTING_DEPTH + 1)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,108 @@
+use crate::{BulkString, RespFrame};
+use std::fmt;
+
+// human readable rendering for logs, not a wire format
+impl fmt::Display for RespFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RespFrame::SimpleString(s) => write!(f, "{}", s.as_str()),
+            RespFrame::Error(e) => write!(f, "(error) {}", e.as_str()),
+            RespFrame::Integer(n) => write!(f, "{}", n),
+            RespFrame::BulkString(b) => write!(f, "{}", b),
+            RespFrame::Array(array) => {
+                write!(f, "[")?;
+                write_list(f, array.iter())?;
+                write!(f, "]")
+            }
+            RespFrame::Null(_) | RespFrame::NullArray(_) => write!(f, "(nil)"),
+            RespFrame::Boolean(b) => write!(f, "{}", b),
+            RespFrame::Double(d) => write!(f, "{}", **d),
+            RespFrame::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.entries().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", BulkString::new(key), value)?;
+                }
+                write!(f, "}}")
+            }
+            RespFrame::Set(set) => {
+                write!(f, "{{")?;
+                write_list(f, set.iter())?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// utf-8 is shown as text, anything else falls back to escaped bytes
+impl fmt::Display for BulkString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self) {
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => write!(f, "{}", self.escape_ascii()),
+        }
+    }
+}
+
+fn write_list<'a>(
+    f: &mut fmt::Formatter<'_>,
+    frames: impl Iterator<Item = &'a RespFrame>,
+) -> fmt::Result {
+    for (i, frame) in frames.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BulkString, Nf64, RespArray, RespFrame, RespMap, RespNull, RespSet, SimpleError,
+        SimpleString,
+    };
+
+    #[test]
+    fn test_scalar_display() {
+        let frame: RespFrame = SimpleString::new("OK").into();
+        assert_eq!(frame.to_string(), "OK");
+        let frame: RespFrame = SimpleError::new("ERR boom").into();
+        assert_eq!(frame.to_string(), "(error) ERR boom");
+        assert_eq!(RespFrame::Integer(-42).to_string(), "-42");
+        assert_eq!(RespFrame::Null(RespNull).to_string(), "(nil)");
+        assert_eq!(RespFrame::Boolean(true).to_string(), "true");
+        assert_eq!(RespFrame::Double(Nf64::new(1.5)).to_string(), "1.5");
+    }
+
+    #[test]
+    fn test_bulk_string_display() {
+        let frame: RespFrame = BulkString::new("hello").into();
+        assert_eq!(frame.to_string(), "hello");
+        let frame: RespFrame = BulkString::new(vec![b'a', 0xff, b'\n']).into();
+        assert_eq!(frame.to_string(), "a\\xff\\n");
+    }
+
+    #[test]
+    fn test_aggregate_display() {
+        let frame: RespFrame = RespArray::new(vec![
+            BulkString::new("get").into(),
+            RespFrame::Integer(1),
+            RespArray::new(vec![]).into(),
+        ])
+        .into();
+        assert_eq!(frame.to_string(), "[get, 1, []]");
+
+        let mut map = RespMap::new();
+        map.insert("a".to_string(), RespFrame::Integer(1));
+        map.insert("b".to_string(), RespFrame::Null(RespNull));
+        let frame: RespFrame = map.into();
+        assert_eq!(frame.to_string(), "{a: 1, b: (nil)}");
+
+        let frame: RespFrame = RespSet::new(vec![BulkString::new("x").into()]).into();
+        assert_eq!(frame.to_string(), "{x}");
+    }
+}
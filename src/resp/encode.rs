@@ -19,70 +19,131 @@
 */
 
 use crate::{
-    BulkString, Nf64, RespArray, RespEncode, RespMap, RespNull, RespSet, SimpleError, SimpleString,
+    BulkString, Nf64, ProtocolVersion, RespArray, RespEncode, RespFrame, RespMap, RespNull,
+    RespNullArray, RespSet, SimpleError, SimpleString,
 };
 
 const BUF_CAP: usize = 4096;
 
+impl RespFrame {
+    pub fn encode_for(&self, proto: ProtocolVersion) -> Vec<u8> {
+        match proto {
+            ProtocolVersion::Resp3 => self.encode(),
+            ProtocolVersion::Resp2 => self.encode_resp2(),
+        }
+    }
+
+    // RESP2 has no null/bool/double/map/set, fall back to the closest RESP2 types
+    fn encode_resp2(&self) -> Vec<u8> {
+        match self {
+            RespFrame::Null(null) => null.encode_v2(),
+            RespFrame::NullArray(_) => RespNull.encode_v2_array(),
+            RespFrame::Boolean(b) => (*b as i64).encode(),
+            RespFrame::Double(f) => BulkString::new(f.to_string()).encode(),
+            RespFrame::Array(array) => encode_resp2_array(array.len(), array.iter()),
+            RespFrame::Set(set) => encode_resp2_array(set.len(), set.iter()),
+            RespFrame::Map(map) => {
+                let mut buf = Vec::with_capacity(BUF_CAP);
+                buf.extend_from_slice(&format!("*{}\r\n", map.len() * 2).into_bytes());
+                for (key, value) in map.entries() {
+                    buf.extend_from_slice(&BulkString::new(key).encode());
+                    buf.extend_from_slice(&value.encode_resp2());
+                }
+                buf
+            }
+            frame => frame.encode(),
+        }
+    }
+}
+
+fn encode_resp2_array<'a>(len: usize, frames: impl Iterator<Item = &'a RespFrame>) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(BUF_CAP);
+    buf.extend_from_slice(&format!("*{}\r\n", len).into_bytes());
+    for frame in frames {
+        buf.extend_from_slice(&frame.encode_resp2());
+    }
+    buf
+}
+
 impl RespEncode for SimpleString {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         format!("+{}\r\n", self.0).into_bytes()
     }
 }
 
 impl RespEncode for SimpleError {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         format!("-{}\r\n", self.0).into_bytes()
     }
 }
 
 impl RespEncode for i64 {
-    fn encode(self) -> Vec<u8> {
-        let sign = if self < 0 { "" } else { "+" };
+    fn encode(&self) -> Vec<u8> {
+        let sign = if *self < 0 { "" } else { "+" };
         format!(":{}{}\r\n", sign, self).into_bytes()
     }
 }
 
 impl RespEncode for BulkString {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(self.len() + 16);
         buf.extend_from_slice(&format!("${}\r\n", self.len()).into_bytes());
-        buf.extend_from_slice(&self);
+        buf.extend_from_slice(self);
         buf.extend_from_slice(b"\r\n");
         buf
     }
 }
 
 impl RespEncode for RespArray {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(BUF_CAP);
         buf.extend_from_slice(&format!("*{}\r\n", self.0.len()).into_bytes());
-        for frame in self.0 {
+        for frame in self.iter() {
             buf.extend_from_slice(&frame.encode());
         }
         buf
     }
 }
 
+// encode is RESP3's null. RESP2 has two: the null bulk string for a missing
+// value, which is also what a nil element of an array is (MGET, HMGET, LPOS...),
+// and the null array for a reply that would have been an array, like BLPOP
+// timing out. RespFrame::Null is the bulk one, RespFrame::NullArray the other
 impl RespEncode for RespNull {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         b"_\r\n".to_vec()
     }
 }
 
+impl RespEncode for RespNullArray {
+    fn encode(&self) -> Vec<u8> {
+        RespNull.encode()
+    }
+}
+
+impl RespNull {
+    pub fn encode_v2(&self) -> Vec<u8> {
+        b"$-1\r\n".to_vec()
+    }
+
+    pub fn encode_v2_array(&self) -> Vec<u8> {
+        b"*-1\r\n".to_vec()
+    }
+}
+
 impl RespEncode for bool {
-    fn encode(self) -> Vec<u8> {
-        format!("#{}\r\n", if self { "t" } else { "f" }).into_bytes()
+    fn encode(&self) -> Vec<u8> {
+        format!("#{}\r\n", if *self { "t" } else { "f" }).into_bytes()
     }
 }
 
 impl RespEncode for f64 {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(32);
         let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
             format!(",{:+e}\r\n", self)
         } else {
-            let sign = if self < 0.0 { "" } else { "+" };
+            let sign = if *self < 0.0 { "" } else { "+" };
             format!(",{}{}\r\n", sign, self)
         };
 
@@ -92,7 +153,7 @@ impl RespEncode for f64 {
 }
 
 impl RespEncode for Nf64 {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(32);
         let ret = if self.0.abs() > 1e+8 || self.0.abs() < 1e-8 {
             format!(",{:+e}", self.0)
@@ -107,22 +168,31 @@ impl RespEncode for Nf64 {
 }
 
 impl RespEncode for RespMap {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(BUF_CAP);
         buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
-        for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
+        for (key, value) in self.entries() {
+            buf.extend_from_slice(&encode_map_key(key));
             buf.extend_from_slice(&value.encode());
         }
         buf
     }
 }
 
+// text keys go out as simple strings, anything a simple string can't carry, like
+// binary hash fields, as a bulk string
+fn encode_map_key(key: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(key) {
+        Ok(key) if !key.contains(['\r', '\n']) => SimpleString::new(key).encode(),
+        _ => BulkString::new(key).encode(),
+    }
+}
+
 impl RespEncode for RespSet {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(BUF_CAP);
         buf.extend_from_slice(&format!("~{}\r\n", self.len()).into_bytes());
-        for frame in self.0 {
+        for frame in self.iter() {
             buf.extend_from_slice(&frame.encode());
         }
         buf
@@ -132,11 +202,119 @@ impl RespEncode for RespSet {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::RespFrame;
 
     #[test]
     fn test_simple_string_encode() {
         let frame: RespFrame = SimpleString::new("OK".to_string()).into();
         assert_eq!(frame.encode(), b"+OK\r\n");
     }
+
+    #[test]
+    fn test_encode_by_reference() {
+        let frame: RespFrame = RespArray::new(vec![
+            BulkString::new("get").into(),
+            BulkString::new("hello").into(),
+        ])
+        .into();
+        let first = frame.encode();
+        let second = frame.encode();
+        assert_eq!(first, b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n");
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_integer_and_map_encode() {
+        let frame: RespFrame = (-123).into();
+        assert_eq!(frame.encode(), b":-123\r\n");
+        assert_eq!(frame.encode(), b":-123\r\n");
+
+        let mut map = RespMap::new();
+        map.insert("hello".to_string(), BulkString::new("world").into());
+        let frame: RespFrame = map.into();
+        assert_eq!(frame.encode(), b"%1\r\n+hello\r\n$5\r\nworld\r\n");
+        assert_eq!(frame.encode(), frame.encode());
+    }
+
+    #[test]
+    fn test_map_encode_for() {
+        let mut map = RespMap::new();
+        map.insert("hello".to_string(), BulkString::new("world").into());
+        map.insert("n".to_string(), RespFrame::Null(RespNull));
+        let frame: RespFrame = map.into();
+
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp3),
+            b"%2\r\n+hello\r\n$5\r\nworld\r\n+n\r\n_\r\n"
+        );
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp2),
+            b"*4\r\n$5\r\nhello\r\n$5\r\nworld\r\n$1\r\nn\r\n$-1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_map_keys() {
+        let mut map = RespMap::new();
+        map.insert(b"\xff".to_vec(), RespFrame::Integer(1));
+        map.insert("a\r\nb", RespFrame::Integer(2));
+        let frame: RespFrame = map.into();
+
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp3),
+            b"%2\r\n$4\r\na\r\nb\r\n:+2\r\n$1\r\n\xff\r\n:+1\r\n"
+        );
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp2),
+            b"*4\r\n$4\r\na\r\nb\r\n:+2\r\n$1\r\n\xff\r\n:+1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_null_encode_for() {
+        let frame = RespFrame::Null(RespNull);
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp3), b"_\r\n");
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp2), b"$-1\r\n");
+
+        assert_eq!(RespNull.encode(), b"_\r\n");
+        assert_eq!(RespNull.encode_v2(), b"$-1\r\n");
+        assert_eq!(RespNull.encode_v2_array(), b"*-1\r\n");
+
+        let frame = RespFrame::NullArray(RespNullArray);
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp3), b"_\r\n");
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp2), b"*-1\r\n");
+    }
+
+    #[test]
+    fn test_null_in_resp2_array() {
+        let frame: RespFrame = RespArray::new(vec![
+            BulkString::new("a").into(),
+            RespFrame::Null(RespNull),
+            RespArray::new(vec![RespFrame::Null(RespNull)]).into(),
+        ])
+        .into();
+        // every nil element is a null bulk string, nested or not
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp2),
+            b"*3\r\n$1\r\na\r\n$-1\r\n*1\r\n$-1\r\n"
+        );
+        assert_eq!(
+            frame.encode_for(ProtocolVersion::Resp3),
+            b"*3\r\n$1\r\na\r\n_\r\n*1\r\n_\r\n"
+        );
+    }
+
+    #[test]
+    fn test_scalar_encode_for() {
+        let frame: RespFrame = true.into();
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp3), b"#t\r\n");
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp2), b":+1\r\n");
+
+        let frame: RespFrame = Nf64::new(1.5).into();
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp3), b",+1.5\r\n");
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp2), b"$3\r\n1.5\r\n");
+
+        let frame: RespFrame = RespSet::new(vec![RespFrame::Integer(1)]).into();
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp3), b"~1\r\n:+1\r\n");
+        assert_eq!(frame.encode_for(ProtocolVersion::Resp2), b"*1\r\n:+1\r\n");
+    }
 }
@@ -0,0 +1,150 @@
+use crate::{BulkString, RespArray, RespError, RespFrame};
+
+// splits an inline command like redis' sdssplitargs: arguments are separated
+// by whitespace, and may be "double quoted" (with \n, \xHH, ... escapes) or
+// 'single quoted' (only \' is an escape)
+pub fn parse_inline(line: &[u8]) -> Result<RespArray, RespError> {
+    let mut args: Vec<RespFrame> = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(RespArray::new(args));
+        }
+
+        let mut arg = Vec::new();
+        match line[i] {
+            b'"' => i = double_quoted(line, i + 1, &mut arg)?,
+            b'\'' => i = single_quoted(line, i + 1, &mut arg)?,
+            _ => {
+                while i < line.len() && !line[i].is_ascii_whitespace() {
+                    arg.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+        args.push(BulkString::new(arg).into());
+    }
+}
+
+// returns the position after the closing quote
+fn double_quoted(line: &[u8], mut i: usize, arg: &mut Vec<u8>) -> Result<usize, RespError> {
+    loop {
+        match line.get(i) {
+            None => return Err(unbalanced()),
+            Some(b'"') => return closing_quote(line, i + 1),
+            Some(b'\\') if i + 3 < line.len() && line[i + 1] == b'x' => {
+                match hex_byte(line[i + 2], line[i + 3]) {
+                    Some(b) => {
+                        arg.push(b);
+                        i += 4;
+                    }
+                    None => {
+                        arg.push(b'x');
+                        i += 2;
+                    }
+                }
+            }
+            Some(b'\\') if i + 1 < line.len() => {
+                arg.push(match line[i + 1] {
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'b' => 0x08,
+                    b'a' => 0x07,
+                    c => c,
+                });
+                i += 2;
+            }
+            Some(c) => {
+                arg.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn single_quoted(line: &[u8], mut i: usize, arg: &mut Vec<u8>) -> Result<usize, RespError> {
+    loop {
+        match line.get(i) {
+            None => return Err(unbalanced()),
+            Some(b'\'') => return closing_quote(line, i + 1),
+            Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                arg.push(b'\'');
+                i += 2;
+            }
+            Some(c) => {
+                arg.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+// a closing quote has to end the argument
+fn closing_quote(line: &[u8], i: usize) -> Result<usize, RespError> {
+    match line.get(i) {
+        Some(c) if !c.is_ascii_whitespace() => Err(unbalanced()),
+        _ => Ok(i),
+    }
+}
+
+fn hex_byte(hi: u8, lo: u8) -> Option<u8> {
+    let digit = |c: u8| (c as char).to_digit(16);
+    Some((digit(hi)? * 16 + digit(lo)?) as u8)
+}
+
+fn unbalanced() -> RespError {
+    RespError::InvalidFrame("unbalanced quotes in request".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn bulks(items: &[&[u8]]) -> RespArray {
+        RespArray::new(
+            items
+                .iter()
+                .map(|v| BulkString::new(*v).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_parse_inline_plain() -> Result<()> {
+        let args = parse_inline(b"set  key\tvalue")?;
+        assert_eq!(args, bulks(&[b"set", b"key", b"value"]));
+        assert_eq!(parse_inline(b"   ")?, bulks(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_inline_quoted() -> Result<()> {
+        let args = parse_inline(br#"set "hello world" 'it\'s' "a\"b\n\x41""#)?;
+        assert_eq!(args, bulks(&[b"set", b"hello world", b"it's", b"a\"b\nA"]));
+        assert_eq!(parse_inline(br#"echo """#)?, bulks(&[b"echo", b""]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_inline_unbalanced() {
+        assert!(matches!(
+            parse_inline(br#"set "hello world"#),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(matches!(
+            parse_inline(b"set 'key"),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(matches!(
+            parse_inline(br#"set "key"value"#),
+            Err(RespError::InvalidFrame(_))
+        ));
+    }
+}
@@ -1,16 +1,23 @@
 mod decode;
+mod display;
 mod encode;
+mod inline;
+mod pretty;
+
+pub use decode::{DecodeConfig, MAX_NESTING_DEPTH};
+pub use inline::parse_inline;
+pub use pretty::pretty_print;
 
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
 use std::collections::BTreeMap;
 use std::hash::Hash;
-use std::ops::{Deref, DerefMut};
+use std::ops::Deref;
 use thiserror::Error;
 
 #[enum_dispatch]
 pub trait RespEncode {
-    fn encode(self) -> Vec<u8>;
+    fn encode(&self) -> Vec<u8>;
 }
 pub trait RespDecode: Sized {
     const PREFIX: &'static str;
@@ -18,6 +25,14 @@ pub trait RespDecode: Sized {
     fn expect_length(buf: &[u8]) -> Result<usize, RespError>;
 }
 
+// RESP2 is what clients speak until they negotiate RESP3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum RespError {
     #[error("Invalid frame: {0}")]
@@ -45,6 +60,8 @@ pub enum RespFrame {
     BulkString(BulkString),
     Array(RespArray),
     Null(RespNull),
+    // a null in place of an array reply, see RespNullArray
+    NullArray(RespNullArray),
 
     Boolean(bool),
     Double(Nf64),
@@ -68,12 +85,24 @@ pub struct BulkString(pub(crate) Vec<u8>);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RespNull;
 
+// RESP3 has one null, RESP2 a second one for replies that would have been an
+// array, like BLPOP timing out
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RespNullArray;
+
 // argument extra need access the value inner
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RespArray(pub(crate) Vec<RespFrame>);
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct RespMap(BTreeMap<String, RespFrame>);
+// sorted by key, unless built with insertion_ordered, then the second field
+// remembers the order keys were first inserted in. keys are bytes, so binary
+// hash fields fit. changes go through insert and remove, which keep the order
+#[derive(Debug, Clone)]
+pub struct RespMap(BTreeMap<Vec<u8>, RespFrame>, Option<Vec<Vec<u8>>>);
+
+// collects reply elements without spelling out every `.into()`
+#[derive(Debug, Default)]
+pub struct RespArrayBuilder(Vec<RespFrame>);
 
 #[derive(Debug, Clone)]
 pub struct RespSet(Vec<RespFrame>);
@@ -107,18 +136,12 @@ impl Deref for RespArray {
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = BTreeMap<Vec<u8>, RespFrame>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl DerefMut for RespMap {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
 impl Deref for RespSet {
     type Target = Vec<RespFrame>;
 
@@ -159,9 +182,86 @@ impl RespArray {
     }
 }
 
+impl RespArrayBuilder {
+    pub fn new() -> Self {
+        RespArrayBuilder(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        RespArrayBuilder(Vec::with_capacity(capacity))
+    }
+
+    pub fn push_bulk(&mut self, s: impl Into<Vec<u8>>) -> &mut Self {
+        self.0.push(BulkString::new(s).into());
+        self
+    }
+
+    pub fn push_int(&mut self, n: i64) -> &mut Self {
+        self.0.push(RespFrame::Integer(n));
+        self
+    }
+
+    pub fn push_null(&mut self) -> &mut Self {
+        self.0.push(RespFrame::Null(RespNull));
+        self
+    }
+
+    pub fn push_frame(&mut self, frame: impl Into<RespFrame>) -> &mut Self {
+        self.0.push(frame.into());
+        self
+    }
+
+    // takes the collected elements, leaving the builder empty
+    pub fn build(&mut self) -> RespArray {
+        RespArray(std::mem::take(&mut self.0))
+    }
+}
+
 impl RespMap {
     pub fn new() -> Self {
-        RespMap(BTreeMap::new())
+        RespMap(BTreeMap::new(), None)
+    }
+
+    // a map that encodes its entries in the order they were inserted
+    pub fn insertion_ordered() -> Self {
+        RespMap(BTreeMap::new(), Some(Vec::new()))
+    }
+
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: RespFrame) -> Option<RespFrame> {
+        let key = key.into();
+        if let Some(order) = &mut self.1 {
+            if !self.0.contains_key(&key) {
+                order.push(key.clone());
+            }
+        }
+        self.0.insert(key, value)
+    }
+
+    // the entries in encoding order: sorted, or as inserted
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8], &RespFrame)> {
+        let ordered = self
+            .1
+            .iter()
+            .flatten()
+            .filter_map(|key| self.0.get_key_value(key));
+        let sorted = self.1.is_none().then(|| self.0.iter());
+        ordered
+            .chain(sorted.into_iter().flatten())
+            .map(|(key, value)| (key.as_slice(), value))
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<RespFrame> {
+        let key = key.as_ref();
+        let value = self.0.remove(key)?;
+        if let Some(order) = &mut self.1 {
+            order.retain(|k| k.as_slice() != key);
+        }
+        Some(value)
+    }
+
+    // takes text keys as well as bytes
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<&RespFrame> {
+        self.0.get(key.as_ref())
     }
 }
 
@@ -183,6 +283,34 @@ impl Nf64 {
     }
 }
 
+impl RespFrame {
+    // like ==, but arrays compare as multisets at every level, for replies built from
+    // unordered collections such as SMEMBERS or a flat HGETALL. only elements are
+    // matched, a field is not kept together with its value
+    pub fn eq_unordered(&self, other: &RespFrame) -> bool {
+        self.canonical() == other.canonical()
+    }
+
+    fn canonical(&self) -> RespFrame {
+        match self {
+            RespFrame::Array(array) => {
+                let mut elements: Vec<RespFrame> = array.iter().map(Self::canonical).collect();
+                elements.sort();
+                RespArray(elements).into()
+            }
+            RespFrame::Map(map) => RespMap(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), v.canonical()))
+                    .collect(),
+                None,
+            )
+            .into(),
+            RespFrame::Set(set) => RespSet(set.iter().map(Self::canonical).collect()).into(),
+            frame => frame.clone(),
+        }
+    }
+}
+
 // from
 impl From<&str> for SimpleString {
     fn from(value: &str) -> Self {
@@ -269,9 +397,13 @@ impl PartialOrd for Nf64 {
         Some(self.cmp(other))
     }
 }
+// consistent with eq: NaN equals NaN and sorts after every number
 impl Ord for Nf64 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        match self.0.partial_cmp(&other.0) {
+            Some(ordering) => ordering,
+            None => self.0.is_nan().cmp(&other.0.is_nan()),
+        }
     }
 }
 
@@ -285,6 +417,29 @@ impl Hash for Nf64 {
     }
 }
 
+// Eq : RespMap, the same entries are equal whichever order they encode in
+impl PartialEq for RespMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl PartialOrd for RespMap {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for RespMap {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+impl Eq for RespMap {}
+impl Hash for RespMap {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
 // Eq : RespSet
 impl PartialEq for RespSet {
     fn eq(&self, other: &Self) -> bool {
@@ -321,3 +476,100 @@ impl Hash for RespSet {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resp_array_builder() {
+        let array = RespArrayBuilder::new()
+            .push_bulk("message")
+            .push_int(42)
+            .push_null()
+            .push_frame(SimpleString::new("OK"))
+            .build();
+        let expected = RespArray::new(vec![
+            BulkString::new("message").into(),
+            RespFrame::Integer(42),
+            RespFrame::Null(RespNull),
+            SimpleString::new("OK").into(),
+        ]);
+        assert_eq!(array, expected);
+    }
+
+    fn bulks(values: &[&str]) -> RespFrame {
+        let frames: Vec<RespFrame> = values.iter().map(|v| BulkString::new(*v).into()).collect();
+        RespArray::new(frames).into()
+    }
+
+    #[test]
+    fn test_eq_unordered() {
+        let a = bulks(&["f1", "v1", "f2", "v2"]);
+        let b = bulks(&["f2", "v2", "f1", "v1"]);
+        assert_ne!(a, b);
+        assert!(a.eq_unordered(&b));
+        assert!(!a.eq_unordered(&bulks(&["f1", "v1", "f2"])));
+        // multisets, duplicates have to match up too
+        assert!(!bulks(&["a", "a", "b"]).eq_unordered(&bulks(&["a", "b", "b"])));
+    }
+
+    #[test]
+    fn test_eq_unordered_nested() {
+        let a: RespFrame = RespArray::new(vec![bulks(&["x", "y"]), RespFrame::Integer(1)]).into();
+        let b: RespFrame = RespArray::new(vec![RespFrame::Integer(1), bulks(&["y", "x"])]).into();
+        assert_ne!(a, b);
+        assert!(a.eq_unordered(&b));
+
+        let mut map = RespMap::new();
+        map.insert("k".to_string(), bulks(&["1", "2"]));
+        let mut other = RespMap::new();
+        other.insert("k".to_string(), bulks(&["2", "1"]));
+        assert!(RespFrame::from(map).eq_unordered(&other.into()));
+
+        assert!(RespFrame::Integer(1).eq_unordered(&RespFrame::Integer(1)));
+        assert!(!RespFrame::Integer(1).eq_unordered(&RespFrame::Integer(2)));
+    }
+
+    #[test]
+    fn test_resp_array_builder_reuse() {
+        let mut builder = RespArrayBuilder::with_capacity(2);
+        builder.push_bulk(b"a".to_vec());
+        assert_eq!(builder.build(), RespArray::new(vec![b"a".into()]));
+        assert_eq!(builder.build(), RespArray::new(vec![]));
+    }
+
+    #[test]
+    fn test_resp_map_ordering() {
+        let mut sorted = RespMap::new();
+        let mut ordered = RespMap::insertion_ordered();
+        for map in [&mut sorted, &mut ordered] {
+            map.insert("c".to_string(), RespFrame::Integer(1));
+            map.insert("a".to_string(), RespFrame::Integer(2));
+            map.insert("b".to_string(), RespFrame::Integer(3));
+            // replacing a value doesn't move the key
+            map.insert("c".to_string(), RespFrame::Integer(4));
+        }
+
+        let keys = |map: &RespMap| map.entries().map(|(k, _)| k.to_vec()).collect::<Vec<_>>();
+        assert_eq!(keys(&sorted), [b"a", b"b", b"c"]);
+        assert_eq!(keys(&ordered), [b"c", b"a", b"b"]);
+        assert_eq!(ordered.get("c"), Some(&RespFrame::Integer(4)));
+        assert_eq!(
+            ordered.encode(),
+            b"%3\r\n+c\r\n:+4\r\n+a\r\n:+2\r\n+b\r\n:+3\r\n"
+        );
+        // the order doesn't take part in equality
+        assert_eq!(sorted, ordered);
+
+        // a removed key goes to the back when it comes again, and only once
+        assert_eq!(ordered.remove("c"), Some(RespFrame::Integer(4)));
+        assert_eq!(ordered.remove("c"), None);
+        ordered.insert("c", RespFrame::Integer(5));
+        assert_eq!(keys(&ordered), [b"a", b"b", b"c"]);
+        assert_eq!(
+            ordered.encode(),
+            b"%3\r\n+a\r\n:+2\r\n+b\r\n:+3\r\n+c\r\n:+5\r\n"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{RespError, RespFrame};
+use std::fmt::Write;
+
+// renders captured traffic as one indented tree per frame, for debugging the
+// protocol. a trailing partial frame, or bytes that don't decode, end the output
+// with a note on how much was left
+pub fn pretty_print(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        match RespFrame::from_bytes(rest) {
+            Ok((frame, consumed)) => {
+                write_frame(&mut out, &frame, 0, "");
+                rest = &rest[consumed..];
+            }
+            Err(RespError::NotComplete) => {
+                let _ = writeln!(out, "(incomplete frame, {} bytes)", rest.len());
+                break;
+            }
+            Err(e) => {
+                let _ = writeln!(out, "(invalid frame, {} bytes left: {})", rest.len(), e);
+                break;
+            }
+        }
+    }
+    out
+}
+
+// each level is indented by two more spaces, map values follow their key
+fn write_frame(out: &mut String, frame: &RespFrame, depth: usize, label: &str) {
+    let header = match frame {
+        RespFrame::SimpleString(s) => format!("simple {:?}", s.as_str()),
+        RespFrame::Error(e) => format!("error {:?}", e.as_str()),
+        RespFrame::Integer(n) => format!("integer {}", n),
+        RespFrame::BulkString(b) => format!("bulk \"{}\"", b.escape_ascii()),
+        RespFrame::Null(_) | RespFrame::NullArray(_) => "null".to_string(),
+        RespFrame::Boolean(b) => format!("boolean {}", b),
+        RespFrame::Double(d) => format!("double {}", **d),
+        RespFrame::Array(array) => format!("array ({})", array.len()),
+        RespFrame::Set(set) => format!("set ({})", set.len()),
+        RespFrame::Map(map) => format!("map ({})", map.len()),
+    };
+    let _ = writeln!(out, "{}{}{}", "  ".repeat(depth), label, header);
+    match frame {
+        RespFrame::Array(array) => array
+            .iter()
+            .for_each(|frame| write_frame(out, frame, depth + 1, "")),
+        RespFrame::Set(set) => set
+            .iter()
+            .for_each(|frame| write_frame(out, frame, depth + 1, "")),
+        RespFrame::Map(map) => {
+            for (key, value) in map.entries() {
+                write_frame(
+                    out,
+                    value,
+                    depth + 1,
+                    &format!("\"{}\" => ", key.escape_ascii()),
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_print_array() {
+        let bytes = b"*3\r\n$3\r\nset\r\n*2\r\n:1\r\n$-1\r\n+OK\r\n+PONG\r\n";
+        let expected = "\
+array (3)
+  bulk \"set\"
+  array (2)
+    integer 1
+    null
+  simple \"OK\"
+simple \"PONG\"
+";
+        assert_eq!(pretty_print(bytes), expected);
+    }
+
+    #[test]
+    fn test_pretty_print_map() {
+        let bytes = b"%2\r\n+a\r\n*1\r\n#t\r\n+b\r\n%1\r\n+c\r\n,1.5\r\n";
+        let expected = "\
+map (2)
+  \"a\" => array (1)
+    boolean true
+  \"b\" => map (1)
+    \"c\" => double 1.5
+";
+        assert_eq!(pretty_print(bytes), expected);
+    }
+
+    #[test]
+    fn test_pretty_print_stops_at_partial_frame() {
+        let bytes = b":1\r\n*2\r\n$3\r\nget\r\n$1\r\n";
+        assert_eq!(
+            pretty_print(bytes),
+            "integer 1\n(incomplete frame, 17 bytes)\n"
+        );
+        assert!(pretty_print(b"?x\r\n").starts_with("(invalid frame, 4 bytes left: "));
+        assert_eq!(pretty_print(b""), "");
+    }
+}
@@ -0,0 +1,49 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use zredis::{network, Backend};
+
+pub async fn start_server() -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?.to_string();
+    // one backend for the whole server, every connection gets a clone of the handle
+    let backend = Backend::new();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let cloned_backend = backend.clone();
+            tokio::spawn(async move { network::stream_handler(stream, cloned_backend).await });
+        }
+    });
+    Ok(addr)
+}
+
+// encode the arguments as a RESP array of bulk strings
+pub fn command(args: &[&str]) -> Vec<u8> {
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+    }
+    buf
+}
+
+pub async fn read_reply(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut buf = vec![0; 4096];
+    let n = stream.read(&mut buf).await?;
+    buf.truncate(n);
+    Ok(buf)
+}
+
+// replies may be split across reads, wait for exactly `len` bytes
+pub async fn read_exact_reply(stream: &mut TcpStream, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+pub async fn request(stream: &mut TcpStream, args: &[&str]) -> Result<Vec<u8>> {
+    stream.write_all(&command(args)).await?;
+    read_reply(stream).await
+}
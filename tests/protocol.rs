@@ -0,0 +1,53 @@
+mod common;
+
+use anyhow::Result;
+use common::{read_reply, request, start_server};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn test_garbage_closes_connection() -> Result<()> {
+    let addr = start_server().await?;
+    let mut stream = TcpStream::connect(&addr).await?;
+
+    // an inline request with an unbalanced quote
+    stream.write_all(b"get \"k\r\n").await?;
+    let ret = read_reply(&mut stream).await?;
+    assert!(ret.starts_with(b"-ERR Protocol error: "));
+    assert!(ret.ends_with(b"\r\n"));
+
+    // the server hangs up after reporting the error
+    let ret = read_reply(&mut stream).await?;
+    assert!(ret.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_bulk_length_closes_connection() -> Result<()> {
+    let addr = start_server().await?;
+    let mut stream = TcpStream::connect(&addr).await?;
+
+    stream.write_all(b"*1\r\n$abc\r\nget\r\n").await?;
+    let ret = read_reply(&mut stream).await?;
+    assert!(ret.starts_with(b"-ERR Protocol error: "));
+    let ret = read_reply(&mut stream).await?;
+    assert!(ret.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_frame_keeps_connection_open() -> Result<()> {
+    let addr = start_server().await?;
+    let mut stream = TcpStream::connect(&addr).await?;
+
+    stream.write_all(b"*3\r\n$3\r\nset\r\n$5\r\nhel").await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(b"lo\r\n$5\r\nworld\r\n").await?;
+    let ret = read_reply(&mut stream).await?;
+    assert_eq!(ret, b"+OK\r\n");
+
+    let ret = request(&mut stream, &["get", "hello"]).await?;
+    assert_eq!(ret, b"$5\r\nworld\r\n");
+    Ok(())
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use anyhow::Result;
+use common::{command, read_exact_reply, request, start_server};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn test_subscribe_and_receive_message() -> Result<()> {
+    let addr = start_server().await?;
+    let mut subscriber = TcpStream::connect(&addr).await?;
+    let mut publisher = TcpStream::connect(&addr).await?;
+
+    subscriber
+        .write_all(&command(&["subscribe", "news", "sports"]))
+        .await?;
+    let expected = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:+1\r\n\
+        *3\r\n$9\r\nsubscribe\r\n$6\r\nsports\r\n:+2\r\n";
+    let ret = read_exact_reply(&mut subscriber, expected.len()).await?;
+    assert_eq!(ret, expected);
+
+    let ret = request(&mut publisher, &["publish", "news", "hello"]).await?;
+    assert_eq!(ret, b":+1\r\n");
+
+    let expected = b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n";
+    let ret = read_exact_reply(&mut subscriber, expected.len()).await?;
+    assert_eq!(ret, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_psubscribe_receives_pmessage() -> Result<()> {
+    let addr = start_server().await?;
+    let mut subscriber = TcpStream::connect(&addr).await?;
+    let mut publisher = TcpStream::connect(&addr).await?;
+
+    subscriber
+        .write_all(&command(&["psubscribe", "news.*"]))
+        .await?;
+    let expected = b"*3\r\n$10\r\npsubscribe\r\n$6\r\nnews.*\r\n:+1\r\n";
+    let ret = read_exact_reply(&mut subscriber, expected.len()).await?;
+    assert_eq!(ret, expected);
+
+    let ret = request(&mut publisher, &["publish", "news.tech", "rust"]).await?;
+    assert_eq!(ret, b":+1\r\n");
+
+    let expected = b"*4\r\n$8\r\npmessage\r\n$6\r\nnews.*\r\n$9\r\nnews.tech\r\n$4\r\nrust\r\n";
+    let ret = read_exact_reply(&mut subscriber, expected.len()).await?;
+    assert_eq!(ret, expected);
+
+    Ok(())
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use anyhow::Result;
+use common::{request, start_server};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn test_data_shared_across_connections() -> Result<()> {
+    let addr = start_server().await?;
+    let mut writer = TcpStream::connect(&addr).await?;
+    let mut reader = TcpStream::connect(&addr).await?;
+
+    let ret = request(&mut writer, &["set", "hello", "world"]).await?;
+    assert_eq!(ret, b"+OK\r\n");
+
+    let ret = request(&mut reader, &["get", "hello"]).await?;
+    assert_eq!(ret, b"$5\r\nworld\r\n");
+
+    Ok(())
+}